@@ -1,11 +1,443 @@
-#[derive(Debug, Clone)]
+use std::{
+    fs,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use dav_server::DavMethod;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::gofile;
+
+const DEFAULT_PORT: u16 = 4914;
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_MAX_RETRIES: u32 = 10;
+const DEFAULT_RETRY_MIN_MS: u64 = 500;
+const DEFAULT_RETRY_MAX_MS: u64 = 20_000;
+const DEFAULT_CACHE_MAX_MB: u64 = 1024;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub root_id: Option<String>,
+    pub roots: Vec<RootMount>,
     pub api_token: Option<String>,
     pub port: u16,
     pub host: String,
+    pub unix_socket: Option<PathBuf>,
+    pub unix_socket_mode: Option<u32>,
     pub user_agent: Option<String>,
+    pub proxy: Option<String>,
+    pub connect_timeout_secs: u64,
+    pub request_timeout_secs: u64,
+    pub pool_max_idle: Option<usize>,
+    pub pool_idle_timeout_secs: Option<u64>,
+    pub operation_timeout_secs: Option<u64>,
+    pub write_idle_timeout_secs: Option<u64>,
     pub bypass: bool,
+    pub require_bypass: bool,
+    pub bypass_url: Option<String>,
+    pub bypass_broken_hosts: Option<Vec<String>>,
     pub password: Option<String>,
+    pub password_raw: bool,
+    pub folder_passwords: Vec<FolderPassword>,
+    #[serde(rename = "mode", deserialize_with = "deserialize_mode")]
     pub write_enabled: bool,
+    pub dir_cache_size: Option<NonZeroUsize>,
+    pub folder_concurrency: Option<usize>,
+    pub max_concurrent_listings: Option<usize>,
+    pub page_size: Option<u64>,
+    pub prefetch_depth: usize,
+    pub prewarm: bool,
+    pub max_retries: u32,
+    pub retry_min_ms: u64,
+    pub retry_max_ms: u64,
+    pub cache_dir: Option<PathBuf>,
+    pub cache_max_mb: u64,
+    pub cache_file: Option<PathBuf>,
+    pub verify_md5: bool,
+    pub max_download_rate: Option<u64>,
+    pub max_file_size: Option<u64>,
+    pub parallel_download: Option<usize>,
+    pub download_reconnects: Option<u32>,
+    pub read_ahead: Option<usize>,
+    pub upload_buffer: Option<usize>,
+    pub allow_recursive_delete: bool,
+    pub no_overwrite: bool,
+    pub create_parents: bool,
+    pub trash_folder: Option<String>,
+    pub dry_run: bool,
+    pub force_write: bool,
+    pub case_insensitive: bool,
+    pub frozen_strategy: FrozenStrategy,
+    pub token_store: Option<PathBuf>,
+    pub dav_user: Option<String>,
+    pub dav_password: Option<String>,
+    pub root_redirect: Option<String>,
+    pub cache_control: Option<String>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub shutdown_timeout_secs: u64,
+    pub workers: Option<usize>,
+    pub max_connections: Option<usize>,
+    pub probes: bool,
+    pub metrics: bool,
+    pub json_api: bool,
+    #[serde(deserialize_with = "deserialize_methods")]
+    pub enabled_methods: Vec<DavMethod>,
+    #[serde(deserialize_with = "deserialize_methods")]
+    pub disabled_methods: Vec<DavMethod>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            root_id: None,
+            roots: Vec::new(),
+            api_token: None,
+            port: DEFAULT_PORT,
+            host: DEFAULT_HOST.to_string(),
+            unix_socket: None,
+            unix_socket_mode: None,
+            user_agent: None,
+            proxy: None,
+            connect_timeout_secs: DEFAULT_CONNECT_TIMEOUT_SECS,
+            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+            pool_max_idle: None,
+            pool_idle_timeout_secs: None,
+            operation_timeout_secs: None,
+            write_idle_timeout_secs: None,
+            bypass: false,
+            require_bypass: false,
+            bypass_url: None,
+            bypass_broken_hosts: None,
+            password: None,
+            password_raw: false,
+            folder_passwords: Vec::new(),
+            write_enabled: false,
+            dir_cache_size: None,
+            folder_concurrency: None,
+            max_concurrent_listings: None,
+            page_size: None,
+            prefetch_depth: 0,
+            prewarm: false,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_min_ms: DEFAULT_RETRY_MIN_MS,
+            retry_max_ms: DEFAULT_RETRY_MAX_MS,
+            cache_dir: None,
+            cache_max_mb: DEFAULT_CACHE_MAX_MB,
+            cache_file: None,
+            verify_md5: false,
+            max_download_rate: None,
+            max_file_size: None,
+            parallel_download: None,
+            download_reconnects: None,
+            read_ahead: None,
+            upload_buffer: None,
+            allow_recursive_delete: false,
+            no_overwrite: false,
+            create_parents: false,
+            trash_folder: None,
+            dry_run: false,
+            force_write: false,
+            case_insensitive: false,
+            frozen_strategy: FrozenStrategy::Hide,
+            token_store: None,
+            dav_user: None,
+            dav_password: None,
+            root_redirect: None,
+            cache_control: None,
+            tls_cert: None,
+            tls_key: None,
+            shutdown_timeout_secs: DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+            workers: None,
+            max_connections: None,
+            probes: false,
+            metrics: false,
+            json_api: false,
+            enabled_methods: Vec::new(),
+            disabled_methods: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    ReadOnly,
+    ReadWrite,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    Get,
+    Head,
+    Options,
+    Propfind,
+    Put,
+    Patch,
+    Delete,
+    Mkcol,
+    Copy,
+    Move,
+    Proppatch,
+    Lock,
+    Unlock,
+}
+
+impl From<Method> for DavMethod {
+    fn from(method: Method) -> Self {
+        match method {
+            Method::Get => DavMethod::Get,
+            Method::Head => DavMethod::Head,
+            Method::Options => DavMethod::Options,
+            Method::Propfind => DavMethod::PropFind,
+            Method::Put => DavMethod::Put,
+            Method::Patch => DavMethod::Patch,
+            Method::Delete => DavMethod::Delete,
+            Method::Mkcol => DavMethod::MkCol,
+            Method::Copy => DavMethod::Copy,
+            Method::Move => DavMethod::Move,
+            Method::Proppatch => DavMethod::PropPatch,
+            Method::Lock => DavMethod::Lock,
+            Method::Unlock => DavMethod::Unlock,
+        }
+    }
+}
+
+/// How listings treat files frozen by gofile.io
+#[derive(Clone, Copy, Debug, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrozenStrategy {
+    #[default]
+    Hide,
+    Show,
+    Placeholder,
+}
+
+impl From<FrozenStrategy> for gofile::FrozenStrategy {
+    fn from(strategy: FrozenStrategy) -> Self {
+        match strategy {
+            FrozenStrategy::Hide => gofile::FrozenStrategy::Hide,
+            FrozenStrategy::Show => gofile::FrozenStrategy::Show,
+            FrozenStrategy::Placeholder => gofile::FrozenStrategy::Placeholder,
+        }
+    }
+}
+
+fn deserialize_mode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(matches!(Mode::deserialize(deserializer)?, Mode::ReadWrite))
+}
+
+fn deserialize_methods<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<DavMethod>, D::Error> {
+    let methods = Vec::<Method>::deserialize(deserializer)?;
+    Ok(methods.into_iter().map(Into::into).collect())
+}
+
+/// A Gofile folder served as a top-level directory, given as `<name>=<id>` on the command line
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RootMount {
+    pub name: String,
+    pub id: String,
+}
+
+impl FromStr for RootMount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, id) = s
+            .split_once('=')
+            .context("expected <name>=<id>, e.g. media=Veil7n")?;
+
+        let (name, id) = (name.trim(), id.trim());
+        if name.is_empty() || name.contains('/') {
+            anyhow::bail!("invalid mount name {name:?}");
+        }
+
+        if id.is_empty() {
+            anyhow::bail!("folder id must not be empty");
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            id: id.to_string(),
+        })
+    }
+}
+
+/// Password of a single folder, given as `<id>=<password>` on the command line
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FolderPassword {
+    /// Folder id or code
+    pub id: String,
+    pub password: String,
+}
+
+impl FromStr for FolderPassword {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, password) = s
+            .split_once('=')
+            .context("expected <id>=<password>, e.g. Veil7n=secret")?;
+
+        let id = id.trim();
+        if id.is_empty() {
+            anyhow::bail!("folder id must not be empty");
+        }
+
+        Ok(Self {
+            id: id.to_string(),
+            password: password.to_string(),
+        })
+    }
+}
+
+/// Describes a Gofile folder exposed by the server
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MountInfo {
+    /// Top-level directory name, empty when the folder is served as the root
+    pub name: String,
+    /// Folder id or code, `None` for the account's root folder
+    pub target: Option<String>,
+    pub mode: &'static str,
+}
+
+impl Config {
+    /// Loads a TOML config file. Missing keys fall back to the defaults.
+    ///
+    /// Passwords are kept as written in the file, they are hashed once CLI overrides are applied.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Config> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+
+        toml::from_str(&content)
+            .with_context(|| format!("failed to parse config file {}", path.display()))
+    }
+
+    /// One entry per `roots` item, or a single unnamed mount of `root_id` when none are set
+    pub fn mounts(&self) -> Vec<MountInfo> {
+        if self.roots.is_empty() {
+            return vec![MountInfo {
+                name: String::new(),
+                target: self.root_id.clone(),
+                mode: self.mode_name(),
+            }];
+        }
+
+        self.roots
+            .iter()
+            .map(|root| MountInfo {
+                name: root.name.clone(),
+                target: Some(root.id.clone()),
+                mode: self.mode_name(),
+            })
+            .collect()
+    }
+
+    /// JSON listing of the configured mounts, served as `/.mounts.json`
+    pub fn mounts_manifest(&self) -> serde_json::Result<Vec<u8>> {
+        serde_json::to_vec_pretty(&self.mounts())
+    }
+
+    fn mode_name(&self) -> &'static str {
+        if self.write_enabled {
+            "read-write"
+        } else {
+            "read-only"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_config_from_toml() {
+        let config: Config = toml::from_str(
+            r#"
+            root_id = "Veil7n"
+            port = 8080
+            mode = "read-write"
+            disabled_methods = ["delete", "proppatch"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.root_id.as_deref(), Some("Veil7n"));
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.host, DEFAULT_HOST);
+        assert!(config.write_enabled);
+        assert_eq!(
+            config.disabled_methods,
+            [DavMethod::Delete, DavMethod::PropPatch]
+        );
+    }
+
+    #[test]
+    fn test_config_rejects_unknown_keys() {
+        assert!(toml::from_str::<Config>("prot = 8080").is_err());
+    }
+
+    #[test]
+    fn test_mounts_manifest() {
+        let config = Config {
+            root_id: Some("Veil7n".to_string()),
+            write_enabled: true,
+            ..Config::default()
+        };
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&config.mounts_manifest().unwrap()).unwrap();
+
+        assert_eq!(
+            manifest,
+            json!([{ "name": "", "target": "Veil7n", "mode": "read-write" }])
+        );
+    }
+
+    #[test]
+    fn test_roots_from_toml() {
+        let config: Config = toml::from_str(
+            r#"
+            [[roots]]
+            name = "projects"
+            id = "Veil7n"
+
+            [[roots]]
+            name = "media"
+            id = "6c9e22a7-7d6c-4986-8e93-b118558be0bb"
+            "#,
+        )
+        .unwrap();
+
+        let mounts = config.mounts();
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0].name, "projects");
+        assert_eq!(mounts[0].target.as_deref(), Some("Veil7n"));
+        assert_eq!(mounts[1].name, "media");
+        assert_eq!(mounts[1].mode, "read-only");
+    }
+
+    #[test]
+    fn test_invalid_root_mount() {
+        for input in ["media", "=Veil7n", "media=", "a/b=Veil7n"] {
+            assert!(input.parse::<RootMount>().is_err(), "{input}");
+        }
+    }
 }
@@ -1,52 +1,123 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use super::{
     error::{GofileError, GofileResult},
+    health::Health,
+    maintenance::{self, MaintenanceStrategy, TransientStrategy},
+    metrics,
     model::{
         AccountInfo, AccountInfoResponse, Attribute, BypassFiles, BypassFilesResponse, Contents,
         ContentsOk, ContentsRestricted, ContentsUdpated, ContentsUdpatedResponse,
         ContentsWithPassword, ContentsWithPasswordResponse, CreateFolderPayload,
         CreateGuestAccount, CreateGuestAccountResponse, DeleteContentsPayload,
-        DeleteContentsResponse, DeletedContents, FileUploaded, FileUploadedResponse, FolderCreated,
-        FolderCreatedResponse, IdOrCode,
+        DeleteContentsResponse, FileUploaded, FileUploadedResponse, FolderCreated,
+        FolderCreatedResponse, IdOrCode, Metadata, MoveContentsPayload, MoveContentsResponse,
+        failed_deletions,
     },
     wt_generator::WtGenerator,
 };
 
 use anyhow::{Context, anyhow};
 use async_recursion::async_recursion;
-use log::{error, warn};
+use futures_util::{StreamExt, stream};
+use log::{debug, error, warn};
 use reqwest::{
-    Client as RqwClient, IntoUrl, Method, RequestBuilder as RqwRequestBuilder,
-    header::{REFERER, USER_AGENT},
+    Client as RqwClient, IntoUrl, Method, Proxy, RequestBuilder as RqwRequestBuilder, Response,
+    header::{REFERER, RETRY_AFTER},
     multipart::{Form, Part},
 };
 use reqwest_middleware::{
     ClientBuilder as MiddlewareClientBuilder, ClientWithMiddleware, RequestBuilder,
 };
 use reqwest_retry::{Jitter, RetryTransientMiddleware, policies::ExponentialBackoff};
-use tokio::sync::OnceCell;
+use serde::de::DeserializeOwned;
+use tokio::{io::AsyncWriteExt, sync::RwLock};
 
 const API_BASE_URL: &str = "https://api.gofile.io";
 const API_BASE_UPLOAD_URL: &str = "https://upload.gofile.io";
 const DEFAULT_MAX_RETRIES: u32 = 10;
+const DEFAULT_RETRY_MIN_INTERVAL: Duration = Duration::from_millis(500);
+const DEFAULT_RETRY_MAX_INTERVAL: Duration = Duration::from_secs(20);
+const DEFAULT_FOLDER_CONCURRENCY: usize = 8;
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+const RATE_LIMIT_STATUS: &str = "error-rateLimit";
+const MAINTENANCE_MAX_RETRIES: u32 = 3;
 const REFERER_HEADER: &str = "https://gofile.io/";
 // JS Number.MAX_SAFE_INTEGER
 const DEFAULT_PAGE_SIZE: &str = "9007199254740991";
 
-const BYPASS_API_URL: &str = "https://gf.1drv.eu.org";
+const DEFAULT_BYPASS_API_URL: &str = "https://gf.1drv.eu.org";
 const BYPASS_GAMBLE_MAX_RETRIES: u32 = 10;
-const BROKEN_BYPASS_PROXY_URL_HOSTS: &[&str] = &["gf.cybar.xyz"];
+const DEFAULT_BROKEN_BYPASS_PROXY_URL_HOSTS: &[&str] = &["gf.cybar.xyz"];
 
 const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/145.0.0.0 Safari/537.36";
 const DEFAULT_LANG: &str = "en-US";
 
+trait ResponseExt {
+    /// Like [`Response::json`], but recognizes gofile.io maintenance and rate limit responses
+    async fn api_json<T: DeserializeOwned>(self) -> GofileResult<T>;
+}
+
+impl ResponseExt for Response {
+    async fn api_json<T: DeserializeOwned>(self) -> GofileResult<T> {
+        let status = self.status();
+        let retry_after = self
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+            .map(Duration::from_secs);
+        let body = self.bytes().await?;
+
+        if maintenance::is_maintenance_response(status, &body) {
+            maintenance::report();
+            return Err(GofileError::Maintenance);
+        }
+
+        // Rate limits come as a regular response with an error status in the body
+        if serde_json::from_slice::<serde_json::Value>(&body)
+            .is_ok_and(|value| value["status"] == RATE_LIMIT_STATUS)
+        {
+            return Err(GofileError::RateLimited { retry_after });
+        }
+
+        let data = serde_json::from_slice(&body)
+            .with_context(|| format!("failed to parse API response ({status})"))?;
+
+        Ok(data)
+    }
+}
+
 pub struct ClientBuilder {
-    client: Option<RqwClient>,
+    proxy: Option<Proxy>,
+    connect_timeout: Duration,
+    request_timeout: Duration,
+    // reqwest's defaults when not set
+    pool_max_idle: Option<usize>,
+    pool_idle_timeout: Option<Duration>,
     api_token: Option<String>,
     password: Option<String>,
+    folder_passwords: HashMap<String, String>,
     user_agent: Option<String>,
     bypass: bool,
+    require_bypass: bool,
+    bypass_url: String,
+    broken_proxy_hosts: Vec<String>,
+    folder_concurrency: usize,
+    page_size: Option<u64>,
+    max_retries: u32,
+    retry_bounds: (Duration, Duration),
+    token_store: Option<PathBuf>,
+    api_url: String,
+    upload_url: String,
 }
 
 impl Default for ClientBuilder {
@@ -58,11 +129,29 @@ impl Default for ClientBuilder {
 impl ClientBuilder {
     pub fn new() -> Self {
         Self {
-            client: None,
+            proxy: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            pool_max_idle: None,
+            pool_idle_timeout: None,
             api_token: None,
             password: None,
+            folder_passwords: HashMap::new(),
             user_agent: None,
             bypass: false,
+            require_bypass: false,
+            bypass_url: DEFAULT_BYPASS_API_URL.to_string(),
+            broken_proxy_hosts: DEFAULT_BROKEN_BYPASS_PROXY_URL_HOSTS
+                .iter()
+                .map(|host| host.to_string())
+                .collect(),
+            folder_concurrency: DEFAULT_FOLDER_CONCURRENCY,
+            page_size: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_bounds: (DEFAULT_RETRY_MIN_INTERVAL, DEFAULT_RETRY_MAX_INTERVAL),
+            token_store: None,
+            api_url: API_BASE_URL.to_string(),
+            upload_url: API_BASE_UPLOAD_URL.to_string(),
         }
     }
 
@@ -71,6 +160,34 @@ impl ClientBuilder {
         self
     }
 
+    /// Sends every request, API calls as well as downloads and uploads, through `proxy`
+    pub fn with_proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Gives up on connections not established within `connect` and on API calls
+    /// not answered within `request`. Downloads only fail when no data arrived for `request`,
+    /// uploads aren't limited beyond connecting.
+    pub fn with_timeouts(mut self, connect: Duration, request: Duration) -> Self {
+        self.connect_timeout = connect;
+        self.request_timeout = request;
+        self
+    }
+
+    /// Keeps at most `max_idle` idle connections open per host, unlimited by default.
+    /// Streaming many files at once needs as many connections to the same servers.
+    pub fn with_pool_max_idle(mut self, max_idle: usize) -> Self {
+        self.pool_max_idle = Some(max_idle);
+        self
+    }
+
+    /// Closes connections left idle for `timeout`, 90 seconds by default
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
     pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
         self.user_agent = Some(user_agent.into());
         self
@@ -81,35 +198,140 @@ impl ClientBuilder {
         self
     }
 
+    /// Fails listing a folder the bypass can't be used on, private or password protected,
+    /// instead of serving its files from Gofile directly
+    pub fn require_bypass(mut self, require: bool) -> Self {
+        self.require_bypass = require;
+        self
+    }
+
+    /// Base URL of the bypass service queried for proxy links
+    pub fn with_bypass_endpoint(mut self, url: impl Into<String>) -> Self {
+        self.bypass_url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Base URL of the API and of uploads, a local one standing in for gofile.io
+    #[cfg(test)]
+    pub fn with_api_endpoint(mut self, url: impl Into<String>) -> Self {
+        self.api_url = url.into().trim_end_matches('/').to_string();
+        self.upload_url = self.api_url.clone();
+        self
+    }
+
+    /// Proxy hosts returned by the bypass service that are known not to work,
+    /// the listing is fetched again when one of them comes back
+    pub fn with_broken_proxy_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.broken_proxy_hosts = hosts;
+        self
+    }
+
     pub fn with_password(mut self, password: String) -> Self {
         self.password = Some(password);
         self
     }
 
+    /// Password of the folder with the given id or code, used instead of the global one
+    pub fn with_folder_password(mut self, folder: String, password: String) -> Self {
+        self.folder_passwords.insert(folder, password);
+        self
+    }
+
+    /// Maximum number of password-protected subfolders fetched at once while listing a folder
+    pub fn with_folder_concurrency(mut self, concurrency: usize) -> Self {
+        self.folder_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Lists folders `page_size` entries at a time instead of all at once
+    pub fn with_page_size(mut self, page_size: u64) -> Self {
+        self.page_size = Some(page_size.max(1));
+        self
+    }
+
+    /// Retries transient failures up to `max_retries` times, backing off exponentially
+    /// from `min` to `max` between attempts
+    pub fn with_retry(mut self, max_retries: u32, min: Duration, max: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_bounds = (min, max);
+        self
+    }
+
+    /// Reuses the guest account token saved at `path` and saves newly created ones there.
+    /// Has no effect when an API token is given.
+    pub fn with_token_store(mut self, path: impl Into<PathBuf>) -> Self {
+        self.token_store = Some(path.into());
+        self
+    }
+
     pub fn build(self) -> Client {
+        let (retry_min, retry_max) = self.retry_bounds;
         let retry_policy = ExponentialBackoff::builder()
-            .retry_bounds(Duration::from_millis(500), Duration::from_secs(20))
+            .retry_bounds(retry_min, retry_max)
             .base(2)
             .jitter(Jitter::Bounded)
-            .build_with_max_retries(DEFAULT_MAX_RETRIES);
+            .build_with_max_retries(self.max_retries);
+
+        // Maintenance lasts minutes rather than seconds, so it gets its own slower backoff
+        let maintenance_retry_policy = ExponentialBackoff::builder()
+            .retry_bounds(Duration::from_secs(10), Duration::from_secs(60))
+            .base(2)
+            .jitter(Jitter::Bounded)
+            .build_with_max_retries(MAINTENANCE_MAX_RETRIES);
+
+        // Kept a browser's by default: the website token is derived from it, and the
+        // CDN serves downloads to browsers
+        let user_agent = self.user_agent.unwrap_or(DEFAULT_USER_AGENT.to_string());
+
+        // every request carries the user agent, API calls as well as downloads and uploads
+        let http_client = |read_timeout: Option<Duration>| {
+            let mut builder = RqwClient::builder()
+                .user_agent(&user_agent)
+                .connect_timeout(self.connect_timeout);
+            if let Some(proxy) = self.proxy.clone() {
+                builder = builder.proxy(proxy);
+            }
+            if let Some(read_timeout) = read_timeout {
+                builder = builder.read_timeout(read_timeout);
+            }
+            if let Some(max_idle) = self.pool_max_idle {
+                builder = builder.pool_max_idle_per_host(max_idle);
+            }
+            if let Some(timeout) = self.pool_idle_timeout {
+                builder = builder.pool_idle_timeout(timeout);
+            }
 
-        let raw_client = self.client.unwrap_or_default();
-        let client = MiddlewareClientBuilder::new(raw_client.clone())
-            .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+            // same as `reqwest::Client::new`, only fails if the TLS backend can't be set up
+            builder.build().expect("failed to build the HTTP client")
+        };
+
+        // gofile.io may take a while to answer once a large upload is sent, so uploads
+        // get no read timeout. API calls get a total one on top, per request.
+        let raw_client = http_client(None);
+        let client = MiddlewareClientBuilder::new(http_client(Some(self.request_timeout)))
+            .with(RetryTransientMiddleware::new_with_policy_and_strategy(
+                maintenance_retry_policy,
+                MaintenanceStrategy,
+            ))
+            .with(RetryTransientMiddleware::new_with_policy_and_strategy(
+                retry_policy,
+                TransientStrategy,
+            ))
             .build();
 
         // TODO?: add CLI arg?
         let lang = DEFAULT_LANG.to_string();
-        let user_agent = self.user_agent.unwrap_or(DEFAULT_USER_AGENT.to_string());
 
-        let wt_generator = if let Some(token) = self.api_token.as_deref() {
-            OnceCell::new_with(Some(WtGenerator::new(token, &user_agent, &lang)))
-        } else {
-            OnceCell::new_with(None)
+        let user_token = self.api_token.is_some();
+        let auth = AuthState {
+            wt_generator: self
+                .api_token
+                .as_deref()
+                .map(|token| WtGenerator::new(token, &user_agent, &lang)),
+            token: self.api_token,
+            generation: 0,
         };
 
-        let api_token = OnceCell::new_with(self.api_token);
-
         let password = self.password;
 
         Client {
@@ -117,24 +339,64 @@ impl ClientBuilder {
             client,
             lang,
             user_agent,
-            wt_generator,
-            api_token,
+            auth: Arc::new(RwLock::new(auth)),
+            user_token,
             password,
+            folder_passwords: Arc::new(self.folder_passwords),
             use_bypass: self.bypass,
+            require_bypass: self.require_bypass,
+            bypass_url: self.bypass_url,
+            broken_proxy_hosts: self.broken_proxy_hosts,
+            folder_concurrency: self.folder_concurrency,
+            page_size: self.page_size,
+            max_retries: self.max_retries,
+            retry_bounds: self.retry_bounds,
+            token_store: self.token_store,
+            request_timeout: self.request_timeout,
+            api_url: self.api_url,
+            upload_url: self.upload_url,
+            health: Health::default(),
         }
     }
 }
 
+/// The account token and the website token generator derived from it
+struct AuthState {
+    token: Option<String>,
+    wt_generator: Option<WtGenerator>,
+    // Bumped whenever the token is replaced, so requests failing together re-authenticate once
+    generation: u64,
+}
+
 #[derive(Clone)]
 pub struct Client {
     raw_client: RqwClient,
     client: ClientWithMiddleware,
     user_agent: String,
     lang: String,
-    wt_generator: OnceCell<WtGenerator>,
-    api_token: OnceCell<String>,
+    // Shared by clones so a new guest account is picked up everywhere
+    auth: Arc<RwLock<AuthState>>,
+    // Whether the token was given by the user, who then has to replace it when it expires
+    user_token: bool,
     password: Option<String>,
+    // Keyed by folder id or code
+    folder_passwords: Arc<HashMap<String, String>>,
     use_bypass: bool,
+    // Contents the bypass can't be used on are an error instead of served directly
+    require_bypass: bool,
+    bypass_url: String,
+    broken_proxy_hosts: Vec<String>,
+    folder_concurrency: usize,
+    // `None` lists a folder in a single request
+    page_size: Option<u64>,
+    max_retries: u32,
+    retry_bounds: (Duration, Duration),
+    token_store: Option<PathBuf>,
+    request_timeout: Duration,
+    api_url: String,
+    upload_url: String,
+    // Outcome of the last API call, shared by clones
+    health: Health,
 }
 
 impl Default for Client {
@@ -152,6 +414,11 @@ impl Client {
         ClientBuilder::new()
     }
 
+    /// Whether gofile.io answered the last API call of this client or its clones
+    pub fn health(&self) -> Health {
+        self.health.clone()
+    }
+
     pub async fn request_builder_for_download_stream(
         &self,
         method: Method,
@@ -170,6 +437,79 @@ impl Client {
         Ok(builder)
     }
 
+    /// Runs `request` again while gofile.io rate limits it, up to the configured retry count.
+    /// Waits for `Retry-After` when the response carries one, backs off exponentially otherwise.
+    async fn retry_rate_limited<T, F, Fut>(&self, mut request: F) -> GofileResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = GofileResult<T>>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            let result = request().await;
+            metrics::observe_api_call(&result);
+
+            match result {
+                Err(GofileError::RateLimited { retry_after }) if attempt < self.max_retries => {
+                    let delay =
+                        retry_after.unwrap_or_else(|| backoff_delay(attempt, self.retry_bounds));
+                    warn!("rate limited by gofile.io, retrying in {delay:?}");
+
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => {
+                    self.health.report(&result);
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// Runs an authenticated `request` like [`Self::retry_rate_limited`], once more with
+    /// a new guest account if gofile.io rejected the token.
+    async fn retry_authenticated<T, F, Fut>(&self, mut request: F) -> GofileResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = GofileResult<T>>,
+    {
+        let generation = self.auth.read().await.generation;
+
+        match self.retry_rate_limited(&mut request).await {
+            Err(GofileError::InvalidToken) => {
+                self.reauthenticate(generation).await?;
+                self.retry_rate_limited(request).await
+            }
+            result => result,
+        }
+    }
+
+    /// Replaces a rejected guest token with a new guest account, unless a request that
+    /// failed alongside already did. A rejected user token is a hard error.
+    async fn reauthenticate(&self, generation: u64) -> GofileResult<()> {
+        let mut auth = self.auth.write().await;
+
+        if auth.generation != generation {
+            return Ok(());
+        }
+
+        if self.user_token {
+            error!("the API token was rejected by gofile.io, it has to be replaced");
+            return Err(GofileError::InvalidToken);
+        }
+
+        warn!("guest token expired, creating a new guest account");
+        let token = self.create_guest_account().await?.token;
+        self.store_token(&token).await;
+
+        auth.token = Some(token);
+        auth.wt_generator = None;
+        auth.generation += 1;
+
+        Ok(())
+    }
+
     async fn auth_request_builder(
         &self,
         method: Method,
@@ -177,60 +517,140 @@ impl Client {
     ) -> GofileResult<RequestBuilder> {
         let api_token = self.get_or_create_guest_token().await?;
 
-        Ok(self
-            .client
-            .request(method, format!("{API_BASE_URL}{}", path.as_ref()))
+        Ok(self.request_builder_with_token(method, path, &api_token))
+    }
+
+    fn request_builder_with_token(
+        &self,
+        method: Method,
+        path: impl AsRef<str>,
+        token: &str,
+    ) -> RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", self.api_url, path.as_ref()))
             .header(REFERER, REFERER_HEADER)
-            .header(USER_AGENT, &self.user_agent)
-            .bearer_auth(api_token))
+            .bearer_auth(token)
+            .timeout(self.request_timeout)
     }
 
     pub async fn get_current_account_info(&self) -> GofileResult<AccountInfo> {
-        self.auth_request_builder(Method::GET, "/accounts/website")
-            .await?
-            .send()
-            .await?
-            .json::<AccountInfoResponse>()
-            .await?
-            .into_result()
+        self.retry_authenticated(|| async {
+            self.auth_request_builder(Method::GET, "/accounts/website")
+                .await?
+                .send()
+                .await?
+                .api_json::<AccountInfoResponse>()
+                .await?
+                .into_result()
+        })
+        .await
     }
 
     pub async fn get_wt_token(&self) -> GofileResult<String> {
-        self.wt_generator
-            .get_or_try_init(|| async {
-                let api_token = self.get_or_create_guest_token().await?;
-                Ok::<WtGenerator, GofileError>(WtGenerator::new(
-                    &api_token,
-                    &self.user_agent,
-                    &self.lang,
-                ))
-            })
-            .await
-            .map(|wt_gn| wt_gn.generate_current())
+        if let Some(wt_generator) = self.auth.read().await.wt_generator.as_ref() {
+            return Ok(wt_generator.generate_current());
+        }
+
+        self.get_or_create_guest_token().await?;
+
+        let mut auth = self.auth.write().await;
+        let AuthState {
+            token,
+            wt_generator,
+            ..
+        } = &mut *auth;
+
+        let token = token.as_deref().context("no account token")?;
+        let wt_generator = wt_generator
+            .get_or_insert_with(|| WtGenerator::new(token, &self.user_agent, &self.lang));
+
+        Ok(wt_generator.generate_current())
+    }
+
+    /// The password configured for `folder`, falling back to the global one
+    fn password_for(&self, folder: &str) -> Option<&str> {
+        self.folder_passwords
+            .get(folder)
+            .or(self.password.as_ref())
+            .map(String::as_str)
+    }
+
+    fn contents_params(&self, folder: &str, page: u64) -> Vec<(&'static str, String)> {
+        let page_size = self
+            .page_size
+            .map_or_else(|| DEFAULT_PAGE_SIZE.to_string(), |size| size.to_string());
+
+        let mut params = Vec::with_capacity(3);
+        params.push(("page", page.to_string()));
+        params.push(("pageSize", page_size));
+        if let Some(pw) = self.password_for(folder) {
+            params.push(("password", pw.to_string()));
+        }
+
+        params
+    }
+
+    async fn get_contents_page(
+        &self,
+        content_id: &IdOrCode,
+        page: u64,
+    ) -> GofileResult<(ContentsWithPassword, Metadata)> {
+        let params = self.contents_params(&content_id.to_string(), page);
+
+        self.retry_authenticated(|| async {
+            // derived from the token, so fetched again after re-authenticating
+            let wt_token = self.get_wt_token().await?;
+
+            self.auth_request_builder(Method::GET, format!("/contents/{}", content_id))
+                .await?
+                .header("X-Website-Token", &wt_token)
+                .header("X-Bl", &self.lang)
+                .query(&params)
+                .send()
+                .await?
+                .api_json::<ContentsWithPasswordResponse>()
+                .await?
+                .into_paged_result()
+        })
+        .await
     }
 
     async fn get_contents_inner(&self, content_id: impl Into<IdOrCode>) -> GofileResult<Contents> {
-        let wt_token = self.get_wt_token().await?;
         let content_id = content_id.into();
 
-        let mut params: Vec<(&str, &str)> = Vec::with_capacity(3);
-        params.push(("page", "1"));
-        params.push(("pageSize", DEFAULT_PAGE_SIZE));
-        if let Some(pw) = self.password.as_deref() {
-            params.push(("password", pw));
-        }
+        let mut page = 1;
+        let (mut result, mut metadata) = self.get_contents_page(&content_id, page).await?;
+
+        // only a folder listed with `page_size` has further pages, merged into the first one
+        while metadata.has_next_page
+            && let ContentsWithPassword::Ok(contents) = &mut result
+            && let ContentsOk::Folder(folder) = contents.as_mut()
+        {
+            page += 1;
+            let (next, next_metadata) = self.get_contents_page(&content_id, page).await?;
+
+            let next_folder = match next {
+                ContentsWithPassword::Ok(contents) => match *contents {
+                    ContentsOk::Folder(next_folder) => next_folder,
+                    ContentsOk::File(file_entry) => {
+                        return Err(
+                            anyhow!("expected folder but got file {}", file_entry.id).into()
+                        );
+                    }
+                },
+                ContentsWithPassword::Restricted(restricted_contents) => {
+                    return Err(restricted_contents.into_err());
+                }
+            };
 
-        let result = self
-            .auth_request_builder(Method::GET, format!("/contents/{}", content_id))
-            .await?
-            .header("X-Website-Token", wt_token)
-            .header("X-Bl", &self.lang)
-            .query(&params)
-            .send()
-            .await?
-            .json::<ContentsWithPasswordResponse>()
-            .await?
-            .into_result()?;
+            if next_folder.children.is_empty() {
+                warn!("page {page} of folder {content_id} is empty");
+                break;
+            }
+
+            folder.children.extend(next_folder.children);
+            metadata = next_metadata;
+        }
 
         let contents = match result {
             // Nothing to do here - the password has already been applied
@@ -280,16 +700,29 @@ impl Client {
             }
         }
 
-        for folder_id in folders_to_process {
-            let result = self
-                .auth_request_builder(Method::GET, format!("/contents/{}", folder_id))
-                .await?
-                .query(&params)
-                .send()
-                .await?
-                .json::<ContentsWithPasswordResponse>()
-                .await?
-                .into_result()?;
+        let mut fetched = stream::iter(folders_to_process)
+            .map(|folder_id| async move {
+                // only the folder itself is kept, its first page is enough
+                let params = &self.contents_params(&folder_id.to_string(), 1);
+                let result = self
+                    .retry_authenticated(|| async {
+                        self.auth_request_builder(Method::GET, format!("/contents/{}", folder_id))
+                            .await?
+                            .query(params)
+                            .send()
+                            .await?
+                            .api_json::<ContentsWithPasswordResponse>()
+                            .await?
+                            .into_result()
+                    })
+                    .await?;
+
+                Ok::<_, GofileError>((folder_id, result))
+            })
+            .buffer_unordered(self.folder_concurrency);
+
+        while let Some(fetched_folder) = fetched.next().await {
+            let (folder_id, result) = fetched_folder?;
 
             match result {
                 ContentsWithPassword::Ok(contents_ok) => match *contents_ok {
@@ -331,20 +764,8 @@ impl Client {
 
                 match contents {
                     Contents::Folder(ref folder_entry) => {
-                        if !folder_entry.public {
-                            warn!(
-                                "Bypass cannot be used on private folder {} - returning regular contents",
-                                folder_entry.id
-                            );
-                            return Ok(contents);
-                        }
-
-                        if folder_entry.password {
-                            warn!(
-                                "Bypass cannot be used on folders with password {} - returning regular contents",
-                                folder_entry.id
-                            );
-                            return Ok(contents);
+                        if let Some(reason) = folder_entry.bypass_refusal() {
+                            return self.without_bypass(contents, reason);
                         }
 
                         if !folder_entry
@@ -361,15 +782,23 @@ impl Client {
                     }
                     Contents::File(ref file_entry) => {
                         if file_entry.password {
-                            warn!(
-                                "Bypass cannot be used on file with password {} - returning regular contents",
-                                file_entry.id
-                            );
-                            return Ok(contents);
+                            return self.without_bypass(contents, "the file is password protected");
                         }
 
+                        // the bypassed entry only comes with the listing of the folder, which
+                        // may be private while the file itself is reached by its code
                         let parrent_contents =
-                            self.get_contents(file_entry.parent_folder.as_str()).await?;
+                            match self.get_contents(file_entry.parent_folder.as_str()).await {
+                                Ok(parrent_contents) => parrent_contents,
+                                Err(e) => {
+                                    debug!(
+                                        "failed to list folder {} of file {}: {e}",
+                                        file_entry.parent_folder, file_entry.id
+                                    );
+                                    return self
+                                        .without_bypass(contents, "its folder can't be listed");
+                                }
+                            };
 
                         match parrent_contents {
                             Contents::File(file_entry) => {
@@ -418,25 +847,106 @@ impl Client {
         }
     }
 
+    /// Serves `contents` the bypass can't be used on as they are, or fails with
+    /// `--require-bypass`
+    fn without_bypass(&self, contents: Contents, reason: &'static str) -> GofileResult<Contents> {
+        let id = contents.id();
+        if self.require_bypass {
+            return Err(GofileError::BypassUnavailable { id, reason });
+        }
+
+        warn!("bypass unavailable for {id}, {reason}, serving it from gofile.io");
+        Ok(contents)
+    }
+
     pub async fn create_guest_account(&self) -> GofileResult<CreateGuestAccount> {
-        self.client
-            .request(Method::POST, format!("{API_BASE_URL}/accounts"))
-            .header(REFERER, REFERER_HEADER)
-            .send()
-            .await?
-            .json::<CreateGuestAccountResponse>()
-            .await?
-            .into_result()
+        self.retry_rate_limited(|| async {
+            self.client
+                .request(Method::POST, format!("{}/accounts", self.api_url))
+                .header(REFERER, REFERER_HEADER)
+                .timeout(self.request_timeout)
+                .send()
+                .await?
+                .api_json::<CreateGuestAccountResponse>()
+                .await?
+                .into_result()
+        })
+        .await
     }
 
     pub async fn get_or_create_guest_token(&self) -> GofileResult<String> {
-        self.api_token
-            .get_or_try_init(|| async {
-                let token = self.create_guest_account().await?.token;
-                Ok(token)
+        if let Some(token) = self.auth.read().await.token.clone() {
+            return Ok(token);
+        }
+
+        // held while creating the account so concurrent requests don't create one each
+        let mut auth = self.auth.write().await;
+        if let Some(token) = auth.token.clone() {
+            return Ok(token);
+        }
+
+        let token = self.load_or_create_guest_token().await?;
+        auth.token = Some(token.clone());
+
+        Ok(token)
+    }
+
+    async fn load_or_create_guest_token(&self) -> GofileResult<String> {
+        if let Some(token) = self.load_stored_token().await {
+            if self.is_valid_token(&token).await? {
+                return Ok(token);
+            }
+
+            warn!("stored guest token was rejected, creating a new guest account");
+        }
+
+        let token = self.create_guest_account().await?.token;
+        self.store_token(&token).await;
+
+        Ok(token)
+    }
+
+    /// Checks a stored guest token, `false` if gofile.io no longer accepts it
+    async fn is_valid_token(&self, token: &str) -> GofileResult<bool> {
+        let result = self
+            .retry_rate_limited(|| async {
+                self.request_builder_with_token(Method::GET, "/accounts/website", token)
+                    .send()
+                    .await?
+                    .api_json::<AccountInfoResponse>()
+                    .await?
+                    .into_result()
             })
-            .await
-            .cloned()
+            .await;
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(GofileError::InvalidToken) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn load_stored_token(&self) -> Option<String> {
+        let path = self.token_store.as_ref()?;
+
+        match tokio::fs::read_to_string(path).await {
+            Ok(token) => Some(token.trim().to_string()).filter(|t| !t.is_empty()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                warn!("failed to read guest token from {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    async fn store_token(&self, token: &str) {
+        let Some(path) = self.token_store.as_ref() else {
+            return;
+        };
+
+        if let Err(e) = write_private(path, token).await {
+            warn!("failed to save guest token to {}: {e}", path.display());
+        }
     }
 
     pub async fn request_builder_for_upload(
@@ -454,7 +964,7 @@ impl Client {
 
         Ok(self
             .raw_client
-            .request(Method::POST, format!("{API_BASE_UPLOAD_URL}/uploadfile"))
+            .request(Method::POST, format!("{}/uploadfile", self.upload_url))
             .header(REFERER, REFERER_HEADER)
             .multipart(form))
     }
@@ -468,7 +978,7 @@ impl Client {
             .await?
             .send()
             .await?
-            .json::<FileUploadedResponse>()
+            .api_json::<FileUploadedResponse>()
             .await?
             .into_result()
     }
@@ -484,14 +994,17 @@ impl Client {
             folder_name: folder_name.as_ref(),
         };
 
-        self.auth_request_builder(Method::POST, "/contents/createfolder")
-            .await?
-            .json(&payload)
-            .send()
-            .await?
-            .json::<FolderCreatedResponse>()
-            .await?
-            .into_result()
+        self.retry_authenticated(|| async {
+            self.auth_request_builder(Method::POST, "/contents/createfolder")
+                .await?
+                .json(&payload)
+                .send()
+                .await?
+                .api_json::<FolderCreatedResponse>()
+                .await?
+                .into_result()
+        })
+        .await
     }
 
     pub async fn update_attribute(
@@ -501,17 +1014,21 @@ impl Client {
     ) -> GofileResult<ContentsUdpated> {
         let content_id = content_id.into();
 
-        self.auth_request_builder(Method::PUT, format!("/contents/{content_id}/update"))
-            .await?
-            .json(&attribute)
-            .send()
-            .await?
-            .json::<ContentsUdpatedResponse>()
-            .await?
-            .into_result()
+        self.retry_authenticated(|| async {
+            self.auth_request_builder(Method::PUT, format!("/contents/{content_id}/update"))
+                .await?
+                .json(&attribute)
+                .send()
+                .await?
+                .api_json::<ContentsUdpatedResponse>()
+                .await?
+                .into_result()
+        })
+        .await
     }
 
-    pub async fn delete_contents<T, U>(&self, content_ids: T) -> GofileResult<DeletedContents>
+    /// Deletes `content_ids` in a single call, failing when any of them wasn't deleted
+    pub async fn delete_contents<T, U>(&self, content_ids: T) -> GofileResult<()>
     where
         T: AsRef<[U]>,
         U: Into<IdOrCode> + Clone,
@@ -528,33 +1045,87 @@ impl Client {
             contents_id: &contents_id,
         };
 
-        self.auth_request_builder(Method::DELETE, "/contents")
-            .await?
-            .json(&payload)
-            .send()
-            .await?
-            .json::<DeleteContentsResponse>()
-            .await?
-            .into_result()
+        let deleted = self
+            .retry_authenticated(|| async {
+                self.auth_request_builder(Method::DELETE, "/contents")
+                    .await?
+                    .json(&payload)
+                    .send()
+                    .await?
+                    .api_json::<DeleteContentsResponse>()
+                    .await?
+                    .into_result()
+            })
+            .await?;
+
+        // the call succeeds as a whole even when some of the contents stay
+        let failed = failed_deletions(deleted);
+        if !failed.is_empty() {
+            return Err(GofileError::DeleteFailed { failed });
+        }
+
+        Ok(())
+    }
+
+    pub async fn move_contents<T, U>(
+        &self,
+        content_ids: T,
+        dest_folder_id: impl Into<IdOrCode>,
+    ) -> GofileResult<()>
+    where
+        T: AsRef<[U]>,
+        U: Into<IdOrCode> + Clone,
+    {
+        let contents_id = content_ids
+            .as_ref()
+            .iter()
+            .cloned()
+            .map(|v| v.into().to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let folder_id = dest_folder_id.into().to_string();
+
+        let payload = MoveContentsPayload {
+            contents_id: &contents_id,
+            folder_id: &folder_id,
+        };
+
+        self.retry_authenticated(|| async {
+            self.auth_request_builder(Method::PUT, "/contents/move")
+                .await?
+                .json(&payload)
+                .send()
+                .await?
+                .api_json::<MoveContentsResponse>()
+                .await?
+                .into_result()
+        })
+        .await?;
+
+        Ok(())
     }
 
     pub async fn get_bypass_files(&self, id: impl AsRef<str>) -> GofileResult<BypassFiles> {
         for _ in 0..BYPASS_GAMBLE_MAX_RETRIES {
             let resp = self
                 .client
-                .get(format!("{BYPASS_API_URL}/api/files"))
+                .get(format!("{}/api/files", self.bypass_url))
                 .query(&[("folderId", id.as_ref())])
+                .timeout(self.request_timeout)
                 .send()
                 .await?;
 
-            let data = resp.json::<BypassFilesResponse>().await?.into_result()?;
+            let data = resp
+                .api_json::<BypassFilesResponse>()
+                .await?
+                .into_result()?;
 
             let retry = data
                 .first()
                 .map(|f| {
-                    BROKEN_BYPASS_PROXY_URL_HOSTS
+                    self.broken_proxy_hosts
                         .iter()
-                        .any(|host| f.proxy_link.host_str() == Some(host))
+                        .any(|host| f.proxy_link.host_str() == Some(host.as_str()))
                 })
                 .unwrap_or(false);
 
@@ -570,3 +1141,178 @@ impl Client {
         .into())
     }
 }
+
+/// Delay before retry number `attempt + 1`, doubling from `min` up to `max`
+fn backoff_delay(attempt: u32, (min, max): (Duration, Duration)) -> Duration {
+    min.saturating_mul(2u32.saturating_pow(attempt)).min(max)
+}
+
+/// Writes `contents` to `path`, leaving it readable by its owner only on Unix, whatever
+/// the mode of a file already there.
+async fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options.open(path).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // tightened before anything is written to it
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .await?;
+    }
+
+    file.write_all(contents.as_bytes()).await?;
+    file.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_token_store_roundtrip() {
+        let path = std::env::temp_dir().join(format!("gofile-dav-{}-token", std::process::id()));
+        let client = Client::builder().with_token_store(&path).build();
+
+        assert_eq!(client.load_stored_token().await, None);
+
+        client.store_token("Maie2RlOFDDDRao0Y5ll54EAtv2imUlZ").await;
+        assert_eq!(
+            client.load_stored_token().await.as_deref(),
+            Some("Maie2RlOFDDDRao0Y5ll54EAtv2imUlZ")
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_token_store_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("gofile-dav-{}-private", std::process::id()));
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        write_private(&path, "first").await.unwrap();
+        assert_eq!(mode(&path), 0o600);
+
+        // an existing file is tightened too
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write_private(&path, "second").await.unwrap();
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rejected_user_token_is_a_hard_error() {
+        let client = Client::builder().with_token("expired").build();
+
+        assert!(matches!(
+            client.reauthenticate(0).await,
+            Err(GofileError::InvalidToken)
+        ));
+
+        // another request already replaced the token
+        assert!(client.reauthenticate(1).await.is_ok());
+    }
+
+    #[test]
+    fn test_require_bypass() {
+        let folder = Contents::Folder(Default::default());
+
+        let client = Client::builder().use_bypass(true).build();
+        assert!(client.without_bypass(folder.clone(), "reason").is_ok());
+
+        let client = Client::builder()
+            .use_bypass(true)
+            .require_bypass(true)
+            .build();
+        let error = client.without_bypass(folder, "the folder is private");
+        assert!(matches!(
+            error,
+            Err(GofileError::BypassUnavailable {
+                reason: "the folder is private",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_folder_password_overrides_global_one() {
+        let client = Client::builder()
+            .with_password("global".to_string())
+            .with_folder_password("Veil7n".to_string(), "folder".to_string())
+            .build();
+
+        assert_eq!(client.password_for("Veil7n"), Some("folder"));
+        assert_eq!(client.password_for("Ab12cd"), Some("global"));
+
+        let client = Client::builder()
+            .with_folder_password("Veil7n".to_string(), "folder".to_string())
+            .build();
+        assert_eq!(client.password_for("Ab12cd"), None);
+    }
+
+    #[tokio::test]
+    async fn test_user_agent_is_sent_on_downloads() {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/download/web/0/a.txt",
+            listener.local_addr().unwrap()
+        );
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut buf = [0; 1024];
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+
+            String::from_utf8(request).unwrap().to_ascii_lowercase()
+        });
+
+        let client = Client::builder()
+            .with_user_agent("gofile-dav-test/1.0")
+            .build();
+        client
+            .request_builder_for_download_stream(Method::GET, url, true)
+            .await
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+
+        assert!(
+            server
+                .await
+                .unwrap()
+                .contains("user-agent: gofile-dav-test/1.0\r\n")
+        );
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let bounds = (Duration::from_millis(500), Duration::from_secs(20));
+
+        assert_eq!(backoff_delay(0, bounds), Duration::from_millis(500));
+        assert_eq!(backoff_delay(3, bounds), Duration::from_secs(4));
+        assert_eq!(backoff_delay(10, bounds), Duration::from_secs(20));
+        assert_eq!(backoff_delay(u32::MAX, bounds), Duration::from_secs(20));
+    }
+}
@@ -1,9 +1,15 @@
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
     io,
     pin::Pin,
-    sync::Arc,
-    time::{Duration, UNIX_EPOCH},
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, UNIX_EPOCH},
 };
 
 use anyhow::{Context, anyhow};
@@ -11,38 +17,110 @@ use bytes::{BufMut, Bytes, BytesMut};
 use dav_server::{
     davpath::{DavPath, DavPathRef},
     fs::{
-        DavDirEntry, DavFile as FsDavFile, DavFileSystem, DavMetaData, FsError, FsFuture, FsResult,
-        FsStream, OpenOptions, ReadDirMeta,
+        DavDirEntry, DavFile as FsDavFile, DavFileSystem, DavMetaData, DavProp, FsError, FsFuture,
+        FsResult, FsStream, OpenOptions, ReadDirMeta,
     },
 };
 use futures_util::{FutureExt, Stream, StreamExt, TryFutureExt};
-use percent_encoding::percent_decode_str;
-use reqwest::{Method, header::RANGE, multipart::Part};
+use log::{debug, info, warn};
+use md5::{Digest, Md5};
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use prometheus::IntGauge;
+use reqwest::{Method, StatusCode, header::RANGE, multipart::Part};
 use tokio::{
-    sync::{Mutex, RwLock, mpsc},
-    task::JoinHandle,
+    sync::{Mutex, OwnedMutexGuard, RwLock, Semaphore, mpsc, watch},
+    task::{AbortHandle, JoinHandle},
 };
-use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
+use url::Url;
+use uuid::Uuid;
 
 use super::{
     Client, DirCache,
+    dircache::{FileCache, ROOT_DIR},
+    download_cache::{CacheWriter, CachedFile, DownloadCache},
     error::{GofileError, GofileResult},
-    model::{Attribute, Contents as DirEntry, FileEntry, FileUploaded, FolderEntry},
+    in_flight::InFlight,
+    metrics,
+    model::{Attribute, Contents as DirEntry, FileEntry, FileUploaded, FolderEntry, IdOrCode},
+    progress::Progress,
+    throttle::RateLimiter,
+    virtual_file::{VirtualDir, VirtualFile, VirtualFileRead},
 };
 
 impl From<GofileError> for FsError {
     fn from(value: GofileError) -> Self {
         match value {
             GofileError::Io { source } => source.into(),
+            value => FsError::from(&value),
+        }
+    }
+}
+
+impl From<&GofileError> for FsError {
+    fn from(value: &GofileError) -> Self {
+        match value {
+            GofileError::Io { source } => match source.raw_os_error() {
+                Some(code) => io::Error::from_raw_os_error(code).into(),
+                None => io::Error::from(source.kind()).into(),
+            },
+            GofileError::Shared(error) => FsError::from(&**error),
             GofileError::NotFound => FsError::NotFound,
             GofileError::Forbidden | GofileError::PasswordRequired | GofileError::PasswordWrong => {
                 FsError::Forbidden
             }
-            _ => FsError::GeneralFailure,
+            GofileError::Http { source } => http_error(source.status()),
+            // dav-server can't answer 429 or 503, a 502 at least tells clients gofile.io
+            // is the one failing and that retrying later may help
+            GofileError::RateLimited { .. } | GofileError::Maintenance => FsError::IsRemote,
+            GofileError::QuotaExceeded { status } => {
+                warn!("gofile.io refused to store more data: {status}");
+                FsError::InsufficientStorage
+            }
+            GofileError::Api { status } => {
+                warn!("gofile.io API returned an error: {status}");
+                FsError::GeneralFailure
+            }
+            GofileError::DeleteFailed { .. } => {
+                warn!("{value}");
+                FsError::GeneralFailure
+            }
+            GofileError::BypassUnavailable { .. } => {
+                warn!("{value}, refusing to serve it with --require-bypass");
+                FsError::GeneralFailure
+            }
+            GofileError::InvalidToken | GofileError::Unexpected(_) => FsError::GeneralFailure,
         }
     }
 }
 
+/// Whether `fresh`, fetched by the id of `known`, is still listed where `known` was.
+/// Files moved, renamed or left out of listings since then need their folder listed.
+fn is_same_file(known: &FileEntry, fresh: &FileEntry) -> bool {
+    fresh.name == known.name
+        && fresh.parent_folder == known.parent_folder
+        && fresh.can_access
+        && !fresh.is_frozen
+}
+
+/// The time `secs` after the epoch, `fallback` when it is 0, now when both are.
+fn timestamp(secs: u64, fallback: u64) -> std::time::SystemTime {
+    match [secs, fallback].into_iter().find(|&secs| secs != 0) {
+        Some(secs) => UNIX_EPOCH + Duration::from_secs(secs),
+        None => std::time::SystemTime::now(),
+    }
+}
+
+/// Maps a failed HTTP request to gofile.io by the status it got, if any
+fn http_error(status: Option<StatusCode>) -> FsError {
+    match status {
+        Some(StatusCode::NOT_FOUND) => FsError::NotFound,
+        Some(StatusCode::FORBIDDEN | StatusCode::UNAUTHORIZED) => FsError::Forbidden,
+        // 5xx, or no response at all
+        _ => FsError::IsRemote,
+    }
+}
+
 impl DavMetaData for DirEntry {
     fn len(&self) -> u64 {
         self.size()
@@ -52,32 +130,57 @@ impl DavMetaData for DirEntry {
         matches!(self, DirEntry::Folder(_))
     }
 
+    /// Falls back to the creation time when Gofile reports none, then to now, so sync
+    /// tools comparing dates don't see 1970.
     fn modified(&self) -> FsResult<std::time::SystemTime> {
-        Ok(UNIX_EPOCH + Duration::from_secs(self.modtime()))
+        Ok(timestamp(self.modtime(), self.created()))
     }
 
+    /// Falls back to the modification time when Gofile reports none, then to now.
     fn created(&self) -> FsResult<std::time::SystemTime> {
-        Ok(UNIX_EPOCH + Duration::from_secs(self.created()))
+        Ok(timestamp(self.created(), self.modtime()))
+    }
+
+    /// Files are tagged by md5 so the tag only changes with the content. dav-server adds the
+    /// quotes when sending the `ETag` header, so the tag is returned unquoted.
+    ///
+    /// dav-server answers `If-Range`, `If-Match` and `If-None-Match` from this tag and
+    /// `If-Modified-Since` from [`Self::modified`], before any byte is read from Gofile.
+    fn etag(&self) -> Option<String> {
+        match self {
+            DirEntry::File(file) => Some(file.md5.to_ascii_lowercase()),
+            DirEntry::Folder(folder) => {
+                Some(format!("{}-{:x}", folder.id.simple(), folder.mod_time))
+            }
+        }
     }
 }
 
 pub struct BufferedStream<S> {
     stream: S,
     buffer: BytesMut,
+    rate_limit: Option<Arc<RateLimiter>>,
 }
 
-impl<S> BufferedStream<S>
+impl<S, E> BufferedStream<S>
 where
-    S: Stream<Item = Result<Bytes, reqwest::Error>> + Unpin,
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
 {
     pub fn new(stream: S) -> Self {
         Self {
             stream,
             buffer: BytesMut::new(),
+            rate_limit: None,
         }
     }
 
-    pub async fn take_n_bytes(&mut self, n: usize) -> Result<Bytes, reqwest::Error> {
+    /// Holds every chunk back until `limiter` lets it through.
+    pub fn with_rate_limit(mut self, limiter: Option<Arc<RateLimiter>>) -> Self {
+        self.rate_limit = limiter;
+        self
+    }
+
+    pub async fn take_n_bytes(&mut self, n: usize) -> Result<Bytes, E> {
         let mut result = BytesMut::with_capacity(n);
 
         while result.len() < n {
@@ -100,11 +203,15 @@ where
             }
         }
 
+        if let Some(limiter) = &self.rate_limit {
+            limiter.acquire(result.len()).await;
+        }
+
         Ok(result.freeze())
     }
 }
 
-type StreamResult = Result<Bytes, reqwest::Error>;
+type StreamResult = GofileResult<Bytes>;
 type StreamType = Pin<Box<dyn Stream<Item = StreamResult> + Send>>;
 type StreamBuffer = BufferedStream<StreamType>;
 
@@ -114,6 +221,14 @@ struct DavFileRead {
     file: FileEntry,
     // Mutex is used here because it satisfies the Send + Sync bounds required by the FsDavFile trait
     stream_buffer: Mutex<Option<StreamBuffer>>,
+    cache_checked: bool,
+    cached: Option<CachedFile>,
+    cache_writer: Option<CacheWriter>,
+    // Only set while the stream has been read from the first byte on
+    hasher: Option<Md5>,
+    progress: Progress,
+    // Reopened streams since the last successful read, see `DavFs::download_reconnects`
+    reconnects: u32,
 }
 
 impl std::fmt::Debug for DavFileRead {
@@ -128,57 +243,236 @@ impl std::fmt::Debug for DavFileRead {
 
 impl DavFileRead {
     fn new(fs: DavFs, file: FileEntry) -> Self {
+        let progress = Progress::new(
+            format!("download of {} ({})", file.name, file.id),
+            Some(file.size),
+        );
+
         Self {
             fs,
             position: 0,
             file,
             stream_buffer: Mutex::new(None),
+            cache_checked: false,
+            cached: None,
+            cache_writer: None,
+            hasher: None,
+            progress,
+            reconnects: 0,
+        }
+    }
+
+    /// Requests the file from `self.position` on, from several servers at once when
+    /// `--parallel-download` allows it.
+    async fn open_stream(&self) -> GofileResult<StreamBuffer> {
+        let client = &self.fs.client;
+        let position = self.position;
+
+        let mirrors = mirror_urls(&self.file);
+        let boxed_stream: StreamType = if position >= self.file.size {
+            // nothing left, a range starting past the end would be refused
+            Box::pin(futures_util::stream::empty())
+        } else if self.fs.parallel_downloads > 1
+            && mirrors.len() > 1
+            && self.file.size.saturating_sub(position) > PARALLEL_CHUNK_SIZE
+        {
+            parallel_stream(
+                client.clone(),
+                mirrors,
+                position..self.file.size,
+                PARALLEL_CHUNK_SIZE,
+                self.fs.parallel_downloads,
+            )
+        } else {
+            self.stream_from_first_mirror(&mirrors).await?
+        };
+
+        let boxed_stream = match self.fs.read_ahead {
+            0 => boxed_stream,
+            high_water => read_ahead(boxed_stream, high_water),
+        };
+
+        Ok(BufferedStream::new(boxed_stream).with_rate_limit(self.fs.download_rate.clone()))
+    }
+
+    /// Streams the file from `self.position` on from the first of `mirrors` that answers
+    /// with success, trying the next one when a server can't be reached or answers with an
+    /// error, like a slow link timing out or a `403 Forbidden`.
+    async fn stream_from_first_mirror(&self, mirrors: &[Url]) -> GofileResult<StreamType> {
+        let mut failed = None;
+
+        for (i, url) in mirrors.iter().enumerate() {
+            let host = url.host_str().unwrap_or_default();
+            let response = self
+                .fs
+                .client
+                .request_builder_for_download_stream(Method::GET, url.clone(), self.file.bypassed)
+                .await?
+                .header(RANGE, format!("bytes={}-", self.position))
+                .send()
+                .await
+                .map_err(GofileError::from)
+                .and_then(|response| response.error_for_status().map_err(GofileError::from));
+
+            match response {
+                Ok(response) => {
+                    if i == 0 {
+                        debug!("download of {} served by {host}", self.file.id);
+                    } else {
+                        info!(
+                            "download of {} served by {host}, after {i} server(s) failed",
+                            self.file.id
+                        );
+                    }
+
+                    let stream = response
+                        .bytes_stream()
+                        .map(|chunk| chunk.map_err(GofileError::from));
+                    return Ok(Box::pin(stream));
+                }
+                Err(e) => {
+                    warn!("download of {} from {host} failed: {e}", self.file.id);
+                    failed = Some(e);
+                }
+            }
+        }
+
+        Err(failed.unwrap_or_else(|| anyhow!("no server to download {} from", self.file.id).into()))
+    }
+
+    /// Hashes downloaded bytes and compares the md5 once the whole file went through.
+    fn verify_md5(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let Some(hasher) = self.hasher.as_mut() else {
+            return Ok(());
+        };
+
+        hasher.update(bytes);
+
+        if self.position != self.file.size {
+            return Ok(());
+        }
+
+        let digest = format!("{:x}", self.hasher.take().unwrap_or_default().finalize());
+        if !digest.eq_ignore_ascii_case(&self.file.md5) {
+            // never cache a corrupted download
+            self.cache_writer = None;
+
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "md5 mismatch for file {}: expected {}, got {digest}",
+                    self.file.id, self.file.md5
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Feeds downloaded bytes to the download cache, moving the file into place once complete.
+    async fn write_cache(&mut self, bytes: &[u8]) {
+        let Some(writer) = self.cache_writer.as_mut() else {
+            return;
+        };
+
+        if let Err(e) = writer.write(bytes).await {
+            warn!("failed to cache file {}: {e}", self.file.id);
+            self.cache_writer = None;
+            return;
+        }
+
+        if self.position == self.file.size
+            && let (Some(writer), Some(cache)) =
+                (self.cache_writer.take(), self.fs.download_cache.as_deref())
+            && let Err(e) = writer.finish(cache).await
+        {
+            warn!("failed to cache file {}: {e}", self.file.id);
         }
     }
 }
 
 impl FsDavFile for DavFileRead {
     fn read_bytes(&'_ mut self, count: usize) -> FsFuture<'_, bytes::Bytes> {
+        let timeout = self.fs.operation_timeout;
+        let id = self.file.id;
+
         async move {
             if count == 0 {
                 return Ok(Bytes::new());
             }
 
-            let client = &self.fs.client;
-            let position = self.position;
-            let url = self.file.link.clone();
-            let range_header = format!("bytes={}-", position);
+            if !self.cache_checked {
+                self.cache_checked = true;
 
-            if self.stream_buffer.get_mut().is_none() {
-                let stream = client
-                    .request_builder_for_download_stream(Method::GET, url, self.file.bypassed)
-                    .await?
-                    .header(RANGE, range_header)
-                    .send()
-                    .await
-                    .map_err(GofileError::from)?
-                    .bytes_stream();
+                if let Some(cache) = self.fs.download_cache.as_deref() {
+                    self.cached = cache.open(&self.file.md5, self.file.size).await;
+                }
+            }
 
-                let boxed_stream: Pin<
-                    Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>,
-                > = Box::pin(stream);
-                let buffered_stream = BufferedStream::new(boxed_stream);
+            if let Some(cached) = self.cached.as_mut() {
+                let bytes = cached.read_at(self.position, count).await?;
 
-                *self.stream_buffer.get_mut() = Some(buffered_stream);
+                self.position += bytes.len() as u64;
+                self.progress.update(self.position);
+                return Ok(bytes);
             }
 
-            let bytes = self
-                .stream_buffer
-                .get_mut()
-                .as_mut()
-                .unwrap()
-                .take_n_bytes(count)
-                .await
-                .map_err(GofileError::from)?;
+            if self.stream_buffer.get_mut().is_none() {
+                let position = self.position;
+
+                // only a download starting at the first byte can be cached or verified
+                self.cache_writer = None;
+                self.hasher = (position == 0 && self.fs.verify_md5).then(Md5::new);
+                if position == 0
+                    && let Some(cache) = self.fs.download_cache.as_deref()
+                {
+                    self.cache_writer = cache.writer(&self.file.md5).await;
+                }
+
+                *self.stream_buffer.get_mut() = Some(self.open_stream().await?);
+            }
+
+            let bytes = loop {
+                let result = self
+                    .stream_buffer
+                    .get_mut()
+                    .as_mut()
+                    .unwrap()
+                    .take_n_bytes(count)
+                    .await;
+
+                match result {
+                    Ok(bytes) => break bytes,
+                    Err(e) if self.reconnects < self.fs.download_reconnects => {
+                        self.reconnects += 1;
+                        warn!(
+                            "download of {} dropped at byte {}, reconnecting ({}/{}): {e}",
+                            self.file.id,
+                            self.position,
+                            self.reconnects,
+                            self.fs.download_reconnects
+                        );
+
+                        // bytes of the failed read were not handed out, the new stream
+                        // starts with them, so the hash and the cached copy stay valid
+                        *self.stream_buffer.get_mut() = Some(self.open_stream().await?);
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            };
+            self.reconnects = 0;
 
             self.position += bytes.len() as u64;
+            self.progress.update(self.position);
+            if let Err(e) = self.verify_md5(&bytes) {
+                warn!("{e}");
+                return Err(e.into());
+            }
+            self.write_cache(&bytes).await;
+
             Ok(bytes)
         }
+        .timed(timeout, move || format!("read of file {id}"))
         .boxed()
     }
 
@@ -203,7 +497,15 @@ impl FsDavFile for DavFileRead {
                 .into());
             }
 
-            self.position = new_pos as u64;
+            let new_pos = new_pos as u64;
+            if new_pos != self.position {
+                // The open stream continues at `self.position`, reopen it at the new offset
+                *self.stream_buffer.get_mut() = None;
+                self.cache_writer = None;
+                self.hasher = None;
+            }
+
+            self.position = new_pos;
             Ok(self.position)
         }
         .boxed()
@@ -222,29 +524,416 @@ impl FsDavFile for DavFileRead {
     }
 }
 
+// https://github.com/messense/dav-server-rs/blob/4a1ae81485f46fe308c1eae5e474a0800fa68109/src/handle_gethead.rs#L29C1-L30C1
+const READ_BUF_SIZE: usize = 16384;
+
+// Bytes fetched per request with `--parallel-download`, each held in memory until read
+const PARALLEL_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+// Dropped downloads reopened in a row before the read fails, see `--download-reconnects`
+const DEFAULT_DOWNLOAD_RECONNECTS: u32 = 3;
+
+/// The download link of `file` on each of its servers, the selected one first. Only the
+/// link itself when it isn't hosted on the selected server, or the file was bypassed.
+fn mirror_urls(file: &FileEntry) -> Vec<Url> {
+    let selected_host = format!("{}.gofile.io", file.server_selected);
+    if file.bypassed || file.link.host_str() != Some(selected_host.as_str()) {
+        return vec![file.link.clone()];
+    }
+
+    let mut urls = vec![file.link.clone()];
+    for server in file.servers.iter().filter(|s| **s != file.server_selected) {
+        let mut url = file.link.clone();
+        if url.set_host(Some(&format!("{server}.gofile.io"))).is_ok() {
+            urls.push(url);
+        }
+    }
+
+    urls
+}
+
+/// Downloads `range` in chunks spread over `mirrors`, `parallel` chunks at a time.
+/// Chunks are yielded in file order whatever order they complete in.
+fn parallel_stream(
+    client: Client,
+    mirrors: Vec<Url>,
+    range: std::ops::Range<u64>,
+    chunk_size: u64,
+    parallel: usize,
+) -> StreamType {
+    let end = range.end;
+    let chunks = range
+        .step_by(chunk_size as usize)
+        .map(move |start| start..(start + chunk_size).min(end))
+        .enumerate();
+
+    let stream = futures_util::stream::iter(chunks)
+        .map(move |(i, chunk)| {
+            let client = client.clone();
+            let mirror = mirrors[i % mirrors.len()].clone();
+            let selected = mirrors[0].clone();
+
+            async move {
+                match fetch_range(&client, mirror.clone(), chunk.clone()).await {
+                    Err(e) if mirror != selected => {
+                        debug!("failed to download from {mirror}, using {selected}: {e}");
+                        fetch_range(&client, selected, chunk).await
+                    }
+                    result => result,
+                }
+            }
+        })
+        // unlike `buffer_unordered`, yields in the order the chunks were started
+        .buffered(parallel);
+
+    Box::pin(stream)
+}
+
+/// Room for a bounded number of bytes sent and not received yet. A sender takes room for
+/// its bytes before sending them, and the receiver gives it back. A chunk larger than the
+/// window takes all of it, it still goes through.
+#[derive(Clone)]
+struct ByteWindow {
+    room: Arc<Semaphore>,
+    size: u32,
+}
+
+impl ByteWindow {
+    /// A window of `size` bytes, within what a semaphore can count
+    fn new(size: usize) -> Self {
+        let size = size.clamp(1, Semaphore::MAX_PERMITS.min(u32::MAX as usize)) as u32;
+
+        Self {
+            room: Arc::new(Semaphore::new(size as usize)),
+            size,
+        }
+    }
+
+    fn permits(&self, len: usize) -> u32 {
+        len.min(self.size as usize) as u32
+    }
+
+    /// Waits for room for `len` bytes, false if the window was closed.
+    async fn take(&self, len: usize) -> bool {
+        match self.room.acquire_many(self.permits(len)).await {
+            Ok(permit) => {
+                permit.forget();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn give_back(&self, len: usize) {
+        self.room.add_permits(self.permits(len) as usize);
+    }
+}
+
+/// Pulls `stream` from a background task, keeping up to `high_water` bytes received ahead
+/// of the reader so the network doesn't wait for the client between reads.
+fn read_ahead(mut stream: StreamType, high_water: usize) -> StreamType {
+    let window = ByteWindow::new(high_water);
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    let filler_window = window.clone();
+    tokio::spawn(async move {
+        while let Some(chunk) = stream.next().await {
+            let failed = chunk.is_err();
+
+            if let Ok(bytes) = &chunk {
+                tokio::select! {
+                    taken = filler_window.take(bytes.len()) => if !taken {
+                        return;
+                    },
+                    // the reader is gone
+                    _ = tx.closed() => return,
+                }
+            }
+
+            if tx.send(chunk).is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    let stream = UnboundedReceiverStream::new(rx).inspect(move |chunk| {
+        if let Ok(bytes) = chunk {
+            window.give_back(bytes.len());
+        }
+    });
+
+    Box::pin(stream)
+}
+
+/// Downloads exactly `range` of the file at `url`.
+async fn fetch_range(
+    client: &Client,
+    url: Url,
+    range: std::ops::Range<u64>,
+) -> GofileResult<Bytes> {
+    let response = client
+        .request_builder_for_download_stream(Method::GET, url, false)
+        .await?
+        .header(RANGE, format!("bytes={}-{}", range.start, range.end - 1))
+        .send()
+        .await
+        .map_err(GofileError::from)?
+        .error_for_status()?;
+
+    // a server ignoring the range would put the wrong bytes at this position
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(anyhow!("expected a partial response, got {}", response.status()).into());
+    }
+
+    let bytes = response.bytes().await?;
+    if bytes.len() as u64 != range.end - range.start {
+        return Err(anyhow!(
+            "expected {} bytes for range {range:?}, got {}",
+            range.end - range.start,
+            bytes.len()
+        )
+        .into());
+    }
+
+    Ok(bytes)
+}
+
+type UploadChunk = Result<Bytes, io::Error>;
+
+/// Sending half of the channel feeding written bytes to an upload body, see [`upload_channel`]
+struct UploadSender {
+    tx: mpsc::Sender<UploadChunk>,
+    // Room left for bytes the upload hasn't taken yet
+    window: Option<ByteWindow>,
+}
+
+impl UploadSender {
+    /// Waits for room in the window, then queues `chunk` for the upload.
+    async fn send(&self, chunk: UploadChunk) -> Result<(), mpsc::error::SendError<UploadChunk>> {
+        if let (Some(window), Ok(bytes)) = (&self.window, &chunk)
+            && !window.take(bytes.len()).await
+        {
+            return Err(mpsc::error::SendError(chunk));
+        }
+
+        self.tx.send(chunk).await
+    }
+}
+
+/// A channel for the bytes of an upload, holding at most `buffer` bytes the upload hasn't
+/// taken yet before the writer waits. A single chunk when not set.
+fn upload_channel(
+    buffer: Option<usize>,
+) -> (
+    UploadSender,
+    impl Stream<Item = UploadChunk> + Send + 'static,
+) {
+    let Some(buffer) = buffer else {
+        let (tx, rx) = mpsc::channel(1);
+        let sender = UploadSender { tx, window: None };
+
+        return (sender, ReceiverStream::new(rx).left_stream());
+    };
+
+    let window = ByteWindow::new(buffer);
+    // the window bounds the bytes, this only bounds the number of empty chunks
+    let (tx, rx) = mpsc::channel(window.size as usize);
+    let sender = UploadSender {
+        tx,
+        window: Some(window.clone()),
+    };
+
+    let stream = ReceiverStream::new(rx).inspect(move |chunk| {
+        if let Ok(bytes) = chunk {
+            window.give_back(bytes.len());
+        }
+    });
+
+    (sender, stream.right_stream())
+}
+
+/// Streams written bytes into a new upload that replaces the file at `path` on flush.
+///
+/// Gofile can't append to a file, so in append mode the existing contents are downloaded
+/// and re-uploaded in front of the new bytes. Appending costs a full read of the file.
 pub struct DavFileWrite {
     fs: DavFs,
     path: DavPath,
-    sender: Option<mpsc::Sender<Result<Bytes, io::Error>>>,
+    append: bool,
+    // Length of the body announced by the client, e.g. the Content-Length of the PUT
+    size: Option<u64>,
+    sender: Option<UploadSender>,
     handle: Option<JoinHandle<GofileResult<FileUploaded>>>,
+    // The file listed at `path` when the upload started, deleted once it's replaced
+    replaces: Option<Uuid>,
+    // Bytes received from the client so far
+    written: u64,
+    progress: Progress,
+    // Set by the first flush, which does the one upload of this write
+    flushed: bool,
+    // Held from the first write or flush on, see `PathLocks`
+    upload_lock: Option<PathLock>,
+    // Registered from the start of the upload to the flush, see `OpenWrites`
+    open_write: Option<u64>,
+    _pending: PendingWrite,
+}
+
+impl Drop for DavFileWrite {
+    fn drop(&mut self) {
+        // never flushed, or given up on while flushing: the client won't get a response
+        // for the upload, which must not go through truncated
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+        // a registered upload stays so, for `DavFs::reap_idle_writes` to delete the file
+        // in case gofile.io already had all of it
+    }
 }
 
 impl std::fmt::Debug for DavFileWrite {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("DavFileWrite")
             .field("path", &self.path)
+            .field("append", &self.append)
+            .field("size", &self.size)
             .finish()
     }
 }
 
 impl DavFileWrite {
-    fn new(fs: DavFs, path: DavPath) -> Self {
+    fn new(fs: DavFs, path: DavPath, append: bool, size: Option<u64>) -> Self {
+        let pending = fs.pending_writes.track();
+        let progress = Progress::new(format!("upload of {}", path.as_url_string()), size);
+
         Self {
             fs,
             path,
+            append,
+            size,
             sender: None,
             handle: None,
+            replaces: None,
+            written: 0,
+            progress,
+            flushed: false,
+            upload_lock: None,
+            open_write: None,
+            _pending: pending,
+        }
+    }
+
+    /// Waits for the uploads to the same path started before this one.
+    async fn lock_path(&mut self) -> FsResult<()> {
+        if self.upload_lock.is_none() {
+            let path = normalize_path(&self.path)?;
+            let key = self.fs.cache_key(&path).into_owned();
+            self.upload_lock = Some(self.fs.upload_locks.lock(key).await);
+        }
+
+        Ok(())
+    }
+
+    /// Length of the upload when known up front, sent as its Content-Length instead of
+    /// streaming it chunked. Unknown when appending, the current contents come first.
+    fn upload_size(&self) -> Option<u64> {
+        self.size.filter(|_| !self.append)
+    }
+
+    /// Keeps the upload from being reaped while a chunk waits for gofile.io to take it,
+    /// see `OpenWrites::sending`.
+    fn sending(&self) -> Option<Sending> {
+        self.open_write.map(|id| self.fs.open_writes.sending(id))
+    }
+
+    /// Starts the upload, feeding it the current contents of the file first in append mode.
+    /// Each step waits at most for the operation timeout, the copy one chunk at a time.
+    async fn start_upload(&mut self) -> FsResult<()> {
+        let timeout = self.fs.operation_timeout;
+        let url = self.path.as_url_string();
+        let describe = || format!("upload to {url}");
+
+        let filename = self
+            .path
+            .file_name()
+            .map(String::from)
+            .ok_or(FsError::GeneralFailure)?;
+
+        let parent = self.path.parent();
+        let folder_entry = async {
+            self.lock_path().await?;
+            Ok(self.fs.find_or_create_folder(&parent).await?)
+        }
+        .timed(timeout, describe)
+        .await?;
+        let case_insensitive = self.fs.case_insensitive;
+        check_file_name_conflict(&folder_entry, &filename, case_insensitive)?;
+
+        let existing = find_file(&folder_entry, &filename, case_insensitive).cloned();
+        self.replaces = existing.as_ref().map(|file| file.id);
+        // a replaced file keeps its name, whatever case the client used
+        let filename = existing.as_ref().map_or(filename, |file| file.name.clone());
+
+        let folder_id = folder_entry.id;
+
+        let (tx, stream) = upload_channel(self.fs.upload_buffer);
+        let body = reqwest::Body::wrap_stream(stream);
+
+        let file_part = match self.upload_size() {
+            Some(size) => Part::stream_with_length(body, size),
+            None => Part::stream(body),
+        }
+        .file_name(filename);
+
+        let uploaded = Arc::new(OnceLock::new());
+        let handle = tokio::spawn({
+            let client = self.fs.client.clone();
+            let uploaded = uploaded.clone();
+            async move {
+                let result = client.upload_file(folder_id, file_part).await;
+                if let Ok(file) = &result {
+                    let _ = uploaded.set(file.id);
+                }
+                result
+            }
+        });
+
+        if self.fs.write_idle_timeout.is_some() {
+            let path = self.path.as_url_string();
+            let id = self
+                .fs
+                .open_writes
+                .open(path, handle.abort_handle(), uploaded);
+            self.open_write = Some(id);
+        }
+        self.handle = Some(handle);
+
+        if let Some(existing) = existing.filter(|_| self.append) {
+            let total_size = existing.size;
+            let mut file_from = DavFileRead::new(self.fs.clone(), existing);
+
+            while file_from.position != total_size {
+                let read = file_from.read_bytes(READ_BUF_SIZE).timed(timeout, describe);
+                let data = match read.await {
+                    Ok(data) if !data.is_empty() => data,
+                    result => {
+                        // fail the upload rather than leaving a truncated copy behind
+                        let error = io::Error::other("failed to read the file to append to");
+                        let _ = tx.send(Err(error)).await;
+
+                        return Err(result.err().unwrap_or(FsError::GeneralFailure));
+                    }
+                };
+
+                let _sending = self.sending();
+                async { tx.send(Ok(data)).await.map_err(|_| FsError::GeneralFailure) }
+                    .timed(timeout, describe)
+                    .await?;
+            }
         }
+
+        self.sender = Some(tx);
+
+        Ok(())
     }
 }
 
@@ -276,43 +965,35 @@ impl FsDavFile for DavFileWrite {
     }
 
     fn write_bytes(&'_ mut self, buf: bytes::Bytes) -> FsFuture<'_, ()> {
-        async move {
-            if self.sender.is_none() {
-                let (tx, rx) = mpsc::channel::<Result<Bytes, io::Error>>(1);
-
-                self.sender = Some(tx);
-
-                let stream = ReceiverStream::new(rx);
-                let body = reqwest::Body::wrap_stream(stream);
-
-                let filename = self
-                    .path
-                    .file_name()
-                    .map(String::from)
-                    .ok_or(FsError::GeneralFailure)?;
+        let timeout = self.fs.operation_timeout;
+        let path = self.path.clone();
 
-                let file_part = Part::stream(body).file_name(filename);
-
-                let folder_entry = self.fs.try_find_folder(&self.path.parent()).await?;
-
-                let folder_id = folder_entry.id;
-
-                let handle = tokio::spawn({
-                    let client = self.fs.client.clone();
-                    async move { client.upload_file(folder_id, file_part).await }
-                });
+        async move {
+            let len = buf.len() as u64;
+            if self.fs.dry_run {
+                self.written += len;
+                return Ok(());
+            }
 
-                self.handle = Some(handle);
+            if self.sender.is_none() {
+                self.start_upload().await?;
             }
 
-            match self.sender.as_mut() {
-                Some(sink) => {
-                    sink.send(Ok(buf))
-                        .await
-                        .map_err(|_| FsError::GeneralFailure)?;
-                }
-                None => return Err(FsError::GeneralFailure),
+            // starting it is timed step by step, an append may copy a large file first
+            let _sending = self.sending();
+            let sink = self.sender.as_ref().ok_or(FsError::GeneralFailure)?;
+            async {
+                sink.send(Ok(buf))
+                    .await
+                    .map_err(|_| FsError::GeneralFailure)
             }
+            .timed(timeout, move || {
+                format!("upload to {}", path.as_url_string())
+            })
+            .await?;
+
+            self.written += len;
+            self.progress.update(self.written);
 
             Ok(())
         }
@@ -328,29 +1009,61 @@ impl FsDavFile for DavFileWrite {
     }
 
     fn flush(&mut self) -> FsFuture<'_, ()> {
+        let timeout = self.fs.operation_timeout;
+        let path = self.path.clone();
+
         async move {
+            let written = self.written;
+            let path = &self.path;
+            if self
+                .fs
+                .skip_in_dry_run(|| format!("upload {written} bytes to {}", path.as_url_string()))
+            {
+                return Ok(());
+            }
+
+            // flushing again must not upload an empty file over the one just uploaded
+            if std::mem::replace(&mut self.flushed, true) {
+                return Ok(());
+            }
+
             // drop sender to close the stream
             self.sender.take();
 
+            // aborted by `DavFs::reap_idle_writes` while the client was silent
+            if let Some(id) = self.open_write.take()
+                && !self.fs.open_writes.close(id)
+            {
+                return Err(FsError::GeneralFailure);
+            }
+
             let filename = self
                 .path
                 .file_name()
                 .map(String::from)
                 .ok_or(FsError::GeneralFailure)?;
 
-            let folder_entry = self.fs.try_find_folder(&self.path.parent()).await?;
+            // left in place until it completes, so the upload is aborted if this is cancelled
+            let uploaded_id = if let Some(handle) = self.handle.as_mut() {
+                let uploaded = handle.await.map_err(io::Error::from)?;
+                self.handle = None;
+                uploaded?.id
+            } else {
+                self.lock_path().await?;
 
-            let mut to_delete: Vec<_> = folder_entry
-                .children
-                .values()
-                .filter(|v| !v.is_dir() && v.name() == filename)
-                .map(|v| v.id())
-                .collect();
+                let folder_entry = self.fs.find_or_create_folder(&self.path.parent()).await?;
+                let case_insensitive = self.fs.case_insensitive;
+                self.replaces =
+                    find_file(&folder_entry, &filename, case_insensitive).map(|file| file.id);
 
-            let uploaded_id = if let Some(handle) = self.handle.take() {
-                handle.await.map_err(io::Error::from)??.id
-            } else {
-                // create an empty file because write_bytes was never called, so its size is 0
+                if self.append && self.replaces.is_some() {
+                    // nothing was appended, the existing file stays as it is
+                    return Ok(());
+                }
+
+                // create an empty file because write_bytes was never called, so its size is 0.
+                // Some clients probe for directories this way, so don't shadow an existing folder.
+                check_file_name_conflict(&folder_entry, &filename, case_insensitive)?;
 
                 let file_part = Part::stream(Bytes::new()).file_name(filename.clone());
                 self.fs
@@ -360,14 +1073,28 @@ impl FsDavFile for DavFileWrite {
                     .id
             };
 
-            to_delete.retain(|&v| v != uploaded_id);
+            // only the file the client addressed, other files sharing its name on Gofile
+            // are listed under names of their own and stay untouched
+            let deleted = match self.replaces.take().filter(|&id| id != uploaded_id) {
+                Some(replaced) => self.fs.client.delete_contents(&[replaced]).await,
+                None => Ok(()),
+            };
+
+            self.fs.invalidate(&self.path).await?;
 
-            if !to_delete.is_empty() {
-                self.fs.client.delete_contents(&to_delete).await?;
+            // the upload went through, the old file is only left listed next to it
+            if let Err(e) = deleted {
+                warn!(
+                    "uploaded {} but failed to delete the file it replaces: {e}",
+                    self.path.as_url_string()
+                );
             }
 
             Ok(())
         }
+        .timed(timeout, move || {
+            format!("upload to {}", path.as_url_string())
+        })
         .boxed()
     }
 
@@ -376,442 +1103,4226 @@ impl FsDavFile for DavFileWrite {
     }
 }
 
-impl DavDirEntry for DirEntry {
-    fn name(&self) -> Vec<u8> {
-        self.name().as_bytes().to_vec()
-    }
-
-    fn metadata(&'_ self) -> FsFuture<'_, Box<dyn DavMetaData>> {
-        async move { Ok(Box::new(self.clone()) as Box<dyn DavMetaData>) }.boxed()
+/// The file a rename of `from` onto `to` replaces. A file replaces a file, a folder never
+/// replaces anything, and neither can take the place of the other kind.
+fn replaced_by_rename(from: &DirEntry, to: Option<&DirEntry>) -> FsResult<Option<Uuid>> {
+    match (from, to) {
+        (_, None) => Ok(None),
+        (DirEntry::File(_), Some(DirEntry::File(file_to))) => Ok(Some(file_to.id)),
+        (DirEntry::Folder(_), Some(DirEntry::Folder(_))) => Err(FsError::Exists),
+        (DirEntry::File(_), Some(DirEntry::Folder(_)))
+        | (DirEntry::Folder(_), Some(DirEntry::File(_))) => Err(FsError::Forbidden),
     }
 }
 
-#[derive(Clone)]
-pub struct DavFs {
-    client: Client,
-    dircache: Arc<RwLock<DirCache<String>>>,
-    write_enabled: bool,
+fn rename_operation(from: &DavPath, to: &DavPath) -> String {
+    format!("rename {} to {}", from.as_url_string(), to.as_url_string())
 }
 
-impl DavFs {
-    pub fn new(
-        client: Client,
-        dircache: Arc<RwLock<DirCache<String>>>,
-        write_enabled: bool,
-    ) -> Self {
-        Self {
-            client,
-            dircache,
-            write_enabled,
-        }
+/// The child of `parent` listed as `name`. Ignoring case, an exact match wins over the
+/// others, then the first of them by id.
+fn find_child<'a>(
+    parent: &'a FolderEntry,
+    name: &str,
+    case_insensitive: bool,
+) -> Option<&'a DirEntry> {
+    if let Some(child) = parent.children.values().find(|child| child.name() == name) {
+        return Some(child);
     }
 
-    pub fn new_boxed(
-        client: Client,
-        dircache: Arc<RwLock<DirCache<String>>>,
-        write_enabled: bool,
-    ) -> Box<Self> {
-        Box::new(Self::new(client, dircache, write_enabled))
+    if !case_insensitive {
+        return None;
     }
 
-    async fn remove(&self, path: &DavPath, remove_dir: bool) -> GofileResult<()> {
-        let contents = self.search(path).await?.ok_or(GofileError::NotFound)?;
-
-        match (&contents, remove_dir) {
-            (DirEntry::File(_), false) => {
-                self.client.delete_contents(&[contents.id()]).await?;
+    let matches: Vec<_> = parent
+        .children
+        .values()
+        .filter(|child| child.name().eq_ignore_ascii_case(name))
+        .collect();
+    if matches.len() > 1 {
+        warn!(
+            "{} entries of folder {} match {name:?} ignoring case, using the first by id",
+            matches.len(),
+            parent.id
+        );
+    }
 
-                Ok(())
-            }
-            (DirEntry::Folder(folder_entry), true) => {
-                if !folder_entry.children.is_empty() {
-                    return Err(GofileError::Forbidden);
-                }
+    matches.into_iter().min_by_key(|child| child.id())
+}
 
-                self.client.delete_contents(&[contents.id()]).await?;
+/// The file listed as `name` in `parent`
+fn find_file<'a>(
+    parent: &'a FolderEntry,
+    name: &str,
+    case_insensitive: bool,
+) -> Option<&'a FileEntry> {
+    match find_child(parent, name, case_insensitive)? {
+        DirEntry::File(file) => Some(file),
+        DirEntry::Folder(_) => None,
+    }
+}
 
-                Ok(())
-            }
-            (DirEntry::File(file_entry), true) => {
-                Err(anyhow!("expected to delete folder but found file {}", file_entry.id).into())
-            }
-            (DirEntry::Folder(folder_entry), false) => Err(anyhow!(
-                "expected to delete file but found folder {}",
-                folder_entry.id
-            )
-            .into()),
-        }
+/// Gofile lets several entries of a folder share a name, but WebDAV addresses them by name.
+/// The most recently created one keeps the name, so a file just written is found where the
+/// client wrote it. The others get ` (2)`, ` (3)`, ... before their extension, from the newest
+/// to the oldest, ties broken by id, skipping names already taken.
+fn disambiguate_names(children: &mut HashMap<Uuid, DirEntry>) {
+    let mut by_name: HashMap<String, Vec<(u64, Uuid)>> = HashMap::new();
+    for (id, child) in children.iter() {
+        by_name
+            .entry(child.name().to_string())
+            .or_default()
+            .push((child.created(), *id));
     }
 
-    async fn search(&self, path: &DavPathRef) -> GofileResult<Option<DirEntry>> {
-        let mut path = path.as_url_string();
-        path = if path.starts_with('/') {
-            path
-        } else {
-            format!("/{}", path)
-        };
-        path = path
-            .strip_suffix("/")
-            .map(|s| s.to_string())
-            .unwrap_or(path);
-        let mut path = percent_decode_str(&path)
-            .decode_utf8()
-            .context("Invalid UTF-8 in percent-decoded URI path")?
-            .to_string();
+    let mut taken: HashSet<String> = by_name.keys().cloned().collect();
 
-        let orig_path = path.clone();
+    // sorted so the suffixes don't depend on the iteration order of the map
+    let mut duplicates: Vec<_> = by_name
+        .into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .collect();
+    duplicates.sort();
 
-        let (mut current_path, mut current_id) = {
-            let dir_guard = self.dircache.read().await;
+    for (name, mut entries) in duplicates {
+        entries.sort_by_key(|&(created, id)| (Reverse(created), id));
+
+        let mut n = 1;
+        for (_, id) in entries.into_iter().skip(1) {
+            let Some(child) = children.get_mut(&id) else {
+                continue;
+            };
+
+            // a leading dot starts a hidden file's name rather than an extension
+            let (stem, extension) = match name.rfind('.') {
+                Some(dot) if dot > 0 && !child.is_dir() => name.split_at(dot),
+                _ => (name.as_str(), ""),
+            };
+
+            let new_name = loop {
+                n += 1;
+                let candidate = format!("{stem} ({n}){extension}");
+                if taken.insert(candidate.clone()) {
+                    break candidate;
+                }
+            };
+
+            match child {
+                DirEntry::File(file) => file.name = new_name,
+                DirEntry::Folder(folder) => folder.name = new_name,
+            }
+        }
+    }
+}
+
+/// Rejects writing a file whose name is already taken by a folder or a frozen file in `parent`.
+/// A frozen file listed as a placeholder takes the name it has on Gofile too.
+fn check_file_name_conflict(
+    parent: &FolderEntry,
+    name: &str,
+    case_insensitive: bool,
+) -> FsResult<()> {
+    let is_named = |child_name: &str| {
+        child_name == name || (case_insensitive && child_name.eq_ignore_ascii_case(name))
+    };
+
+    for child in parent.children.values() {
+        match child {
+            DirEntry::Folder(folder) if is_named(&folder.name) => return Err(FsError::Exists),
+            DirEntry::File(file)
+                if file.is_frozen
+                    && (is_named(&file.name)
+                        || file.name.strip_suffix(FROZEN_SUFFIX).is_some_and(is_named)) =>
+            {
+                return Err(FsError::Forbidden);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Frozen files can't be downloaded, and neither deleted, moved nor renamed from here
+fn is_frozen(entry: &DirEntry) -> bool {
+    matches!(entry, DirEntry::File(file) if file.is_frozen)
+}
+
+impl DavDirEntry for DirEntry {
+    fn name(&self) -> Vec<u8> {
+        self.name().as_bytes().to_vec()
+    }
+
+    fn metadata(&'_ self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        async move { Ok(Box::new(self.clone()) as Box<dyn DavMetaData>) }.boxed()
+    }
+}
+
+/// Normalizes a DAV path into the form used as a cache key, see [`normalize_url_path`].
+fn normalize_path(path: &DavPathRef) -> GofileResult<String> {
+    normalize_url_path(&path.as_url_string())
+}
+
+/// Percent-decodes a URL path and rebuilds it from its components: a leading `/`, no
+/// trailing or repeated `/`, `.` and `..` resolved. The root folder maps to `ROOT_DIR`.
+/// A path climbing above the root is forbidden rather than clamped to it.
+fn normalize_url_path(path: &str) -> GofileResult<String> {
+    let path = percent_decode_str(path)
+        .decode_utf8()
+        .context("Invalid UTF-8 in percent-decoded URI path")?;
+
+    let mut components = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop().ok_or(GofileError::Forbidden)?;
+            }
+            _ => components.push(component),
+        }
+    }
+
+    Ok(components.iter().map(|c| format!("/{c}")).collect())
+}
+
+/// Appends an entry name to a DAV path.
+fn join_path(parent: &DavPathRef, name: &str) -> FsResult<DavPath> {
+    let parent = parent.as_url_string();
+    let name = utf8_percent_encode(name, NON_ALPHANUMERIC);
+
+    DavPath::new(&format!("{}/{name}", parent.trim_end_matches('/')))
+        .map_err(|_| FsError::GeneralFailure)
+}
+
+/// Paths and codes of the folders directly inside `folder`, found at `path`.
+fn subfolders(path: &str, folder: &FolderEntry) -> Vec<(String, String)> {
+    folder
+        .children
+        .values()
+        .filter_map(|child| match child {
+            DirEntry::Folder(child) => Some((format!("{path}/{}", child.name), child.code.clone())),
+            DirEntry::File(_) => None,
+        })
+        .collect()
+}
+
+/// Whether the normalized `path` is `ancestor` itself or lies below it.
+fn is_within(path: &str, ancestor: &str) -> bool {
+    path == ancestor || path.starts_with(&format!("{ancestor}/"))
+}
+
+tokio::task_local! {
+    // Set once an operation of the request being handled timed out, see `report_timeouts`
+    static TIMED_OUT: std::cell::Cell<bool>;
+}
+
+/// Handles a request, telling whether one of its operations timed out. dav-server has
+/// no error for a timeout, the failure it answers with is better told as a 504.
+pub async fn report_timeouts<F: Future>(request: F) -> (F::Output, bool) {
+    TIMED_OUT
+        .scope(std::cell::Cell::new(false), async move {
+            let output = request.await;
+            (output, TIMED_OUT.with(std::cell::Cell::get))
+        })
+        .await
+}
+
+/// Gives up on operations taking too long, see [`DavFs::operation_timeout`]
+trait Timed<T>: Future<Output = FsResult<T>> + Send + Sized {
+    /// Fails with `GeneralFailure` once `timeout` passed, cancelling whatever the operation
+    /// was waiting for, and logs it as described. Waits as long as it takes without one.
+    /// Within [`report_timeouts`], the request is marked as timed out.
+    fn timed(
+        self,
+        timeout: Option<Duration>,
+        describe: impl FnOnce() -> String + Send,
+    ) -> impl Future<Output = FsResult<T>> + Send {
+        async move {
+            let Some(timeout) = timeout else {
+                return self.await;
+            };
+
+            tokio::time::timeout(timeout, self)
+                .await
+                .unwrap_or_else(|_| {
+                    warn!("{} timed out after {timeout:?}", describe());
+                    let _ = TIMED_OUT.try_with(|timed_out| timed_out.set(true));
+                    Err(FsError::GeneralFailure)
+                })
+        }
+    }
+}
+
+impl<T, F: Future<Output = FsResult<T>> + Send> Timed<T> for F {}
+
+// Short enough for clients polling metadata, long enough to absorb PROPFIND bursts
+const FILE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+// Account usage barely moves between PROPFINDs, a stale figure only skews the free space
+const QUOTA_TTL: Duration = Duration::from_secs(300);
+
+// Bytes used and available, or why they couldn't be fetched, see `DavFs::quota`
+type Quota = Result<(u64, u64), Arc<GofileError>>;
+
+// Free space reported when the tier of the account has no storage limit, 1 PiB
+const UNLIMITED_QUOTA: u64 = 1 << 50;
+
+const MOUNTS_MANIFEST_NAME: &str = ".mounts.json";
+
+// Keeps the comma-separated id list of a single delete call reasonably short
+const DELETE_BATCH_SIZE: usize = 100;
+
+// Folders listed at once while prefetching, on top of the requests of the client itself
+const PREFETCH_CONCURRENCY: usize = 4;
+
+// Prefetches running at once, listing a folder while they all run prefetches nothing
+const MAX_PREFETCHES: usize = 4;
+
+/// Appended to the name of frozen files listed with [`FrozenStrategy::Placeholder`]
+const FROZEN_SUFFIX: &str = ".frozen";
+
+/// How listings treat files gofile.io froze, which can't be downloaded anymore
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FrozenStrategy {
+    /// Leave them out of listings
+    #[default]
+    Hide,
+    /// List them as they are, reading them fails
+    Show,
+    /// List them as empty read-only files named with [`FROZEN_SUFFIX`]
+    Placeholder,
+}
+
+/// Where removed files go instead of being deleted, see [`DavFs::trash_folder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TrashLocation {
+    /// An existing folder, anywhere in the account
+    Id(Uuid),
+    /// A folder with this name right under the root of each mount, created on first use
+    Name(String),
+}
+
+impl TrashLocation {
+    fn parse(id_or_name: &str) -> Self {
+        match Uuid::parse_str(id_or_name) {
+            Ok(id) => Self::Id(id),
+            Err(_) => Self::Name(id_or_name.to_string()),
+        }
+    }
+}
+
+/// The trash folder of a mount once looked up or created
+#[derive(Debug, Clone)]
+struct TrashFolder {
+    id: Uuid,
+    code: String,
+}
+
+impl TrashFolder {
+    /// Whether `code_or_id`, as kept in a dircache, points to this folder.
+    fn is(&self, code_or_id: &str) -> bool {
+        self.code == code_or_id || self.id.to_string() == code_or_id
+    }
+}
+
+/// Counts the files opened for writing that haven't been dropped yet,
+/// so shutdown can wait for their uploads to finish.
+#[derive(Clone)]
+pub struct PendingWrites(Arc<watch::Sender<usize>>);
+
+impl Default for PendingWrites {
+    fn default() -> Self {
+        Self(Arc::new(watch::Sender::new(0)))
+    }
+}
+
+impl PendingWrites {
+    pub fn count(&self) -> usize {
+        *self.0.borrow()
+    }
+
+    /// Resolves once no write is in flight.
+    pub async fn drained(&self) {
+        let _ = self.0.subscribe().wait_for(|&count| count == 0).await;
+    }
+
+    fn track(&self) -> PendingWrite {
+        self.0.send_modify(|count| *count += 1);
+        PendingWrite(self.clone())
+    }
+}
+
+struct PendingWrite(PendingWrites);
+
+impl Drop for PendingWrite {
+    fn drop(&mut self) {
+        self.0.0.send_modify(|count| *count -= 1);
+    }
+}
+
+/// Uploads of the writes not flushed yet, with when they last got data from the client,
+/// so `DavFs::reap_idle_writes` can get rid of the ones their client abandoned. Only
+/// filled with `--write-idle-timeout`.
+#[derive(Clone, Default)]
+struct OpenWrites {
+    writes: Arc<std::sync::Mutex<HashMap<u64, OpenWrite>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+struct OpenWrite {
+    path: String,
+    // When a chunk last went through, or started waiting for the upload to take it
+    last_write: Instant,
+    // Chunks waiting for the upload to take them, gofile.io is the slow one then
+    sending: usize,
+    upload: AbortHandle,
+    // Set by the upload once gofile.io stored the file
+    uploaded: Arc<OnceLock<Uuid>>,
+}
+
+impl OpenWrites {
+    /// Registers the upload of a write, returning the id to refer to it by.
+    fn open(&self, path: String, upload: AbortHandle, uploaded: Arc<OnceLock<Uuid>>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let write = OpenWrite {
+            path,
+            last_write: Instant::now(),
+            sending: 0,
+            upload,
+            uploaded,
+        };
+        self.writes.lock().unwrap().insert(id, write);
+
+        id
+    }
+
+    /// Marks a chunk of the write as waiting for the upload until the guard is dropped.
+    /// The write is active in the meantime, its idle time counts from when it's done.
+    fn sending(&self, id: u64) -> Sending {
+        self.update(id, |write| write.sending += 1);
+
+        Sending {
+            writes: self.clone(),
+            id,
+        }
+    }
+
+    fn update(&self, id: u64, update: impl FnOnce(&mut OpenWrite)) {
+        if let Some(write) = self.writes.lock().unwrap().get_mut(&id) {
+            update(write);
+            write.last_write = Instant::now();
+        }
+    }
+
+    /// Forgets a flushed write, returning false if it was reaped already.
+    fn close(&self, id: u64) -> bool {
+        self.writes.lock().unwrap().remove(&id).is_some()
+    }
+
+    /// Takes out the writes that got no data for `timeout`.
+    fn take_idle(&self, timeout: Duration) -> Vec<OpenWrite> {
+        let mut writes = self.writes.lock().unwrap();
+        let idle: Vec<_> = writes
+            .iter()
+            .filter(|(_, write)| write.sending == 0 && write.last_write.elapsed() >= timeout)
+            .map(|(&id, _)| id)
+            .collect();
+
+        idle.into_iter()
+            .filter_map(|id| writes.remove(&id))
+            .collect()
+    }
+}
+
+/// A chunk of a write waiting for its upload, see [`OpenWrites::sending`]
+struct Sending {
+    writes: OpenWrites,
+    id: u64,
+}
+
+impl Drop for Sending {
+    fn drop(&mut self) {
+        self.writes.update(self.id, |write| write.sending -= 1);
+    }
+}
+
+/// One lock per path being written to. An upload only starts once the previous upload
+/// to the same path replaced the file and sees the file it left, and a missing folder is
+/// only created by the first upload needing it.
+#[derive(Clone, Default)]
+struct PathLocks(Arc<std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>>);
+
+impl PathLocks {
+    async fn lock(&self, path: String) -> PathLock {
+        // never held across an await
+        let lock = self
+            .0
+            .lock()
+            .unwrap()
+            .entry(path.clone())
+            .or_default()
+            .clone();
+        let guard = lock.lock_owned().await;
+
+        PathLock {
+            locks: self.clone(),
+            path,
+            guard: Some(guard),
+        }
+    }
+}
+
+struct PathLock {
+    locks: PathLocks,
+    path: String,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for PathLock {
+    fn drop(&mut self) {
+        self.guard.take();
+
+        // forget the lock once nobody holds or waits for it
+        let mut locks = self.locks.0.lock().unwrap();
+        if locks
+            .get(&self.path)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.path);
+        }
+    }
+}
+
+/// A Gofile folder served by [`DavFs`]. Paths in its caches are relative to the mount point.
+struct Mount {
+    dircache: Arc<RwLock<DirCache<String>>>,
+    filecache: RwLock<FileCache<FileEntry>>,
+    // Listings fetched ahead of the client by `--prefetch-depth`
+    prefetched: RwLock<FileCache<FolderEntry>>,
+    // Root listing fetched at startup by `--prewarm`, cached once first looked up
+    prewarmed: Mutex<Option<FolderEntry>>,
+    // Bumped by every invalidation, see `prefetch`
+    generation: AtomicU64,
+    dircache_entries: IntGauge,
+    // Resolved on the first removal when `--trash-folder` is set
+    trash: Mutex<Option<TrashFolder>>,
+}
+
+#[derive(Clone)]
+pub struct DavFs {
+    client: Client,
+    // A single mount named `ROOT_DIR` is served as the root itself,
+    // otherwise every mount is a top-level directory
+    mounts: Arc<BTreeMap<String, Mount>>,
+    write_enabled: bool,
+    // Mutations are refused while set, shared by every clone, see `toggle_writes`
+    writes_paused: Arc<AtomicBool>,
+    mounts_manifest: Option<VirtualFile>,
+    download_cache: Option<Arc<DownloadCache>>,
+    verify_md5: bool,
+    download_rate: Option<Arc<RateLimiter>>,
+    // Chunks downloaded at once from the servers of a file, 1 streams from its link only
+    parallel_downloads: usize,
+    // Times a dropped download is reopened where it stopped before the read fails
+    download_reconnects: u32,
+    // Bytes downloaded ahead of the reader by a background task, 0 reads on demand
+    read_ahead: usize,
+    // Bytes written by a client and not yet taken by the upload, a single chunk if not set
+    upload_buffer: Option<usize>,
+    // Writes never replace an existing file or folder, see `create_only`
+    create_only: bool,
+    // Larger files can't be downloaded, unlimited if not set
+    max_file_size: Option<u64>,
+    // Operations fail once they take longer, see `operation_timeout`
+    operation_timeout: Option<Duration>,
+    // Uploads without data for longer are aborted, see `reap_idle_writes`
+    write_idle_timeout: Option<Duration>,
+    open_writes: OpenWrites,
+    // Uploads create the missing folders above them, see `create_parents`
+    create_parents: bool,
+    recursive_delete: bool,
+    // Mutations are logged instead of sent to Gofile when set
+    dry_run: bool,
+    trash: Option<TrashLocation>,
+    frozen_strategy: FrozenStrategy,
+    prefetch_depth: usize,
+    // One per prefetch running in the background, shared by every clone
+    prefetches: Arc<Semaphore>,
+    // Cache keys are lowercased when set, see `cache_key`
+    case_insensitive: bool,
+    pending_writes: PendingWrites,
+    // One per file being uploaded, see `DavFileWrite::lock_path`
+    upload_locks: PathLocks,
+    // One per folder being created for an upload, see `find_or_create_folder`
+    folder_locks: PathLocks,
+    // Caps the listings requested from Gofile at once, unlimited if not set
+    listing_permits: Option<Arc<Semaphore>>,
+    listings: InFlight<DirEntry>,
+    // When the storage used and allowed by the account were fetched, see `quota`
+    quota: Arc<Mutex<Option<(Instant, Quota)>>>,
+}
+
+impl DavFs {
+    /// Serves each folder as a top-level directory named after its mount.
+    /// A single mount with an empty name is served as the root instead.
+    pub fn new(
+        client: Client,
+        mounts: impl IntoIterator<Item = (String, Arc<RwLock<DirCache<String>>>)>,
+        write_enabled: bool,
+    ) -> Self {
+        let mounts = mounts
+            .into_iter()
+            .map(|(name, dircache)| {
+                let label = if name == ROOT_DIR { "/" } else { name.as_str() };
+                let mount = Mount {
+                    dircache,
+                    filecache: RwLock::new(FileCache::new(FILE_CACHE_TTL)),
+                    prefetched: RwLock::new(FileCache::new(FILE_CACHE_TTL)),
+                    prewarmed: Mutex::new(None),
+                    generation: AtomicU64::new(0),
+                    dircache_entries: metrics::dir_cache_entries(label),
+                    trash: Mutex::new(None),
+                };
+
+                (name, mount)
+            })
+            .collect();
+
+        Self {
+            client,
+            mounts: Arc::new(mounts),
+            write_enabled,
+            writes_paused: Arc::default(),
+            mounts_manifest: None,
+            download_cache: None,
+            verify_md5: false,
+            download_rate: None,
+            parallel_downloads: 1,
+            download_reconnects: DEFAULT_DOWNLOAD_RECONNECTS,
+            read_ahead: 0,
+            upload_buffer: None,
+            create_only: false,
+            max_file_size: None,
+            operation_timeout: None,
+            write_idle_timeout: None,
+            open_writes: OpenWrites::default(),
+            create_parents: false,
+            recursive_delete: false,
+            dry_run: false,
+            trash: None,
+            frozen_strategy: FrozenStrategy::default(),
+            prefetch_depth: 0,
+            prefetches: Arc::new(Semaphore::new(MAX_PREFETCHES)),
+            case_insensitive: false,
+            pending_writes: PendingWrites::default(),
+            upload_locks: PathLocks::default(),
+            folder_locks: PathLocks::default(),
+            listing_permits: None,
+            listings: InFlight::default(),
+            quota: Arc::default(),
+        }
+    }
+
+    /// Serves `data` as a read-only `/.mounts.json` describing the configured mounts.
+    pub fn with_mounts_manifest(mut self, data: impl Into<Bytes>) -> Self {
+        self.mounts_manifest = Some(VirtualFile::new(MOUNTS_MANIFEST_NAME, data));
+        self
+    }
+
+    /// Keeps downloaded files in `cache` so reading them again doesn't hit Gofile.
+    pub fn with_download_cache(mut self, cache: DownloadCache) -> Self {
+        self.download_cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Checks the md5 of downloads that cover a whole file, failing the read on a mismatch.
+    pub fn verify_md5(mut self, verify: bool) -> Self {
+        self.verify_md5 = verify;
+        self
+    }
+
+    /// Caps the bytes per second read from Gofile, shared by every download.
+    pub fn max_download_rate(mut self, bytes_per_sec: u64) -> Self {
+        self.download_rate = Some(Arc::new(RateLimiter::new(bytes_per_sec)));
+        self
+    }
+
+    /// Downloads files hosted on several servers `chunks` chunks at a time, spread over
+    /// the servers. `1` streams from the server gofile.io selected.
+    pub fn parallel_downloads(mut self, chunks: usize) -> Self {
+        self.parallel_downloads = chunks.max(1);
+        self
+    }
+
+    /// Requests at most `listings` folder listings from Gofile at once, whatever the
+    /// number of clients. The others wait for a slot.
+    pub fn max_concurrent_listings(mut self, listings: usize) -> Self {
+        self.listing_permits = Some(Arc::new(Semaphore::new(listings.max(1))));
+        self
+    }
+
+    /// Fails an operation of a client still running after `timeout`, cancelling the
+    /// requests to Gofile it was waiting for. Reading and writing a file are timed per
+    /// chunk instead, so long transfers only fail once they stall.
+    pub fn operation_timeout(mut self, timeout: Duration) -> Self {
+        self.operation_timeout = Some(timeout);
+        self
+    }
+
+    /// Aborts an upload once its client sent nothing for `timeout`, see `reap_idle_writes`.
+    pub fn write_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.write_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Reopens a download that fails midway at the offset it reached, up to `reconnects`
+    /// times in a row before the read fails. `0` fails it right away.
+    pub fn download_reconnects(mut self, reconnects: u32) -> Self {
+        self.download_reconnects = reconnects;
+        self
+    }
+
+    /// Keeps downloading up to `bytes` ahead of what clients have read, so sequential reads
+    /// are mostly served from memory. `0` only downloads what is asked for.
+    pub fn read_ahead(mut self, bytes: usize) -> Self {
+        self.read_ahead = bytes;
+        self
+    }
+
+    /// Lets clients write up to `bytes` ahead of what the upload to Gofile has taken before
+    /// they wait, instead of a single chunk.
+    pub fn upload_buffer(mut self, bytes: usize) -> Self {
+        self.upload_buffer = Some(bytes);
+        self
+    }
+
+    /// Refuses to download files larger than `bytes`, before anything is requested.
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Creates the missing folders above an uploaded file instead of failing with
+    /// `NotFound`, as if the client sent a MKCOL for each of them first.
+    pub fn create_parents(mut self, create_parents: bool) -> Self {
+        self.create_parents = create_parents;
+        self
+    }
+
+    /// Lets removing a non-empty folder delete everything below it in batched calls
+    /// instead of failing.
+    pub fn allow_recursive_delete(mut self, allow: bool) -> Self {
+        self.recursive_delete = allow;
+        self
+    }
+
+    /// Logs what writes would do instead of doing them. Reads still hit Gofile, so
+    /// clients see the tree as it is.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Moves removed files and folders to a trash folder instead of deleting them.
+    /// `id_or_name` is either the id of an existing folder or the name of a folder
+    /// created under the root of each mount. Removing from the trash deletes for good.
+    pub fn trash_folder(mut self, id_or_name: &str) -> Self {
+        self.trash = Some(TrashLocation::parse(id_or_name));
+        self
+    }
+
+    /// Chooses how frozen files show up in listings, hidden by default.
+    pub fn frozen_strategy(mut self, strategy: FrozenStrategy) -> Self {
+        self.frozen_strategy = strategy;
+        self
+    }
+
+    /// Lists subfolders `depth` levels below a listed folder in the background,
+    /// so clients walking the tree find them ready. `0` disables it.
+    pub fn prefetch_depth(mut self, depth: usize) -> Self {
+        self.prefetch_depth = depth;
+        self
+    }
+
+    /// Resolves paths ignoring ASCII case, as Windows clients expect. Entries keep their
+    /// actual names in listings.
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Refuses every write to a path that already exists, as if each client asked for it
+    /// with `If-None-Match: *`. The server turns the requests into conditional ones, see
+    /// [`Self::is_create_only`].
+    pub fn create_only(mut self, create_only: bool) -> Self {
+        self.create_only = create_only;
+        self
+    }
+
+    /// Whether writes must not replace existing entries.
+    pub fn is_create_only(&self) -> bool {
+        self.create_only
+    }
+
+    /// Whether removing a non-empty folder deletes everything below it.
+    pub fn is_recursive_delete(&self) -> bool {
+        self.recursive_delete
+    }
+
+    /// Bytes stored by the account and the bytes it can hold in total, fetched at most
+    /// once per `QUOTA_TTL`, failing or not. A tier without a storage limit gets
+    /// `UNLIMITED_QUOTA` free.
+    async fn quota(&self) -> GofileResult<(u64, u64)> {
+        // held while fetching, so a PROPFIND burst asks once
+        let mut quota = self.quota.lock().await;
+        if let Some((fetched, result)) = &*quota
+            && fetched.elapsed() < QUOTA_TTL
+        {
+            return result.clone().map_err(GofileError::Shared);
+        }
+
+        let result = self
+            .client
+            .get_current_account_info()
+            .await
+            .map(|account| {
+                let used = account.stats_current.storage;
+                let total = account
+                    .subscription_limit_storage
+                    .unwrap_or(used.saturating_add(UNLIMITED_QUOTA));
+                (used, total)
+            })
+            .map_err(Arc::new);
+        *quota = Some((Instant::now(), result.clone()));
+
+        result.map_err(GofileError::Shared)
+    }
+
+    /// Runs for as long as the server, aborting the uploads that got no data from their
+    /// client for `--write-idle-timeout`. A file gofile.io got in full before the client
+    /// went away is deleted, as no flush will replace the old one with it. Returns right
+    /// away without a timeout.
+    pub async fn reap_idle_writes(self) {
+        let Some(timeout) = self.write_idle_timeout else {
+            return;
+        };
+
+        let mut interval = tokio::time::interval((timeout / 4).max(Duration::from_secs(1)));
+        loop {
+            interval.tick().await;
+
+            for write in self.open_writes.take_idle(timeout) {
+                warn!(
+                    "upload to {} got no data for {timeout:?}, aborting it",
+                    write.path
+                );
+                write.upload.abort();
+
+                if let Some(&id) = write.uploaded.get()
+                    && let Err(e) = self.client.delete_contents(&[id]).await
+                {
+                    warn!(
+                        "failed to delete {id}, left by the upload to {}: {e}",
+                        write.path
+                    );
+                }
+            }
+        }
+    }
+
+    /// Pauses writes when they are accepted, resumes them otherwise, and returns whether
+    /// they are paused now. Meant for maintenance, reads carry on either way.
+    pub fn toggle_writes(&self) -> bool {
+        let paused = !self.writes_paused.fetch_xor(true, Ordering::SeqCst);
+        if paused {
+            info!("Writes paused, every mutation is refused until they are resumed");
+        } else {
+            info!("Writes resumed");
+        }
+
+        paused
+    }
+
+    /// Served read-write, and writes aren't paused by `toggle_writes`
+    fn is_writable(&self) -> bool {
+        self.write_enabled && !self.writes_paused.load(Ordering::SeqCst)
+    }
+
+    /// Tracks the uploads still in flight, shared by every clone of this filesystem.
+    pub fn pending_writes(&self) -> PendingWrites {
+        self.pending_writes.clone()
+    }
+
+    /// Keeps `folder`, the freshly listed root of the mount named `mount`, so the first
+    /// requests after startup don't wait for it. It is only cached, and starts expiring,
+    /// once first looked up. Returns the number of entries listed.
+    pub async fn prewarm(&self, mount: &str, folder: FolderEntry) -> usize {
+        let Some(mount) = self.mounts.get(mount) else {
+            return 0;
+        };
+
+        let entries = folder.children.len();
+        *mount.prewarmed.lock().await = Some(folder);
+
+        entries
+    }
+
+    /// Caches the listing kept by [`Self::prewarm`] for `mount`, if not used yet.
+    async fn use_prewarmed(&self, mount: &Mount) {
+        let generation = mount.generation.load(Ordering::SeqCst);
+        let Some(mut folder) = mount.prewarmed.lock().await.take() else {
+            return;
+        };
+
+        if !self
+            .cache_listing_since(mount, ROOT_DIR, &mut folder, Some(generation))
+            .await
+        {
+            return;
+        }
+
+        let mut prefetched = mount.prefetched.write().await;
+        if mount.generation.load(Ordering::SeqCst) == generation {
+            prefetched.insert_file(ROOT_DIR, folder);
+        }
+    }
+
+    /// Whether `operation` must be skipped because of [`Self::dry_run`], logging it if so.
+    fn skip_in_dry_run(&self, operation: impl FnOnce() -> String) -> bool {
+        if self.dry_run {
+            info!("dry run: {}", operation());
+        }
+
+        self.dry_run
+    }
+
+    /// The key of `path` in the caches of a mount. Lowercasing ASCII keeps its length,
+    /// so a key and its path can be sliced at the same positions.
+    fn cache_key<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        if self.case_insensitive {
+            Cow::Owned(path.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(path)
+        }
+    }
+
+    /// Whether `to` is `from` itself or lies below it, where a copy or move would loop into
+    /// its own tree. A name changing only in case is a rename, not a move onto itself.
+    fn is_into_itself(&self, from: &DavPathRef, to: &DavPathRef) -> GofileResult<bool> {
+        let (from, to) = (normalize_path(from)?, normalize_path(to)?);
+        let (from_key, to_key) = (self.cache_key(&from), self.cache_key(&to));
+
+        if from != to && from_key == to_key {
+            return Ok(false);
+        }
+
+        Ok(is_within(&to_key, &from_key))
+    }
+
+    /// Returns the server-generated file living at `path`, if any.
+    fn find_virtual(&self, path: &DavPathRef) -> Option<&VirtualFile> {
+        let manifest = self.mounts_manifest.as_ref()?;
+        let path = normalize_path(path).ok()?;
+
+        (path == format!("/{}", manifest.name())).then_some(manifest)
+    }
+
+    /// Splits a normalized path into the mount serving it and the path inside that mount.
+    /// `None` stands for the top-level directory of a multi-mount setup.
+    fn resolve<'a>(&self, path: &'a str) -> GofileResult<Option<(&Mount, &'a str)>> {
+        if let Some(mount) = self.mounts.get(ROOT_DIR) {
+            return Ok(Some((mount, path)));
+        }
+
+        if path == ROOT_DIR {
+            return Ok(None);
+        }
+
+        let rest = path
+            .strip_prefix('/')
+            .context("Expected a path-like string starting with '/'")?;
+        let name = rest.split_once('/').map_or(rest, |(name, _)| name);
+        let mount = self.mounts.get(name).ok_or(GofileError::NotFound)?;
+
+        Ok(Some((mount, &path[name.len() + 1..])))
+    }
+
+    /// Whether `path` is the synthetic top-level directory listing the mounts.
+    fn is_top_level(&self, path: &DavPathRef) -> GofileResult<bool> {
+        Ok(!self.mounts.contains_key(ROOT_DIR) && normalize_path(path)? == ROOT_DIR)
+    }
+
+    /// Whether `path` is the top-level directory or one of the mount points of a
+    /// multi-mount setup. Removing those would delete the mounted Gofile folder itself.
+    fn is_mount_point(&self, path: &DavPathRef) -> GofileResult<bool> {
+        if self.mounts.contains_key(ROOT_DIR) {
+            return Ok(false);
+        }
+
+        let path = normalize_path(path)?;
+
+        Ok(matches!(self.resolve(&path)?, None | Some((_, ROOT_DIR))))
+    }
+
+    async fn remove(&self, path: &DavPath, remove_dir: bool) -> GofileResult<()> {
+        if !self.is_writable() || self.find_virtual(path).is_some() || self.is_mount_point(path)? {
+            return Err(GofileError::Forbidden);
+        }
+
+        let contents = self.search(path).await?.ok_or(GofileError::NotFound)?;
+        if is_frozen(&contents) {
+            return Err(GofileError::Forbidden);
+        }
+
+        match (&contents, remove_dir) {
+            (DirEntry::File(file_entry), true) => {
+                return Err(
+                    anyhow!("expected to delete folder but found file {}", file_entry.id).into(),
+                );
+            }
+            (DirEntry::Folder(folder_entry), false) => {
+                return Err(anyhow!(
+                    "expected to delete file but found folder {}",
+                    folder_entry.id
+                )
+                .into());
+            }
+            _ => {}
+        }
+
+        if self.skip_in_dry_run(|| format!("delete {}", path.as_url_string())) {
+            return Ok(());
+        }
+
+        if let Some(location) = &self.trash {
+            let full_path = normalize_path(path)?;
+            let (mount, mount_path) = self.resolve(&full_path)?.ok_or(GofileError::Forbidden)?;
+            let trash = self.trash_folder_of(mount, location).await?;
+
+            if !self.in_trash(mount, mount_path, &trash).await {
+                self.move_to_trash(mount_path, &contents, &trash).await?;
+                self.invalidate(path).await?;
+                if let TrashLocation::Name(name) = location {
+                    self.invalidate_in(mount, &format!("/{name}")).await;
+                }
+
+                return Ok(());
+            }
+
+            if trash.id == contents.id() {
+                // recreated on the next removal
+                *mount.trash.lock().await = None;
+            }
+        }
+
+        if let DirEntry::Folder(folder_entry) = &contents
+            && !folder_entry.children.is_empty()
+        {
+            if !self.recursive_delete {
+                return Err(GofileError::Forbidden);
+            }
+
+            let descendants = self.collect_descendants(folder_entry).await?;
+            for batch in descendants.chunks(DELETE_BATCH_SIZE) {
+                self.client.delete_contents(batch).await?;
+            }
+        }
+
+        self.client.delete_contents(&[contents.id()]).await?;
+        self.invalidate(path).await?;
+
+        Ok(())
+    }
+
+    /// The trash folder of `mount`, looked up or created the first time.
+    async fn trash_folder_of(
+        &self,
+        mount: &Mount,
+        location: &TrashLocation,
+    ) -> GofileResult<TrashFolder> {
+        let mut trash_guard = mount.trash.lock().await;
+        if let Some(trash) = trash_guard.as_ref() {
+            return Ok(trash.clone());
+        }
+
+        let trash = match location {
+            TrashLocation::Id(id) => match self.get_contents(*id).await? {
+                DirEntry::Folder(folder) => TrashFolder {
+                    id: folder.id,
+                    code: folder.code,
+                },
+                DirEntry::File(_) => return Err(anyhow!("trash folder {id} is a file").into()),
+            },
+            TrashLocation::Name(name) => {
+                let root = mount
+                    .dircache
+                    .write()
+                    .await
+                    .find_dir(ROOT_DIR)
+                    .cloned()
+                    .context("mount without a root folder")?;
+                let DirEntry::Folder(root) = self.get_contents(root.as_str()).await? else {
+                    return Err(anyhow!("root of the mount is a file").into());
+                };
+
+                match find_child(&root, name, self.case_insensitive) {
+                    Some(DirEntry::Folder(folder)) => TrashFolder {
+                        id: folder.id,
+                        code: folder.code.clone(),
+                    },
+                    Some(DirEntry::File(file)) => {
+                        return Err(
+                            anyhow!("trash folder {name:?} is taken by file {}", file.id).into(),
+                        );
+                    }
+                    None => {
+                        let created = self.client.create_folder(root.id, name).await?;
+                        // the root listing now has the trash in it
+                        self.invalidate_in(mount, &format!("/{name}")).await;
+
+                        TrashFolder {
+                            id: created.id,
+                            code: created.code,
+                        }
+                    }
+                }
+            }
+        };
+
+        *trash_guard = Some(trash.clone());
+
+        Ok(trash)
+    }
+
+    /// Whether `path` is the trash folder or below it, going by the folders cached on
+    /// the way down to it.
+    async fn in_trash(&self, mount: &Mount, path: &str, trash: &TrashFolder) -> bool {
+        let key = self.cache_key(path);
+        let mut dir_guard = mount.dircache.write().await;
+        let mut current: &str = &key;
+
+        loop {
+            if dir_guard
+                .find_dir(current)
+                .is_some_and(|code| trash.is(code))
+            {
+                return true;
+            }
+            if current == ROOT_DIR {
+                return false;
+            }
+
+            current = current
+                .rsplit_once('/')
+                .map_or(ROOT_DIR, |(parent, _)| parent);
+        }
+    }
+
+    /// Moves `contents`, found at `path` in its mount, to the same path inside the trash.
+    /// Missing folders on the way are created, so emptying a folder file by file and then
+    /// removing it keeps its tree in the trash.
+    async fn move_to_trash(
+        &self,
+        path: &str,
+        contents: &DirEntry,
+        trash: &TrashFolder,
+    ) -> GofileResult<()> {
+        let parent = path.rsplit_once('/').map_or(ROOT_DIR, |(parent, _)| parent);
+
+        let mut dest = self.get_folder(trash.id).await?;
+        for name in parent.split('/').filter(|name| !name.is_empty()) {
+            let folder_id = match find_child(&dest, name, false) {
+                Some(DirEntry::Folder(folder)) => folder.id,
+                _ => self.client.create_folder(dest.id, name).await?.id,
+            };
+            dest = self.get_folder(folder_id).await?;
+        }
+
+        // an emptied folder whose files already went to the trash is there already
+        if let DirEntry::Folder(folder) = contents
+            && folder.children.is_empty()
+            && matches!(
+                find_child(&dest, &folder.name, false),
+                Some(DirEntry::Folder(_))
+            )
+        {
+            self.client.delete_contents(&[folder.id]).await?;
+            return Ok(());
+        }
+
+        self.client.move_contents(&[contents.id()], dest.id).await
+    }
+
+    /// Lists `id` on Gofile, waiting for a slot when [`Self::max_concurrent_listings`] is set.
+    /// Callers asking for a folder already being listed share that listing.
+    async fn get_contents(&self, id: impl Into<IdOrCode> + Send) -> GofileResult<DirEntry> {
+        let id = id.into();
+        let key = id.to_string();
+        let client = self.client.clone();
+        let permits = self.listing_permits.clone();
+
+        let fetch = async move {
+            let _permit = match &permits {
+                Some(permits) => Some(permits.acquire().await.context("listing slots closed")?),
+                None => None,
+            };
+
+            client.get_contents(id).await
+        };
+
+        self.listings.run(key, fetch).await
+    }
+
+    async fn get_folder(&self, id: Uuid) -> GofileResult<FolderEntry> {
+        match self.get_contents(id).await? {
+            DirEntry::Folder(folder) => Ok(folder),
+            DirEntry::File(_) => Err(anyhow!("expected folder but found file {id}").into()),
+        }
+    }
+
+    /// Ids of everything below `folder`, each folder after its contents so no batch
+    /// removes what a later one still names. Listings only carry direct children,
+    /// so every subfolder is listed in turn, all of them before anything is deleted.
+    async fn collect_descendants(&self, folder: &FolderEntry) -> GofileResult<Vec<Uuid>> {
+        let mut ids = Vec::new();
+        let mut pending: Vec<Uuid> = Vec::new();
+
+        let mut visit = |folder: &FolderEntry, pending: &mut Vec<_>| {
+            for child in folder.children.values() {
+                ids.push(child.id());
+
+                if let DirEntry::Folder(subfolder) = child {
+                    pending.push(subfolder.id);
+                }
+            }
+        };
+
+        visit(folder, &mut pending);
+
+        while let Some(folder_id) = pending.pop() {
+            if let DirEntry::Folder(subfolder) = self.get_contents(folder_id).await? {
+                visit(&subfolder, &mut pending);
+            }
+        }
+
+        // folders were seen before their children
+        ids.reverse();
+        Ok(ids)
+    }
+
+    async fn copy_file(&self, file_from: FileEntry, to: &DavPath) -> FsResult<()> {
+        if file_from.is_frozen {
+            return Err(FsError::Forbidden);
+        }
+
+        let mut file_from = DavFileRead::new(self.clone(), file_from);
+        let mut file_to =
+            DavFileWrite::new(self.clone(), to.clone(), false, Some(file_from.file.size));
+
+        let total_size = file_from.file.size;
+
+        let mut pos: u64 = 0;
+
+        while pos != total_size {
+            let offset = std::io::SeekFrom::Start(pos);
+            file_from.seek(offset).await?;
+
+            let data = file_from.read_bytes(READ_BUF_SIZE).await?;
+            let bytes_read = data.len();
+
+            file_to.write_bytes(data).await?;
+
+            pos += bytes_read as u64;
+        }
+
+        file_to.flush().await?;
+
+        Ok(())
+    }
+
+    async fn search(&self, path: &DavPathRef) -> GofileResult<Option<DirEntry>> {
+        let full_path = normalize_path(path)?;
+
+        // The top-level directory of a multi-mount setup has no Gofile folder behind it
+        let (mount, orig_path) = self.resolve(&full_path)?.ok_or(GofileError::Forbidden)?;
+        let key = self.cache_key(orig_path);
+        self.use_prewarmed(mount).await;
+
+        if let Some(file) = mount.filecache.read().await.find_file(&key) {
+            return Ok(Some(DirEntry::File(file)));
+        }
+
+        // the file was seen before, asking for it alone is cheaper than listing its folder
+        let known = mount.filecache.read().await.find_expired(&key);
+        if let Some(known) = known
+            && let Ok(DirEntry::File(file)) = self.get_contents(known.id).await
+            && is_same_file(&known, &file)
+        {
+            mount
+                .filecache
+                .write()
+                .await
+                .insert_file(key.into_owned(), file.clone());
+            return Ok(Some(DirEntry::File(file)));
+        }
+
+        let (cached_len, mut current_id) = {
+            // write lock: lookups bump the entry's recency
+            let mut dir_guard = mount.dircache.write().await;
+            let mut path: &str = &key;
             loop {
-                if let Some(id) = dir_guard.find_dir(&path) {
-                    break (path.clone(), id.to_owned());
+                if let Some(id) = dir_guard.find_dir(path) {
+                    break (path.len(), id.to_owned());
+                } else {
+                    let (parent_path, _) = path
+                        .rsplit_once('/')
+                        .context("Expected a path-like string starting with '/'")?;
+
+                    path = parent_path
+                }
+            }
+        };
+
+        if cached_len == orig_path.len() {
+            if let Some(folder) = mount.prefetched.read().await.find_file(&key) {
+                return Ok(Some(DirEntry::Folder(folder)));
+            }
+
+            let mut contents = self.get_contents(current_id.as_str()).await?;
+
+            if let DirEntry::Folder(ref mut folder) = contents {
+                self.cache_listing(mount, orig_path, folder).await;
+            }
+
+            return Ok(Some(contents));
+        }
+
+        let mut current_path = orig_path[..cached_len].to_owned();
+        let components: Vec<_> = orig_path[cached_len..]
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        for (i, component) in components.iter().enumerate() {
+            let result = self.get_contents(current_id.as_str()).await;
+            let mut contents = match result {
+                Ok(contents) => contents,
+                Err(e) if matches!(e.unshared(), GofileError::NotFound) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+
+            let found_contents = match contents {
+                DirEntry::Folder(ref mut folder) => {
+                    self.cache_listing(mount, &current_path, folder).await;
+
+                    find_child(folder, component, self.case_insensitive).cloned()
+                }
+                DirEntry::File(_) => None,
+            };
+
+            match found_contents {
+                Some(found) if i == components.len() - 1 => return Ok(Some(found)),
+                Some(DirEntry::Folder(folder)) => {
+                    current_path = format!("{current_path}/{}", folder.name);
+                    current_id = folder.code
+                }
+                _ => return Ok(None),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Filters a fresh listing of the folder at `path` and caches its children in `mount`.
+    async fn cache_listing(&self, mount: &Mount, path: &str, folder: &mut FolderEntry) {
+        self.cache_listing_since(mount, path, folder, None).await;
+    }
+
+    /// [`Self::cache_listing`] unless `mount` was invalidated since `generation`, the listing
+    /// may be older than the change then. Returns whether it was cached.
+    async fn cache_listing_since(
+        &self,
+        mount: &Mount,
+        path: &str,
+        folder: &mut FolderEntry,
+        generation: Option<u64>,
+    ) -> bool {
+        // TODO Implement strategies for files that can't be accessed, currently skipped
+        folder.children.retain(|_, child| match child {
+            DirEntry::File(file) if !file.can_access => false,
+            DirEntry::File(file) if file.is_frozen => self.frozen_strategy != FrozenStrategy::Hide,
+            _ => true,
+        });
+
+        if self.frozen_strategy == FrozenStrategy::Placeholder {
+            for child in folder.children.values_mut() {
+                if let DirEntry::File(file) = child
+                    && file.is_frozen
+                {
+                    file.name.push_str(FROZEN_SUFFIX);
+                    file.size = 0;
+                }
+            }
+        }
+
+        disambiguate_names(&mut folder.children);
+
+        // names differing only in case would share a key, they are resolved from the listing
+        let mut ambiguous = HashSet::new();
+        if self.case_insensitive {
+            let mut seen = HashSet::new();
+            for child in folder.children.values() {
+                let name = child.name().to_ascii_lowercase();
+                if !seen.insert(name.clone()) {
+                    ambiguous.insert(name);
+                }
+            }
+        }
+
+        let mut dir_guard = mount.dircache.write().await;
+        if generation
+            .is_some_and(|generation| generation != mount.generation.load(Ordering::SeqCst))
+        {
+            return false;
+        }
+        let mut file_guard = mount.filecache.write().await;
+
+        file_guard.invalidate_children(self.cache_key(path));
+
+        for child in folder.children.values() {
+            if ambiguous.contains(&child.name().to_ascii_lowercase()) {
+                continue;
+            }
+
+            let child_path = self
+                .cache_key(&format!("{path}/{}", child.name()))
+                .into_owned();
+
+            match child {
+                DirEntry::Folder(child_folder) => {
+                    dir_guard.insert_dir(child_path, child_folder.code.clone());
+                }
+                DirEntry::File(child_file) => {
+                    file_guard.insert_file(child_path, child_file.clone());
+                }
+            }
+        }
+
+        mount.dircache_entries.set(dir_guard.len() as i64);
+        true
+    }
+
+    /// Lists the subfolders of `folder`, found at `full_path`, `prefetch_depth` levels deep
+    /// and caches the listings. Failures are only logged, the client fetches them itself then.
+    /// Stops once anything in the mount changes, a listing fetched before would undo it.
+    async fn prefetch(&self, full_path: &str, folder: &FolderEntry) {
+        let Ok(Some((mount, path))) = self.resolve(full_path) else {
+            return;
+        };
+        let generation = mount.generation.load(Ordering::SeqCst);
+
+        let mut level = subfolders(path, folder);
+
+        for _ in 0..self.prefetch_depth {
+            let mut to_fetch = Vec::new();
+            let mut next = Vec::new();
+
+            for (path, code) in level {
+                match mount
+                    .prefetched
+                    .read()
+                    .await
+                    .find_file(self.cache_key(&path))
+                {
+                    Some(folder) => next.extend(subfolders(&path, &folder)),
+                    None => to_fetch.push((path, code)),
+                }
+            }
+
+            let mut listings = futures_util::stream::iter(to_fetch)
+                .map(|(path, code)| async move {
+                    let result = self.get_contents(code.as_str()).await;
+                    (path, result)
+                })
+                .buffer_unordered(PREFETCH_CONCURRENCY);
+
+            while let Some((path, result)) = listings.next().await {
+                let mut folder = match result {
+                    Ok(DirEntry::Folder(folder)) => folder,
+                    Ok(DirEntry::File(_)) => continue,
+                    Err(e) => {
+                        debug!("failed to prefetch {path}: {e}");
+                        continue;
+                    }
+                };
+
+                if !self
+                    .cache_listing_since(mount, &path, &mut folder, Some(generation))
+                    .await
+                {
+                    return;
+                }
+                next.extend(subfolders(&path, &folder));
+                let key = self.cache_key(&path).into_owned();
+
+                // checked again as the invalidation may have cleared the listings in between
+                let mut prefetched = mount.prefetched.write().await;
+                if mount.generation.load(Ordering::SeqCst) != generation {
+                    return;
+                }
+                prefetched.insert_file(key, folder);
+            }
+
+            if next.is_empty() {
+                break;
+            }
+
+            level = next;
+        }
+    }
+
+    /// Drops cached metadata for `path` and everything below it after a mutation.
+    async fn invalidate(&self, path: &DavPathRef) -> GofileResult<()> {
+        let path = normalize_path(path)?;
+        let Some((mount, path)) = self.resolve(&path)? else {
+            return Ok(());
+        };
+
+        self.invalidate_in(mount, path).await;
+
+        Ok(())
+    }
+
+    /// [`Self::invalidate`] for a path inside `mount`.
+    async fn invalidate_in(&self, mount: &Mount, path: &str) {
+        let path: &str = &self.cache_key(path);
+
+        let mut dir_guard = mount.dircache.write().await;
+        // under the lock, a prefetch caching a listing either sees it or gets cleared below
+        mount.generation.fetch_add(1, Ordering::SeqCst);
+        dir_guard.invalidate_prefix(path);
+        mount.dircache_entries.set(dir_guard.len() as i64);
+        drop(dir_guard);
+
+        mount.filecache.write().await.invalidate_prefix(path);
+
+        // the listing of the parent changes as well
+        let parent = path.rsplit_once('/').map_or(ROOT_DIR, |(parent, _)| parent);
+        mount.prefetched.write().await.invalidate_prefix(parent);
+        *mount.prewarmed.lock().await = None;
+
+        // listings sent before the change would put it back, they are keyed by folder
+        // id so all of them go
+        self.listings.forget_all();
+    }
+
+    /// The Gofile entry at `path` when it has properties to report, `None` for anything else.
+    async fn find_props_entry(&self, path: &DavPathRef) -> GofileResult<Option<DirEntry>> {
+        if self.find_virtual(path).is_some() || self.is_top_level(path)? {
+            return Ok(None);
+        }
+
+        self.search(path).await
+    }
+
+    /// The type gofile.io reported for the file at `path`, if any.
+    pub async fn mimetype(&self, path: &DavPathRef) -> Option<String> {
+        match self.find_props_entry(path).await {
+            Ok(Some(DirEntry::File(file))) => file.mimetype,
+            _ => None,
+        }
+    }
+
+    /// Sets the modification time of the file at `path` to `secs`, as clients syncing by
+    /// time send it along with an upload. gofile.io stamps uploads with their own time.
+    pub async fn set_mod_time(&self, path: &DavPathRef, secs: u64) -> GofileResult<()> {
+        if self.skip_in_dry_run(|| {
+            format!(
+                "set the modification time of {} to {secs}",
+                path.as_url_string()
+            )
+        }) {
+            return Ok(());
+        }
+
+        let file = self.try_find_file(path).await?;
+        self.client
+            .update_attribute(file.id, Attribute::ModTime(secs))
+            .await?;
+
+        self.invalidate(path).await
+    }
+
+    /// The children of the folder at `path` as the WebDAV listings show them, sorted by
+    /// name. `NotFound` when nothing is there, `Forbidden` for a file.
+    pub async fn list(&self, path: &DavPathRef) -> FsResult<Vec<DirEntry>> {
+        let DirEntry::Folder(folder) = self.search(path).await?.ok_or(FsError::NotFound)? else {
+            return Err(FsError::Forbidden);
+        };
+
+        let mut children: Vec<_> = folder.children.into_values().collect();
+        children.sort_by(|a, b| a.name().cmp(b.name()));
+
+        Ok(children)
+    }
+
+    async fn try_find_folder(&self, path: &DavPathRef) -> GofileResult<FolderEntry> {
+        let contents = self.search(path).await?.ok_or(GofileError::NotFound)?;
+
+        match contents {
+            DirEntry::Folder(folder_entry) => Ok(folder_entry),
+            DirEntry::File(_) => Err(anyhow!(
+                "expected folder but found file at path {}",
+                path.as_url_string()
+            )
+            .into()),
+        }
+    }
+
+    /// The folder at `path`, created along with the missing folders above it when
+    /// [`Self::create_parents`] is set.
+    async fn find_or_create_folder(&self, path: &DavPathRef) -> GofileResult<FolderEntry> {
+        match self.try_find_folder(path).await {
+            Err(GofileError::NotFound) if self.create_parents => {}
+            result => return result,
+        }
+
+        let full_path = normalize_path(path)?;
+        let (mount, path) = self.resolve(&full_path)?.ok_or(GofileError::NotFound)?;
+        let mount_prefix = &full_path[..full_path.len() - path.len()];
+
+        let root = mount
+            .dircache
+            .write()
+            .await
+            .find_dir(ROOT_DIR)
+            .cloned()
+            .context("mount without a root folder")?;
+        let DirEntry::Folder(mut folder) = self.get_contents(root.as_str()).await? else {
+            return Err(anyhow!("root of the mount is a file").into());
+        };
+        let mut current_path = String::new();
+
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            current_path = format!("{current_path}/{name}");
+
+            let id = match find_child(&folder, name, self.case_insensitive) {
+                Some(DirEntry::Folder(child)) => child.id,
+                Some(DirEntry::File(_)) => {
+                    return Err(
+                        anyhow!("expected folder but found file at path {current_path}").into(),
+                    );
+                }
+                None => {
+                    // uploads next to each other wait here, the first one creates the folder
+                    let folder_path = format!("{mount_prefix}{current_path}");
+                    let key = self.cache_key(&folder_path).into_owned();
+                    let _lock = self.folder_locks.lock(key).await;
+
+                    // creating the folder forgets the listings in flight, this one has it
+                    let parent = self.get_folder(folder.id).await?;
+
+                    match find_child(&parent, name, self.case_insensitive) {
+                        Some(DirEntry::Folder(child)) => child.id,
+                        Some(DirEntry::File(_)) => {
+                            return Err(anyhow!(
+                                "expected folder but found file at path {current_path}"
+                            )
+                            .into());
+                        }
+                        None => {
+                            info!("creating missing folder {folder_path}");
+                            let created = self.client.create_folder(parent.id, name).await?;
+
+                            // the parent listing now has the folder in it
+                            self.invalidate_in(mount, &current_path).await;
+                            let mut dir_guard = mount.dircache.write().await;
+                            dir_guard.insert_dir(
+                                self.cache_key(&current_path).into_owned(),
+                                created.code,
+                            );
+                            mount.dircache_entries.set(dir_guard.len() as i64);
+
+                            created.id
+                        }
+                    }
+                }
+            };
+
+            folder = self.get_folder(id).await?;
+        }
+
+        Ok(folder)
+    }
+
+    async fn try_find_file(&self, path: &DavPathRef) -> GofileResult<FileEntry> {
+        let contents = self.search(path).await?.ok_or(GofileError::NotFound)?;
+
+        match contents {
+            DirEntry::File(file_entry) => Ok(file_entry),
+            DirEntry::Folder(_) => Err(anyhow!(
+                "expected file but found folder at path {}",
+                path.as_url_string()
+            )
+            .into()),
+        }
+    }
+}
+
+impl DavFileSystem for DavFs {
+    fn open<'a>(
+        &'a self,
+        path: &'a DavPath,
+        options: OpenOptions,
+    ) -> FsFuture<'a, Box<dyn FsDavFile>> {
+        async move {
+            if let Some(file) = self.find_virtual(path) {
+                if options.write || options.append {
+                    return Err(FsError::Forbidden);
+                }
+
+                return Ok(Box::new(VirtualFileRead::new(file.clone())) as Box<dyn FsDavFile>);
+            }
+
+            if options.read {
+                let result = self.search(path).await?.ok_or(FsError::NotFound)?;
+
+                let file = if let DirEntry::File(file) = result {
+                    file
                 } else {
-                    let (parent_path, _) = path
-                        .rsplit_once('/')
-                        .context("Expected a path-like string starting with '/'")?;
+                    return Err(FsError::Forbidden);
+                };
+
+                if file.is_frozen {
+                    if self.frozen_strategy == FrozenStrategy::Placeholder {
+                        let placeholder = VirtualFile::new(file.name, Bytes::new());
+                        return Ok(
+                            Box::new(VirtualFileRead::new(placeholder)) as Box<dyn FsDavFile>
+                        );
+                    }
+
+                    warn!(
+                        "{} is frozen on gofile.io and can't be downloaded",
+                        file.name
+                    );
+                    return Err(FsError::Forbidden);
+                }
+
+                if let Some(max) = self.max_file_size
+                    && file.size > max
+                {
+                    warn!(
+                        "refusing to download {} ({}), its {} bytes are over --max-file-size",
+                        file.name, file.id, file.size
+                    );
+                    return Err(FsError::Forbidden);
+                }
+
+                return Ok(Box::new(DavFileRead::new(self.clone(), file)) as Box<dyn FsDavFile>);
+            } else if self.is_writable() && (options.write || options.append) {
+                // dav-server checked `If-None-Match: *` against the listing it got, check
+                // again in case the file showed up since
+                if options.create_new && self.search(path).await?.is_some() {
+                    return Err(FsError::Exists);
+                }
+
+                let file =
+                    DavFileWrite::new(self.clone(), path.clone(), options.append, options.size);
+
+                return Ok(Box::new(file) as Box<dyn FsDavFile>);
+            }
+
+            Err(FsError::Forbidden)
+        }
+        .timed(self.operation_timeout, || {
+            format!("open {}", path.as_url_string())
+        })
+        .boxed()
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a DavPath,
+        _meta: ReadDirMeta,
+    ) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
+        async move {
+            let mut entries: Vec<Box<dyn DavDirEntry>> = if self.is_top_level(path)? {
+                self.mounts
+                    .keys()
+                    .map(|name| Box::new(VirtualDir::new(name)) as Box<dyn DavDirEntry>)
+                    .collect()
+            } else {
+                let result = self.search(path).await?.ok_or(FsError::NotFound)?;
+
+                let folder = if let DirEntry::Folder(folder) = result {
+                    folder
+                } else {
+                    return Err(FsError::Forbidden);
+                };
+
+                if self.prefetch_depth > 0
+                    && let Ok(permit) = self.prefetches.clone().try_acquire_owned()
+                {
+                    let fs = self.clone();
+                    let full_path = normalize_path(path)?;
+                    let folder = folder.clone();
+
+                    tokio::spawn(async move {
+                        fs.prefetch(&full_path, &folder).await;
+                        drop(permit);
+                    });
+                }
+
+                let childrens = folder.children.into_values();
+
+                childrens
+                    .map(|entry| Box::new(entry) as Box<dyn DavDirEntry>)
+                    .collect()
+            };
+
+            if let Some(manifest) = self.mounts_manifest.as_ref()
+                && normalize_path(path)? == ROOT_DIR
+            {
+                entries.push(Box::new(manifest.clone()));
+            }
+
+            let stream = futures_util::stream::iter(entries.into_iter().map(Ok::<_, FsError>));
+            Ok(Box::pin(stream) as FsStream<Box<dyn DavDirEntry>>)
+        }
+        .timed(self.operation_timeout, || {
+            format!("list {}", path.as_url_string())
+        })
+        .boxed()
+    }
+
+    fn metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        async move {
+            if let Some(file) = self.find_virtual(path) {
+                return Ok(Box::new(file.clone()) as Box<dyn DavMetaData>);
+            }
+
+            if self.is_top_level(path)? {
+                return Ok(Box::new(VirtualDir::new(ROOT_DIR)) as Box<dyn DavMetaData>);
+            }
+
+            let result = self.search(path).await?.ok_or(FsError::NotFound)?;
+
+            result.metadata().await
+        }
+        .timed(self.operation_timeout, || {
+            format!("metadata of {}", path.as_url_string())
+        })
+        .boxed()
+    }
+
+    fn create_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            if !self.is_writable() || self.find_virtual(path).is_some() {
+                return Err(FsError::Forbidden);
+            }
+
+            let folder_name = path
+                .file_name()
+                .map(String::from)
+                .ok_or(FsError::GeneralFailure)?;
+
+            let parent_folder_entry = self.try_find_folder(&path.parent()).await?;
+
+            // RFC 4918 answers 405 to a MKCOL on anything already there
+            if find_child(&parent_folder_entry, &folder_name, self.case_insensitive).is_some() {
+                return Err(FsError::Exists);
+            }
+
+            if self.skip_in_dry_run(|| format!("create folder {}", path.as_url_string())) {
+                return Ok(());
+            }
+
+            self.client
+                .create_folder(parent_folder_entry.id, folder_name)
+                .await?;
+            self.invalidate(path).await?;
+
+            Ok(())
+        }
+        .timed(self.operation_timeout, || {
+            format!("create folder {}", path.as_url_string())
+        })
+        .boxed()
+    }
+
+    /// MOVE. dav-server answers 400 to any `Depth` but infinity, as RFC 4918 requires for
+    /// collections, so a folder always moves with everything below it.
+    fn rename<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            if !self.is_writable()
+                || self.find_virtual(from).is_some()
+                || self.find_virtual(to).is_some()
+            {
+                return Err(FsError::Forbidden);
+            }
+
+            if self.is_into_itself(from, to)? {
+                return Err(FsError::Forbidden);
+            }
+
+            let name_from = from
+                .file_name()
+                .map(String::from)
+                .ok_or(FsError::GeneralFailure)?;
+
+            let name_to = to
+                .file_name()
+                .map(String::from)
+                .ok_or(FsError::GeneralFailure)?;
+
+            let parent_folder_from_entry = self.try_find_folder(&from.parent()).await?;
+
+            let contents_from =
+                find_child(&parent_folder_from_entry, &name_from, self.case_insensitive)
+                    .ok_or(FsError::GeneralFailure)?;
+            if is_frozen(contents_from) {
+                return Err(FsError::Forbidden);
+            }
+
+            let mut files_to_delete = Vec::with_capacity(2);
+
+            if from.parent() == to.parent() {
+                // share parent folder - simple rename
+
+                // changing only the case of a name finds the entry itself
+                let contents_to =
+                    find_child(&parent_folder_from_entry, &name_to, self.case_insensitive)
+                        .filter(|to| to.id() != contents_from.id());
+
+                files_to_delete.extend(replaced_by_rename(contents_from, contents_to)?);
+
+                if self.skip_in_dry_run(|| rename_operation(from, to)) {
+                    return Ok(());
+                }
+
+                let new_attribute = Attribute::Name(&name_to);
+
+                self.client
+                    .update_attribute(contents_from.id(), new_attribute)
+                    .await?;
+            } else {
+                let parent_folder_to_entry = self.try_find_folder(&to.parent()).await?;
+
+                let contents_to =
+                    find_child(&parent_folder_to_entry, &name_to, self.case_insensitive);
+
+                let replaced = replaced_by_rename(contents_from, contents_to)?;
+                files_to_delete.extend(replaced);
+
+                if self.skip_in_dry_run(|| rename_operation(from, to)) {
+                    return Ok(());
+                }
+
+                // reparent, then rename if the name changes as well
+                let moved = self
+                    .client
+                    .move_contents(&[contents_from.id()], parent_folder_to_entry.id)
+                    .await;
+
+                match (moved, contents_from) {
+                    (Ok(()), _) => {
+                        if name_from != name_to {
+                            self.client
+                                .update_attribute(contents_from.id(), Attribute::Name(&name_to))
+                                .await?;
+                        }
+                    }
+                    (Err(e), DirEntry::File(file_from)) => {
+                        warn!(
+                            "moving file {} failed, falling back to copy + delete: {e}",
+                            file_from.id
+                        );
+
+                        // the copy deletes the file it replaces itself
+                        files_to_delete.retain(|id| Some(*id) != replaced);
+                        files_to_delete.push(file_from.id);
+                        self.copy(from, to).await?;
+                    }
+                    (Err(e), DirEntry::Folder(_)) => return Err(e.into()),
+                }
+            }
+
+            if !files_to_delete.is_empty() {
+                self.client.delete_contents(&files_to_delete).await?;
+            }
+
+            self.invalidate(from).await?;
+            self.invalidate(to).await?;
+
+            Ok(())
+        }
+        .timed(self.operation_timeout, || {
+            format!("move {}", from.as_url_string())
+        })
+        .boxed()
+    }
+
+    fn remove_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        self.remove(path, true)
+            .map_err(Into::into)
+            .timed(self.operation_timeout, || {
+                format!("remove {}", path.as_url_string())
+            })
+            .boxed()
+    }
+
+    fn remove_file<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        self.remove(path, false)
+            .map_err(Into::into)
+            .timed(self.operation_timeout, || {
+                format!("remove {}", path.as_url_string())
+            })
+            .boxed()
+    }
+
+    fn copy<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            if !self.is_writable()
+                || self.find_virtual(from).is_some()
+                || self.find_virtual(to).is_some()
+            {
+                return Err(FsError::Forbidden);
+            }
+
+            // dav-server walks folders itself for COPY, honoring Depth, and only hands
+            // single files to this method. Folders are copied for other callers.
+            if self.is_into_itself(from, to)? {
+                return Err(FsError::Forbidden);
+            }
+
+            let contents_from = self.search(from).await?.ok_or(FsError::NotFound)?;
+
+            if self.skip_in_dry_run(|| {
+                format!("copy {} to {}", from.as_url_string(), to.as_url_string())
+            }) {
+                return Ok(());
+            }
+
+            let folder_from = match contents_from {
+                DirEntry::File(file_from) => return self.copy_file(file_from, to).await,
+                DirEntry::Folder(folder_from) => folder_from,
+            };
+
+            // copied into the folder already there, if any
+            match self.create_dir(to).await {
+                Ok(()) | Err(FsError::Exists) => {}
+                Err(e) => return Err(e),
+            }
+
+            for child in folder_from.children.values() {
+                let child_from = join_path(from, child.name())?;
+                let child_to = join_path(to, child.name())?;
+
+                self.copy(&child_from, &child_to).await?;
+            }
+
+            Ok(())
+        }
+        .timed(self.operation_timeout, || {
+            format!("copy {}", from.as_url_string())
+        })
+        .boxed()
+    }
+
+    /// RFC 4331 `quota-used-bytes` and `quota-available-bytes`. dav-server reports the usage
+    /// of the account on the root only, and the size of the entry anywhere else.
+    fn get_quota(&self) -> FsFuture<'_, (u64, Option<u64>)> {
+        async move {
+            let (used, total) = self.quota().await?;
+            Ok((used, Some(total)))
+        }
+        .timed(self.operation_timeout, || "quota".to_string())
+        .boxed()
+    }
+
+    fn have_props<'a>(
+        &'a self,
+        _path: &'a DavPath,
+    ) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>> {
+        futures_util::future::ready(true).boxed()
+    }
+
+    fn get_props<'a>(&'a self, path: &'a DavPath, do_content: bool) -> FsFuture<'a, Vec<DavProp>> {
+        async move {
+            let Some(entry) = self.find_props_entry(path).await? else {
+                return Ok(Vec::new());
+            };
+
+            Ok(gofile_props(&entry)
+                .into_iter()
+                .map(|(name, value)| gofile_prop(name, do_content.then_some(value.as_str())))
+                .collect())
+        }
+        .timed(self.operation_timeout, || {
+            format!("properties of {}", path.as_url_string())
+        })
+        .boxed()
+    }
+
+    fn get_prop<'a>(&'a self, path: &'a DavPath, prop: DavProp) -> FsFuture<'a, Vec<u8>> {
+        async move {
+            if prop.namespace.as_deref() != Some(GOFILE_NS) {
+                return Err(FsError::NotFound);
+            }
+
+            let entry = self
+                .find_props_entry(path)
+                .await?
+                .ok_or(FsError::NotFound)?;
+
+            gofile_props(&entry)
+                .into_iter()
+                .find(|(name, _)| *name == prop.name)
+                .and_then(|(name, value)| gofile_prop(name, Some(&value)).xml)
+                .ok_or(FsError::NotFound)
+        }
+        .timed(self.operation_timeout, || {
+            format!("property of {}", path.as_url_string())
+        })
+        .boxed()
+    }
+
+    /// Only `public` can be set, on an owned folder of a read-write server. The other
+    /// Gofile properties are read-only and nothing else can be stored. As PROPPATCH is all
+    /// or nothing, a refused property fails the others with `424 Failed Dependency`.
+    fn patch_props<'a>(
+        &'a self,
+        path: &'a DavPath,
+        patch: Vec<(bool, DavProp)>,
+    ) -> FsFuture<'a, Vec<(StatusCode, DavProp)>> {
+        async move {
+            let folder = match self.find_props_entry(path).await? {
+                Some(DirEntry::Folder(folder)) if self.is_writable() && folder.is_owner => {
+                    Some(folder)
+                }
+                _ => None,
+            };
+
+            let mut public = None;
+            let mut results = Vec::with_capacity(patch.len());
+            for (set, prop) in patch {
+                let status = match (set, folder.as_ref(), parse_public_prop(&prop)) {
+                    (true, Some(_), Some(Ok(value))) => {
+                        public = Some(value);
+                        StatusCode::OK
+                    }
+                    (true, Some(_), Some(Err(()))) => StatusCode::CONFLICT,
+                    _ => StatusCode::FORBIDDEN,
+                };
+
+                let prop = DavProp { xml: None, ..prop };
+                results.push((status, prop));
+            }
+
+            if results.iter().any(|(status, _)| *status != StatusCode::OK) {
+                for (status, _) in &mut results {
+                    if *status == StatusCode::OK {
+                        *status = StatusCode::FAILED_DEPENDENCY;
+                    }
+                }
+
+                return Ok(results);
+            }
+
+            if let (Some(folder), Some(public)) = (folder, public)
+                && !self
+                    .skip_in_dry_run(|| format!("set public={public} on {}", path.as_url_string()))
+            {
+                self.client
+                    .update_attribute(folder.id, Attribute::Public(public))
+                    .await?;
+                self.invalidate(path).await?;
+            }
+
+            Ok(results)
+        }
+        .timed(self.operation_timeout, || {
+            format!("set properties of {}", path.as_url_string())
+        })
+        .boxed()
+    }
+}
+
+/// Namespace of the read-only properties describing Gofile entries in PROPFIND responses
+const GOFILE_NS: &str = "http://gofile.io/ns";
+const GOFILE_NS_PREFIX: &str = "G";
+
+/// Properties of `entry` in [`GOFILE_NS`]. Files report their download count and checksum,
+/// plus the thumbnail when they have one, folders whether they are public.
+fn gofile_props(entry: &DirEntry) -> Vec<(&'static str, String)> {
+    match entry {
+        DirEntry::File(file) => {
+            let mut props = vec![
+                ("download-count", file.download_count.to_string()),
+                ("is-owner", file.is_owner.to_string()),
+                ("md5", file.md5.to_ascii_lowercase()),
+            ];
+            if let Some(thumbnail) = &file.thumbnail {
+                props.push(("thumbnail", thumbnail.clone()));
+            }
+
+            props
+        }
+        DirEntry::Folder(folder) => vec![
+            ("is-owner", folder.is_owner.to_string()),
+            ("public", folder.public.to_string()),
+        ],
+    }
+}
+
+/// The value of `prop` when it is the `public` property in [`GOFILE_NS`], an error when
+/// it is neither `true` nor `false`.
+fn parse_public_prop(prop: &DavProp) -> Option<Result<bool, ()>> {
+    if prop.namespace.as_deref() != Some(GOFILE_NS) || prop.name != "public" {
+        return None;
+    }
+
+    // the whole element, e.g. `<G:public xmlns:G="http://gofile.io/ns">true</G:public>`
+    let xml = String::from_utf8_lossy(prop.xml.as_deref().unwrap_or_default());
+    let value = xml
+        .split_once('>')
+        .and_then(|(_, rest)| rest.rsplit_once('<'))
+        .map_or("", |(value, _)| value.trim());
+
+    Some(match value {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(()),
+    })
+}
+
+/// A property in [`GOFILE_NS`], with its value rendered as XML when given
+fn gofile_prop(name: &str, value: Option<&str>) -> DavProp {
+    let xml = value.map(|value| {
+        format!(
+            r#"<{GOFILE_NS_PREFIX}:{name} xmlns:{GOFILE_NS_PREFIX}="{GOFILE_NS}">{}</{GOFILE_NS_PREFIX}:{name}>"#,
+            escape_xml(value)
+        )
+        .into_bytes()
+    });
+
+    DavProp {
+        name: name.to_string(),
+        prefix: Some(GOFILE_NS_PREFIX.to_string()),
+        namespace: Some(GOFILE_NS.to_string()),
+        xml,
+    }
+}
+
+/// Escapes text for an XML element, URLs may carry `&` in their query
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use uuid::Uuid;
+
+    use crate::gofile::testing::{api_fs, file, serve_api};
+
+    fn folder(name: &str) -> FolderEntry {
+        FolderEntry {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            ..FolderEntry::default()
+        }
+    }
+
+    fn with_children(mut parent: FolderEntry, children: Vec<DirEntry>) -> FolderEntry {
+        parent.children = children.into_iter().map(|c| (c.id(), c)).collect();
+        parent
+    }
+
+    /// How [`serve_data`] answers
+    #[derive(Clone, Copy)]
+    struct ServeOptions {
+        // Status of every response
+        status: u16,
+        // Waited before each response
+        delay: Duration,
+        // Bytes of the first response sent before its connection breaks
+        drop_after: Option<usize>,
+    }
+
+    impl Default for ServeOptions {
+        fn default() -> Self {
+            Self {
+                status: 206,
+                delay: Duration::ZERO,
+                drop_after: None,
+            }
+        }
+    }
+
+    /// Serves `data` over HTTP honoring `Range: bytes=<start>-[<end>]`, returns its URL
+    fn serve_data(data: &'static [u8], options: ServeOptions) -> String {
+        use actix_web::{App, HttpRequest, HttpResponse, HttpServer, http::StatusCode, web};
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let server = HttpServer::new(move || {
+            let dropped = dropped.clone();
+            App::new().default_service(web::to(move |req: HttpRequest| {
+                let dropped = dropped.clone();
+                async move {
+                    let (start, end) = req
+                        .headers()
+                        .get(actix_web::http::header::RANGE)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.strip_prefix("bytes="))
+                        .and_then(|v| v.split_once('-'))
+                        .map_or((0, data.len()), |(start, end)| {
+                            let end = end.parse::<usize>().map_or(data.len(), |end| end + 1);
+                            (start.parse().unwrap_or(0), end)
+                        });
+
+                    tokio::time::sleep(options.delay).await;
+                    let body = match options.drop_after {
+                        Some(cut) if !dropped.swap(true, Ordering::SeqCst) => {
+                            // the head and the first bytes go out before the connection breaks
+                            futures_util::stream::once(async move {
+                                Ok(Bytes::from_static(&data[start..cut]))
+                            })
+                            .chain(futures_util::stream::once(async {
+                                tokio::time::sleep(Duration::from_millis(50)).await;
+                                Err(io::Error::other("connection reset"))
+                            }))
+                            .boxed()
+                        }
+                        _ => {
+                            futures_util::stream::iter([Ok(Bytes::from_static(&data[start..end]))])
+                                .boxed()
+                        }
+                    };
+
+                    HttpResponse::build(StatusCode::from_u16(options.status).unwrap())
+                        .streaming(body)
+                }
+            }))
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+
+        format!("http://{addr}/data.bin")
+    }
+
+    fn mounted_fs(names: &[&str]) -> DavFs {
+        let mounts = names.iter().map(|name| {
+            let dircache = DirCache::new(format!("{name}-code"));
+            (name.to_string(), Arc::new(RwLock::new(dircache)))
+        });
+
+        DavFs::new(Client::new(), mounts, false)
+    }
+
+    #[test]
+    fn test_error_mapping() {
+        assert_eq!(
+            FsError::from(GofileError::RateLimited { retry_after: None }),
+            FsError::IsRemote
+        );
+        assert_eq!(
+            FsError::from(GofileError::Api {
+                status: "error-fatal".to_string()
+            }),
+            FsError::GeneralFailure
+        );
+        assert_eq!(
+            FsError::from(GofileError::DeleteFailed {
+                failed: vec!["6c9e22a7-7d6c-4986-8e93-b118558be0bb (forbidden)".to_string()]
+            }),
+            FsError::GeneralFailure
+        );
+        assert_eq!(
+            FsError::from(GofileError::QuotaExceeded {
+                status: "error-storageLimit".to_string()
+            }),
+            FsError::InsufficientStorage
+        );
+        assert_eq!(http_error(Some(StatusCode::NOT_FOUND)), FsError::NotFound);
+        assert_eq!(http_error(Some(StatusCode::FORBIDDEN)), FsError::Forbidden);
+        assert_eq!(
+            http_error(Some(StatusCode::SERVICE_UNAVAILABLE)),
+            FsError::IsRemote
+        );
+        assert_eq!(http_error(None), FsError::IsRemote);
+    }
+
+    #[tokio::test]
+    async fn test_create_parents_uses_existing_folders() {
+        let fs = mounted_fs(&[ROOT_DIR]).create_parents(true);
+        let mount = &fs.mounts[ROOT_DIR];
+        let albums = folder("albums");
+        let mut root = with_children(
+            folder("root"),
+            vec![
+                DirEntry::Folder(albums.clone()),
+                DirEntry::File(file("notes.txt")),
+            ],
+        );
+        fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+        mount
+            .prefetched
+            .write()
+            .await
+            .insert_file("/albums".to_string(), albums.clone());
+
+        let path = DavPath::new("/albums").unwrap();
+        let found = fs.find_or_create_folder(&path).await.unwrap();
+        assert_eq!(found.id, albums.id);
+
+        // a file in the way is an error, not a missing folder
+        let path = DavPath::new("/notes.txt").unwrap();
+        assert!(!matches!(
+            fs.find_or_create_folder(&path).await,
+            Ok(_) | Err(GofileError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_max_file_size() {
+        let fs = mounted_fs(&[ROOT_DIR]).max_file_size(10);
+        let big = FileEntry {
+            size: 11,
+            ..file("big.bin")
+        };
+        let small = FileEntry {
+            size: 10,
+            ..file("small.bin")
+        };
+        let mut root = with_children(
+            folder("root"),
+            vec![DirEntry::File(big), DirEntry::File(small)],
+        );
+        fs.cache_listing(&fs.mounts[ROOT_DIR], ROOT_DIR, &mut root)
+            .await;
+
+        let read = OpenOptions {
+            read: true,
+            ..Default::default()
+        };
+        let path = DavPath::new("/big.bin").unwrap();
+        assert_eq!(
+            fs.open(&path, read.clone()).await.err(),
+            Some(FsError::Forbidden)
+        );
+        let path = DavPath::new("/small.bin").unwrap();
+        assert!(fs.open(&path, read).await.is_ok());
+    }
+
+    #[test]
+    fn test_is_same_file() {
+        let known = file("a.txt");
+
+        assert!(is_same_file(&known, &known.clone()));
+        let renamed = FileEntry {
+            name: "b.txt".to_string(),
+            ..known.clone()
+        };
+        assert!(!is_same_file(&known, &renamed));
+        let moved = FileEntry {
+            parent_folder: "elsewhere".to_string(),
+            ..known.clone()
+        };
+        assert!(!is_same_file(&known, &moved));
+        let frozen = FileEntry {
+            is_frozen: true,
+            ..known.clone()
+        };
+        assert!(!is_same_file(&known, &frozen));
+    }
+
+    #[test]
+    fn test_zero_timestamps_fall_back() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+        let entry = DirEntry::File(FileEntry {
+            mod_time: 0,
+            ..file("a.txt")
+        });
+        assert_eq!(entry.modified().unwrap(), at(1719990416));
+        assert_eq!(DavMetaData::created(&entry).unwrap(), at(1719990416));
+
+        let entry = DirEntry::Folder(FolderEntry {
+            create_time: 0,
+            mod_time: 1720000000,
+            ..folder("albums")
+        });
+        assert_eq!(DavMetaData::created(&entry).unwrap(), at(1720000000));
+        assert_eq!(entry.modified().unwrap(), at(1720000000));
+
+        // neither is known
+        let before = std::time::SystemTime::now();
+        let entry = DirEntry::Folder(FolderEntry {
+            create_time: 0,
+            mod_time: 0,
+            ..folder("albums")
+        });
+        assert!(entry.modified().unwrap() >= before);
+        assert!(DavMetaData::created(&entry).unwrap() >= before);
+    }
+
+    fn created_at(name: &str, create_time: u64) -> DirEntry {
+        DirEntry::File(FileEntry {
+            create_time,
+            ..file(name)
+        })
+    }
+
+    fn names(folder: &FolderEntry) -> BTreeMap<Uuid, String> {
+        folder
+            .children
+            .iter()
+            .map(|(id, child)| (*id, child.name().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_duplicate_names_are_disambiguated() {
+        let oldest = created_at("a.txt", 1);
+        let newest = created_at("a.txt", 3);
+        let middle = created_at("a.txt", 2);
+        let taken = created_at("a (2).txt", 1);
+        let ids = [&oldest, &newest, &middle, &taken].map(DirEntry::id);
+        let mut parent = with_children(folder("root"), vec![oldest, newest, middle, taken]);
+
+        disambiguate_names(&mut parent.children);
+
+        let names = names(&parent);
+        assert_eq!(names[&ids[1]], "a.txt");
+        assert_eq!(names[&ids[2]], "a (3).txt");
+        assert_eq!(names[&ids[0]], "a (4).txt");
+        assert_eq!(names[&ids[3]], "a (2).txt");
+
+        // the newest one is the file written at the original name
+        assert_eq!(find_file(&parent, "a.txt", false).unwrap().id, ids[1]);
+        assert_eq!(find_file(&parent, "a (4).txt", false).unwrap().id, ids[0]);
+    }
+
+    #[test]
+    fn test_duplicate_names_are_deterministic() {
+        let children = vec![
+            created_at("a.txt", 1),
+            created_at("a.txt", 1),
+            created_at(".hidden", 1),
+            created_at(".hidden", 1),
+            DirEntry::Folder(folder("v1.0")),
+            DirEntry::Folder(folder("v1.0")),
+        ];
+        let mut first = with_children(folder("root"), children);
+        let mut second = first.clone();
+
+        disambiguate_names(&mut first.children);
+        disambiguate_names(&mut second.children);
+        assert_eq!(names(&first), names(&second));
+
+        let mut listed: Vec<_> = names(&first).into_values().collect();
+        listed.sort();
+        assert_eq!(
+            listed,
+            [
+                ".hidden",
+                ".hidden (2)",
+                "a (2).txt",
+                "a.txt",
+                "v1.0",
+                "v1.0 (2)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_file_conflicts_with_folder() {
+        let parent = with_children(folder("root"), vec![DirEntry::Folder(folder("probe"))]);
+
+        assert_eq!(
+            check_file_name_conflict(&parent, "probe", false),
+            Err(FsError::Exists)
+        );
+        assert_eq!(check_file_name_conflict(&parent, "other", false), Ok(()));
+    }
+
+    fn frozen_file(name: &str) -> FileEntry {
+        FileEntry {
+            is_frozen: true,
+            size: 42,
+            ..file(name)
+        }
+    }
+
+    #[test]
+    fn test_frozen_file_cannot_be_overwritten() {
+        let parent = with_children(folder("root"), vec![DirEntry::File(frozen_file("a.txt"))]);
+
+        assert_eq!(
+            check_file_name_conflict(&parent, "a.txt", false),
+            Err(FsError::Forbidden)
+        );
+
+        // listed as a placeholder, under another name
+        let placeholder = FileEntry {
+            name: format!("a.txt{FROZEN_SUFFIX}"),
+            ..frozen_file("a.txt")
+        };
+        let parent = with_children(folder("root"), vec![DirEntry::File(placeholder)]);
+        assert_eq!(
+            check_file_name_conflict(&parent, "a.txt", false),
+            Err(FsError::Forbidden)
+        );
+        assert_eq!(
+            check_file_name_conflict(&parent, "A.TXT", true),
+            Err(FsError::Forbidden)
+        );
+        assert_eq!(
+            check_file_name_conflict(&parent, "a.txt.frozen", false),
+            Err(FsError::Forbidden)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_frozen_placeholder_cannot_be_removed_or_moved() {
+        let mut fs = mounted_fs(&[ROOT_DIR])
+            .frozen_strategy(FrozenStrategy::Placeholder)
+            .dry_run(true);
+        fs.write_enabled = true;
+        let mount = &fs.mounts[ROOT_DIR];
+
+        let mut root = with_children(
+            folder("root"),
+            vec![
+                DirEntry::File(frozen_file("b.txt")),
+                DirEntry::File(file("a.txt")),
+            ],
+        );
+        fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+        mount.prefetched.write().await.insert_file(ROOT_DIR, root);
+
+        let placeholder = DavPath::new("/b.txt.frozen").unwrap();
+        let other = DavPath::new("/c.txt").unwrap();
+        assert_eq!(fs.remove_file(&placeholder).await, Err(FsError::Forbidden));
+        assert_eq!(
+            fs.rename(&placeholder, &other).await,
+            Err(FsError::Forbidden)
+        );
+
+        // the others go through
+        let a = DavPath::new("/a.txt").unwrap();
+        assert_eq!(fs.rename(&a, &other).await, Ok(()));
+        assert_eq!(fs.remove_file(&a).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_create_existing_dir() {
+        let mut fs = mounted_fs(&[ROOT_DIR]).dry_run(true);
+        fs.write_enabled = true;
+        let mount = &fs.mounts[ROOT_DIR];
+
+        let mut root = with_children(
+            folder("root"),
+            vec![
+                DirEntry::File(file("a.txt")),
+                DirEntry::Folder(folder("albums")),
+            ],
+        );
+        fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+        mount.prefetched.write().await.insert_file(ROOT_DIR, root);
+
+        let albums = DavPath::new("/albums").unwrap();
+        let a = DavPath::new("/a.txt").unwrap();
+        assert_eq!(fs.create_dir(&albums).await, Err(FsError::Exists));
+        assert_eq!(fs.create_dir(&a).await, Err(FsError::Exists));
+
+        let new = DavPath::new("/new").unwrap();
+        assert_eq!(fs.create_dir(&new).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_frozen_strategy() {
+        let listing = |strategy| async move {
+            let fs = mounted_fs(&[ROOT_DIR]).frozen_strategy(strategy);
+            let mut parent = with_children(
+                folder("root"),
+                vec![
+                    DirEntry::File(file("a.txt")),
+                    DirEntry::File(frozen_file("b.txt")),
+                ],
+            );
+
+            fs.cache_listing(&fs.mounts[ROOT_DIR], "", &mut parent)
+                .await;
+
+            let mut children: Vec<_> = parent
+                .children
+                .into_values()
+                .map(|child| (child.name().to_string(), child.len()))
+                .collect();
+            children.sort();
+            children
+        };
+
+        assert_eq!(
+            listing(FrozenStrategy::Hide).await,
+            [("a.txt".to_string(), 0)]
+        );
+        assert_eq!(
+            listing(FrozenStrategy::Show).await,
+            [("a.txt".to_string(), 0), ("b.txt".to_string(), 42)]
+        );
+        assert_eq!(
+            listing(FrozenStrategy::Placeholder).await,
+            [("a.txt".to_string(), 0), ("b.txt.frozen".to_string(), 0)]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_seek_backward_reopens_stream() {
+        const DATA: &[u8] = b"0123456789abcdefghij";
+
+        let mut entry = file("data.bin");
+        entry.link = serve_data(DATA, ServeOptions::default()).parse().unwrap();
+        entry.size = DATA.len() as u64;
+        entry.bypassed = true;
+
+        let mut reader = DavFileRead::new(mounted_fs(&[ROOT_DIR]), entry);
+
+        assert_eq!(reader.read_bytes(12).await.unwrap(), &DATA[..12]);
+
+        reader.seek(std::io::SeekFrom::Start(4)).await.unwrap();
+        assert_eq!(reader.read_bytes(6).await.unwrap(), &DATA[4..10]);
+
+        // sequential reads keep using the same stream
+        assert_eq!(reader.read_bytes(4).await.unwrap(), &DATA[10..14]);
+    }
+
+    #[actix_web::test]
+    async fn test_conditional_requests() {
+        use actix_web::{App, test as actix_test};
+        use dav_server::DavHandler;
+
+        const DATA: &[u8] = b"0123456789abcdefghij";
+
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let mut entry = file("data.bin");
+        entry.link = serve_data(DATA, ServeOptions::default()).parse().unwrap();
+        entry.size = DATA.len() as u64;
+        entry.bypassed = true;
+        let etag = format!("\"{}\"", entry.md5);
+        let mut root = with_children(folder("root"), vec![DirEntry::File(entry)]);
+        fs.cache_listing(&fs.mounts[ROOT_DIR], ROOT_DIR, &mut root)
+            .await;
+
+        let handler = DavHandler::builder()
+            .filesystem(Box::new(fs))
+            .build_handler();
+        let app = actix_test::init_service(App::new().configure(|cfg| {
+            crate::server::configure(cfg, handler, crate::server::Routes::default())
+        }))
+        .await;
+
+        let get = |headers: &[(&'static str, &str)]| {
+            let mut req = actix_test::TestRequest::get().uri("/data.bin");
+            for &(name, value) in headers {
+                req = req.insert_header((name, value.to_string()));
+            }
+            req.to_request()
+        };
+
+        // unchanged since the client got its copy, only the range is sent
+        let req = get(&[("Range", "bytes=4-9"), ("If-Range", &etag)]);
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 206);
+        assert_eq!(actix_test::read_body(resp).await, &DATA[4..10]);
+
+        // changed, the whole file is sent again
+        let req = get(&[("Range", "bytes=4-9"), ("If-Range", "\"0123\"")]);
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(actix_test::read_body(resp).await, DATA);
+
+        let resp = actix_test::call_service(&app, get(&[("If-None-Match", &etag)])).await;
+        assert_eq!(resp.status().as_u16(), 304);
+
+        let req = get(&[("If-Modified-Since", "Sun, 07 Jul 2024 00:00:00 GMT")]);
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 304);
+
+        let resp = actix_test::call_service(&app, get(&[("If-Match", "\"0123\"")])).await;
+        assert_eq!(resp.status().as_u16(), 412);
+    }
+
+    #[actix_web::test]
+    async fn test_create_only_writes() {
+        use actix_web::{App, test as actix_test, web};
+        use dav_server::DavHandler;
+
+        let serve = |create_only: bool| async move {
+            let mut fs = mounted_fs(&[ROOT_DIR])
+                .dry_run(true)
+                .create_only(create_only);
+            fs.write_enabled = true;
+            let albums = folder("albums");
+            let mut root = with_children(
+                folder("root"),
+                vec![
+                    DirEntry::File(file("notes.txt")),
+                    DirEntry::Folder(albums.clone()),
+                ],
+            );
+            let mount = &fs.mounts[ROOT_DIR];
+            fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+            let mut prefetched = mount.prefetched.write().await;
+            prefetched.insert_file(ROOT_DIR, root);
+            prefetched.insert_file("/albums", albums);
+            drop(prefetched);
+
+            let handler = DavHandler::builder()
+                .filesystem(Box::new(fs.clone()))
+                .build_handler();
+            actix_test::init_service(App::new().app_data(web::Data::new(fs)).configure(|cfg| {
+                crate::server::configure(cfg, handler, crate::server::Routes::default())
+            }))
+            .await
+        };
+        let request = |method: &str, uri: &str, headers: &[(&'static str, &str)]| {
+            let method = actix_web::http::Method::from_bytes(method.as_bytes()).unwrap();
+            let mut req = actix_test::TestRequest::default()
+                .method(method.clone())
+                .uri(uri);
+            for &(name, value) in headers {
+                req = req.insert_header((name, value.to_string()));
+            }
+            if method == actix_web::http::Method::PUT {
+                req = req.set_payload("data");
+            }
+            req.to_request()
+        };
+
+        let app = serve(false).await;
+        let create_only = [("If-None-Match", "*")];
+        let resp = actix_test::call_service(&app, request("PUT", "/notes.txt", &create_only)).await;
+        assert_eq!(resp.status().as_u16(), 412);
+        let resp = actix_test::call_service(&app, request("MKCOL", "/albums", &create_only)).await;
+        assert_eq!(resp.status().as_u16(), 412);
+
+        // overwriting stays the default
+        let resp = actix_test::call_service(&app, request("PUT", "/notes.txt", &[])).await;
+        assert!(resp.status().is_success());
+
+        let app = serve(true).await;
+        let resp = actix_test::call_service(&app, request("PUT", "/notes.txt", &[])).await;
+        assert_eq!(resp.status().as_u16(), 412);
+        let resp = actix_test::call_service(&app, request("MKCOL", "/albums", &[])).await;
+        assert_eq!(resp.status().as_u16(), 412);
+        let resp = actix_test::call_service(
+            &app,
+            request("MOVE", "/albums", &[("Destination", "/notes.txt")]),
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 412);
+    }
+
+    #[actix_web::test]
+    async fn test_content_type_from_gofile() {
+        use actix_web::{App, test as actix_test, web};
+        use dav_server::DavHandler;
+
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let clip = FileEntry {
+            mimetype: Some("video/mp4".to_string()),
+            ..file("clip.bin")
+        };
+        let mut root = with_children(
+            folder("root"),
+            vec![DirEntry::File(clip), DirEntry::File(file("notes.txt"))],
+        );
+        fs.cache_listing(&fs.mounts[ROOT_DIR], ROOT_DIR, &mut root)
+            .await;
+
+        let handler = DavHandler::builder()
+            .filesystem(Box::new(fs.clone()))
+            .build_handler();
+        let app =
+            actix_test::init_service(App::new().app_data(web::Data::new(fs)).configure(|cfg| {
+                crate::server::configure(cfg, handler, crate::server::Routes::default())
+            }))
+            .await;
+
+        let content_type = |resp: &actix_web::dev::ServiceResponse| {
+            resp.headers()
+                .get("content-type")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        let req = actix_test::TestRequest::default()
+            .method(actix_web::http::Method::HEAD)
+            .uri("/clip.bin")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(content_type(&resp).as_deref(), Some("video/mp4"));
+
+        // no type from gofile.io, guessed from the extension
+        let req = actix_test::TestRequest::default()
+            .method(actix_web::http::Method::HEAD)
+            .uri("/notes.txt")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(content_type(&resp).as_deref(), Some("text/plain"));
+    }
+
+    #[actix_web::test]
+    async fn test_cache_control() {
+        use actix_web::{App, test as actix_test, web};
+        use dav_server::DavHandler;
+        use reqwest::header::HeaderValue;
+
+        use crate::server::CacheControl;
+
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let mut root = with_children(folder("root"), vec![DirEntry::File(file("notes.txt"))]);
+        fs.cache_listing(&fs.mounts[ROOT_DIR], ROOT_DIR, &mut root)
+            .await;
+
+        let serve = |cache_control: Option<&'static str>| {
+            let handler = DavHandler::builder()
+                .filesystem(Box::new(fs.clone()))
+                .build_handler();
+            let cache_control = CacheControl(cache_control.map(HeaderValue::from_static));
+
+            actix_test::init_service(
+                App::new()
+                    .app_data(web::Data::new(cache_control))
+                    .configure(|cfg| {
+                        crate::server::configure(cfg, handler, crate::server::Routes::default())
+                    }),
+            )
+        };
+        let head = || {
+            actix_test::TestRequest::default()
+                .method(actix_web::http::Method::HEAD)
+                .uri("/notes.txt")
+                .to_request()
+        };
+
+        let app = serve(Some("public, max-age=3600")).await;
+        let resp = actix_test::call_service(&app, head()).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(
+            resp.headers().get("cache-control").unwrap(),
+            "public, max-age=3600"
+        );
+        assert_eq!(resp.headers().get("accept-ranges").unwrap(), "bytes");
+
+        // not set, nothing is added
+        let app = serve(None).await;
+        let resp = actix_test::call_service(&app, head()).await;
+        assert!(!resp.headers().contains_key("cache-control"));
+        assert_eq!(resp.headers().get("accept-ranges").unwrap(), "bytes");
+    }
+
+    #[actix_web::test]
+    async fn test_json_api() {
+        use actix_web::{App, test as actix_test, web};
+        use dav_server::DavHandler;
+
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let mount = &fs.mounts[ROOT_DIR];
+        let notes = FileEntry {
+            size: 12,
+            ..file("notes.txt")
+        };
+        let mut root = with_children(
+            folder("root"),
+            vec![
+                DirEntry::File(notes.clone()),
+                DirEntry::Folder(folder("albums")),
+            ],
+        );
+        fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+        mount
+            .prefetched
+            .write()
+            .await
+            .insert_file(ROOT_DIR.to_string(), root);
+
+        let handler = DavHandler::builder()
+            .filesystem(Box::new(fs.clone()))
+            .build_handler();
+        let app =
+            actix_test::init_service(App::new().app_data(web::Data::new(fs)).configure(|cfg| {
+                crate::server::configure(
+                    cfg,
+                    handler,
+                    crate::server::Routes {
+                        json_api: true,
+                        ..Default::default()
+                    },
+                )
+            }))
+            .await;
+
+        let req = actix_test::TestRequest::get().uri("/api/list").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        let listing: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(listing[0]["name"], "albums");
+        assert_eq!(listing[0]["type"], "folder");
+        assert!(listing[0].get("md5").is_none());
+        assert_eq!(listing[1]["name"], "notes.txt");
+        assert_eq!(listing[1]["type"], "file");
+        assert_eq!(listing[1]["size"], 12);
+        assert_eq!(listing[1]["md5"], notes.md5.as_str());
+        assert_eq!(listing[1]["id"], notes.id.to_string());
+
+        // a file has nothing to list
+        let req = actix_test::TestRequest::get()
+            .uri("/api/list/notes.txt")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 403);
+    }
+
+    #[actix_web::test]
+    async fn test_create_missing_folders() {
+        let (url, api) = serve_api("Veil7n");
+        let fs = api_fs(&url, "Veil7n").create_parents(true);
+
+        let path = DavPath::new("/albums/2024/a.txt").unwrap();
+        let folder = fs.find_or_create_folder(&path.parent()).await.unwrap();
+        assert_eq!(folder.name, "2024");
+        assert_eq!(api.created.load(Ordering::SeqCst), 2);
+
+        // found from now on, not created again
+        let found = fs.find_or_create_folder(&path.parent()).await.unwrap();
+        assert_eq!(found.id, folder.id);
+        assert_eq!(api.created.load(Ordering::SeqCst), 2);
+
+        // only with `create_parents`
+        let fs = api_fs(&url, "Veil7n");
+        let path = DavPath::new("/videos/a.txt").unwrap();
+        assert!(matches!(
+            fs.find_or_create_folder(&path.parent()).await,
+            Err(GofileError::NotFound)
+        ));
+    }
 
-                    path = parent_path.to_owned()
-                }
-            }
+    #[actix_web::test]
+    async fn test_uploads_create_a_folder_once() {
+        let (url, api) = serve_api("Veil7n");
+        let fs = api_fs(&url, "Veil7n").create_parents(true);
+
+        let path = DavPath::new("/albums").unwrap();
+        let (first, second) = tokio::join!(
+            fs.find_or_create_folder(&path),
+            fs.find_or_create_folder(&path)
+        );
+
+        assert_eq!(first.unwrap().id, second.unwrap().id);
+        assert_eq!(api.created.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_open_stream_past_the_end() {
+        let mut entry = file("data.bin");
+        entry.size = 2 * PARALLEL_CHUNK_SIZE;
+        entry.servers = vec!["store1".to_string(), "store2".to_string()];
+        let fs = mounted_fs(&[ROOT_DIR]).parallel_downloads(4);
+
+        // seeking may go past the end, nothing is downloaded from there
+        for position in [entry.size, entry.size + 1] {
+            let mut reader = DavFileRead::new(fs.clone(), entry.clone());
+            reader.position = position;
+
+            let mut stream = reader.open_stream().await.unwrap();
+            assert!(stream.take_n_bytes(16).await.unwrap().is_empty());
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_download_fails_over_to_next_mirror() {
+        const DATA: &[u8] = b"0123456789";
+
+        let mut entry = file("data.bin");
+        entry.size = DATA.len() as u64;
+        entry.bypassed = true;
+        let reader = DavFileRead::new(mounted_fs(&[ROOT_DIR]), entry);
+
+        let forbidden = ServeOptions {
+            status: 403,
+            ..ServeOptions::default()
         };
+        let forbidden: Url = serve_data(b"forbidden", forbidden).parse().unwrap();
+        let unreachable: Url = "http://127.0.0.1:1/data.bin".parse().unwrap();
+        let working: Url = serve_data(DATA, ServeOptions::default()).parse().unwrap();
+
+        let stream = reader
+            .stream_from_first_mirror(&[forbidden.clone(), unreachable.clone(), working])
+            .await
+            .unwrap();
+        let mut buffer = BufferedStream::new(stream);
+        assert_eq!(buffer.take_n_bytes(DATA.len()).await.unwrap(), DATA);
+
+        // the error of the last one when none works
+        assert!(
+            reader
+                .stream_from_first_mirror(&[unreachable, forbidden])
+                .await
+                .is_err()
+        );
+    }
 
-        if current_path == orig_path {
-            let mut dir_guard = self.dircache.write().await;
-            let mut contents = self.client.get_contents(current_id.as_str()).await?;
+    #[actix_web::test]
+    async fn test_dropped_download_is_reopened() {
+        const DATA: &[u8] = b"0123456789abcdefghij";
 
-            if let DirEntry::Folder(ref mut folder) = contents {
-                let mut filtered_childs: HashMap<_, _> = HashMap::new();
-
-                for child in folder.children.values() {
-                    // TODO Implement strategies for files that can't be read or streamed.
-                    // Currently skipped if inaccessible or frozen
-                    if let DirEntry::File(file) = child
-                        && (!file.can_access || file.is_frozen)
-                    {
-                        continue;
-                    }
+        let mut entry = file("data.bin");
+        entry.size = DATA.len() as u64;
+        entry.bypassed = true;
 
-                    if let DirEntry::Folder(child_folder) = child {
-                        dir_guard.insert_dir(
-                            format!("/{}", child_folder.name),
-                            child_folder.code.clone(),
-                        );
-                    }
+        let dropping = ServeOptions {
+            drop_after: Some(8),
+            ..ServeOptions::default()
+        };
+        entry.link = serve_data(DATA, dropping).parse().unwrap();
+        let mut reader = DavFileRead::new(mounted_fs(&[ROOT_DIR]), entry.clone());
+        assert_eq!(reader.read_bytes(4).await.unwrap(), &DATA[..4]);
+        // the stream breaks within this read, it goes on from byte 4
+        assert_eq!(reader.read_bytes(12).await.unwrap(), &DATA[4..16]);
+        assert_eq!(reader.read_bytes(8).await.unwrap(), &DATA[16..]);
+        assert_eq!(reader.reconnects, 0);
+
+        entry.link = serve_data(DATA, dropping).parse().unwrap();
+        let fs = mounted_fs(&[ROOT_DIR]).download_reconnects(0);
+        let mut reader = DavFileRead::new(fs, entry);
+        assert!(reader.read_bytes(16).await.is_err());
+    }
 
-                    filtered_childs.insert(child.id(), child.clone());
-                }
+    #[actix_web::test]
+    async fn test_parallel_stream_keeps_chunks_in_order() {
+        const DATA: &[u8] = b"0123456789abcdefghij";
 
-                folder.children = filtered_childs;
-            }
+        // the first chunk comes back last
+        let slow = ServeOptions {
+            delay: Duration::from_millis(100),
+            ..ServeOptions::default()
+        };
+        let mirrors = vec![
+            serve_data(DATA, slow).parse().unwrap(),
+            serve_data(DATA, ServeOptions::default()).parse().unwrap(),
+        ];
+        let client = Client::builder().with_token("token").build();
 
-            return Ok(Some(contents));
-        }
+        let stream = parallel_stream(client, mirrors, 2..DATA.len() as u64, 4, 3);
+        let mut buffered = BufferedStream::new(stream);
 
-        let components = orig_path
-            .strip_prefix(&current_path)
-            .context("orig_path does not start with current_path")?
-            .split('/')
-            .filter(|s| !s.is_empty());
+        assert_eq!(buffered.take_n_bytes(100).await.unwrap(), &DATA[2..]);
+    }
 
-        for component in components {
-            let result = self.client.get_contents(current_id.as_str()).await;
-            let contents = match result {
-                Ok(contents) => contents,
-                Err(GofileError::NotFound) => return Ok(None),
-                Err(e) => return Err(e),
-            };
+    #[tokio::test]
+    async fn test_timed() {
+        let stalled = || futures_util::future::pending::<FsResult<()>>();
+        let timeout = Some(Duration::from_millis(10));
+
+        assert_eq!(
+            stalled().timed(timeout, || "stalled".to_string()).await,
+            Err(FsError::GeneralFailure)
+        );
+        assert_eq!(
+            async { Ok(7) }.timed(timeout, || "quick".to_string()).await,
+            Ok(7)
+        );
+
+        // without a timeout, waits as long as it takes
+        let waited = tokio::time::timeout(
+            Duration::from_millis(50),
+            stalled().timed(None, || "stalled".to_string()),
+        )
+        .await;
+        assert!(waited.is_err());
+
+        // told to the handler, to answer with a 504
+        let (result, timed_out) =
+            report_timeouts(stalled().timed(timeout, || "stalled".to_string())).await;
+        assert_eq!(result, Err(FsError::GeneralFailure));
+        assert!(timed_out);
+        let (result, timed_out) =
+            report_timeouts(async { Ok(7) }.timed(timeout, || "quick".to_string())).await;
+        assert_eq!(result, Ok(7));
+        assert!(!timed_out);
+    }
 
-            let mut found_contents = None;
-            {
-                let mut dir_guard = self.dircache.write().await;
-                if let DirEntry::Folder(ref folder) = contents {
-                    for child in folder.children.values() {
-                        // TODO Implement strategies for files that can't be read or streamed.
-                        // Currently skipped if inaccessible or frozen
-                        if let DirEntry::File(file) = child
-                            && (!file.can_access || file.is_frozen)
-                        {
-                            continue;
-                        }
+    #[tokio::test]
+    async fn test_read_ahead() {
+        let pulled = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = pulled.clone();
+        let chunks = (0..10u8).map(|i| Ok(Bytes::from(vec![i; 4])));
+        let stream = futures_util::stream::iter(chunks).inspect(move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        });
+
+        let mut buffered = BufferedStream::new(read_ahead(Box::pin(stream), 8));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // two chunks fill the room, the third waits for the reader
+        assert_eq!(pulled.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        // taking the first chunk off the channel makes room for one more
+        assert_eq!(buffered.take_n_bytes(2).await.unwrap(), &[0; 2][..]);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(pulled.load(std::sync::atomic::Ordering::SeqCst), 4);
+
+        let rest = buffered.take_n_bytes(100).await.unwrap();
+        assert_eq!(rest.len(), 38);
+        assert_eq!(rest[2..6], [1; 4]);
+        assert_eq!(rest[34..], [9; 4]);
+
+        // errors reach the reader, and nothing is read past them
+        let chunks = vec![Ok(Bytes::from_static(b"abcd")), Err(GofileError::NotFound)];
+        let mut buffered = BufferedStream::new(read_ahead(
+            Box::pin(futures_util::stream::iter(chunks)),
+            1024,
+        ));
+        assert!(buffered.take_n_bytes(8).await.is_err());
+    }
 
-                        if child.name() == component {
-                            found_contents = Some(child.clone())
-                        }
+    #[tokio::test]
+    async fn test_read_ahead_of_4_gib() {
+        // past what a u32 counts, the window is as large as it gets instead of empty
+        let window = ByteWindow::new(4 << 30);
+        assert_eq!(window.size, u32::MAX);
+        assert_eq!(window.permits(usize::MAX), u32::MAX);
+        assert_eq!(ByteWindow::new(0).size, 1);
+
+        let chunks = (0..4u8).map(|i| Ok(Bytes::from(vec![i; 4])));
+        let stream = read_ahead(Box::pin(futures_util::stream::iter(chunks)), 4 << 30);
+        let mut buffered = BufferedStream::new(stream);
+        assert_eq!(buffered.take_n_bytes(100).await.unwrap().len(), 16);
+    }
 
-                        if let DirEntry::Folder(child_folder) = child {
-                            dir_guard.insert_dir(
-                                format!("{current_path}/{}", child_folder.name),
-                                child_folder.code.clone(),
-                            );
-                        }
-                    }
-                }
-            }
-            if let Some(ref contents) = found_contents
-                && orig_path == format!("{current_path}/{}", contents.name())
-            {
-                return Ok(found_contents);
-            }
+    #[test]
+    fn test_mirror_urls() {
+        let mut entry = file("a.txt");
+        entry.link = "https://store1.gofile.io/download/web/0/a.txt"
+            .parse()
+            .unwrap();
+        entry.servers = vec!["store2".to_string(), "store1".to_string()];
+
+        assert_eq!(
+            mirror_urls(&entry)
+                .iter()
+                .map(Url::as_str)
+                .collect::<Vec<_>>(),
+            [
+                "https://store1.gofile.io/download/web/0/a.txt",
+                "https://store2.gofile.io/download/web/0/a.txt"
+            ]
+        );
+
+        entry.bypassed = true;
+        assert_eq!(mirror_urls(&entry), [entry.link.clone()]);
+    }
 
-            if let Some(DirEntry::Folder(folder)) = found_contents {
-                current_path = format!("{current_path}/{}", folder.name);
-                current_id = folder.code
-            } else {
-                return Ok(None);
-            }
-        }
+    #[actix_web::test]
+    async fn test_md5_mismatch_fails_the_last_read() {
+        const DATA: &[u8] = b"0123456789";
 
-        Ok(None)
+        let mut entry = file("data.bin");
+        entry.link = serve_data(DATA, ServeOptions::default()).parse().unwrap();
+        entry.size = DATA.len() as u64;
+        entry.bypassed = true;
+
+        let fs = mounted_fs(&[ROOT_DIR]).verify_md5(true);
+
+        entry.md5 = format!("{:x}", Md5::digest(DATA));
+        let mut reader = DavFileRead::new(fs.clone(), entry.clone());
+        assert_eq!(reader.read_bytes(6).await.unwrap(), &DATA[..6]);
+        assert_eq!(reader.read_bytes(6).await.unwrap(), &DATA[6..]);
+
+        entry.md5 = format!("{:x}", Md5::digest(b"corrupted"));
+        let mut reader = DavFileRead::new(fs, entry);
+        assert!(reader.read_bytes(6).await.is_ok());
+        assert!(reader.read_bytes(6).await.is_err());
     }
 
-    async fn try_find_folder(&self, path: &DavPathRef) -> GofileResult<FolderEntry> {
-        let contents = self.search(path).await?.ok_or(GofileError::NotFound)?;
+    #[test]
+    fn test_etag() {
+        let entry = DirEntry::File(file("a.txt"));
+        assert_eq!(
+            entry.etag().as_deref(),
+            Some("d41d8cd98f00b204e9800998ecf8427e")
+        );
+
+        let mut dir = folder("dir");
+        dir.mod_time = 0x10;
+        let tag = DirEntry::Folder(dir.clone()).etag().unwrap();
+        assert_eq!(tag, format!("{}-10", dir.id.simple()));
+        assert_eq!(DirEntry::Folder(dir).etag().unwrap(), tag);
+    }
 
-        match contents {
-            DirEntry::Folder(folder_entry) => Ok(folder_entry),
-            DirEntry::File(_) => Err(anyhow!(
-                "expected folder but found file at path {}",
-                path.as_url_string()
-            )
-            .into()),
-        }
+    #[test]
+    fn test_join_path() {
+        let parent = DavPath::new("/my%20dir/").unwrap();
+        let child = join_path(&parent, "a b#1.txt").unwrap();
+
+        assert_eq!(normalize_path(&child).unwrap(), "/my dir/a b#1.txt");
     }
 
-    async fn try_find_file(&self, path: &DavPathRef) -> GofileResult<FileEntry> {
-        let contents = self.search(path).await?.ok_or(GofileError::NotFound)?;
+    #[test]
+    fn test_normalize_url_path() {
+        for (path, expected) in [
+            ("/", ROOT_DIR),
+            ("", ROOT_DIR),
+            ("/a/b", "/a/b"),
+            ("/a/b/", "/a/b"),
+            ("a//b///", "/a/b"),
+            ("/a/./b/../c", "/a/c"),
+            ("/a/%2e%2e/b", "/b"),
+            ("/a/%2E/b%20c", "/a/b c"),
+            ("/a/..", ROOT_DIR),
+        ] {
+            assert_eq!(normalize_url_path(path).unwrap(), expected, "{path}");
+        }
 
-        match contents {
-            DirEntry::File(file_entry) => Ok(file_entry),
-            DirEntry::Folder(_) => Err(anyhow!(
-                "expected file but found folder at path {}",
-                path.as_url_string()
-            )
-            .into()),
+        // never above the root, however it's spelled
+        for path in ["/..", "/a/../..", "/%2e%2e/a", "/a/%2F..%2F..%2Fb"] {
+            assert!(
+                matches!(normalize_url_path(path), Err(GofileError::Forbidden)),
+                "{path}"
+            );
         }
     }
-}
 
-impl DavFileSystem for DavFs {
-    fn open<'a>(
-        &'a self,
-        path: &'a DavPath,
-        options: OpenOptions,
-    ) -> FsFuture<'a, Box<dyn FsDavFile>> {
-        async move {
-            if options.read {
-                let result = self.search(path).await?.ok_or(FsError::NotFound)?;
+    #[test]
+    fn test_copy_into_own_descendant() {
+        assert!(is_within("/a", "/a"));
+        assert!(is_within("/a/b/c", "/a"));
+        assert!(!is_within("/ab", "/a"));
+        assert!(is_within("/a", ROOT_DIR));
+    }
 
-                let file = if let DirEntry::File(file) = result {
-                    file
-                } else {
-                    return Err(FsError::Forbidden);
-                };
+    #[tokio::test]
+    async fn test_copy_and_move_into_itself() {
+        let mut fs = mounted_fs(&[ROOT_DIR]);
+        fs.write_enabled = true;
+        let into_itself = |from: &str, to: &str| {
+            let from = DavPath::new(from).unwrap();
+            let to = DavPath::new(to).unwrap();
+            fs.is_into_itself(&from, &to).unwrap()
+        };
 
-                return Ok(Box::new(DavFileRead::new(self.clone(), file)) as Box<dyn FsDavFile>);
-            } else if self.write_enabled && options.write {
-                if options.append {
-                    return Err(FsError::NotImplemented);
-                }
+        assert!(into_itself("/a", "/a"));
+        assert!(into_itself("/a/", "/a"));
+        assert!(into_itself("/my%20dir", "/my dir/sub/"));
+        assert!(!into_itself("/a", "/ab"));
+        assert!(!into_itself("/a/b", "/a"));
+        assert!(!into_itself("/a", "/A/b"));
+
+        // refused before anything is looked up
+        let from = DavPath::new("/a").unwrap();
+        let to = DavPath::new("/a/b").unwrap();
+        assert_eq!(fs.rename(&from, &to).await, Err(FsError::Forbidden));
+        assert_eq!(fs.copy(&from, &to).await, Err(FsError::Forbidden));
+
+        let fs = fs.case_insensitive(true);
+        let into_itself = |from: &str, to: &str| {
+            let from = DavPath::new(from).unwrap();
+            let to = DavPath::new(to).unwrap();
+            fs.is_into_itself(&from, &to).unwrap()
+        };
+        assert!(into_itself("/a", "/A/b"));
+        // only the case changes
+        assert!(!into_itself("/a", "/A"));
+    }
 
-                return Ok(
-                    Box::new(DavFileWrite::new(self.clone(), path.clone())) as Box<dyn FsDavFile>
-                );
-            }
+    #[test]
+    fn test_file_overwrite_is_not_a_conflict() {
+        let parent = with_children(folder("root"), vec![DirEntry::File(file("probe"))]);
 
+        assert_eq!(check_file_name_conflict(&parent, "probe", false), Ok(()));
+    }
+
+    #[test]
+    fn test_rename_onto_existing_entries() {
+        let file_from = DirEntry::File(file("a.txt"));
+        let folder_from = DirEntry::Folder(folder("a"));
+        let file_to = file("b.txt");
+        let empty_folder_to = DirEntry::Folder(folder("b"));
+        let folder_to = DirEntry::Folder(with_children(
+            folder("b"),
+            vec![DirEntry::File(file("c.txt"))],
+        ));
+
+        // nothing there
+        assert_eq!(replaced_by_rename(&file_from, None), Ok(None));
+        assert_eq!(replaced_by_rename(&folder_from, None), Ok(None));
+
+        // a file replaces a file
+        let existing = DirEntry::File(file_to.clone());
+        assert_eq!(
+            replaced_by_rename(&file_from, Some(&existing)),
+            Ok(Some(file_to.id))
+        );
+
+        // whatever the parent, a folder never replaces a folder
+        assert_eq!(
+            replaced_by_rename(&folder_from, Some(&empty_folder_to)),
+            Err(FsError::Exists)
+        );
+        assert_eq!(
+            replaced_by_rename(&folder_from, Some(&folder_to)),
+            Err(FsError::Exists)
+        );
+
+        // nor takes the place of the other kind
+        assert_eq!(
+            replaced_by_rename(&file_from, Some(&folder_to)),
             Err(FsError::Forbidden)
-        }
-        .boxed()
+        );
+        assert_eq!(
+            replaced_by_rename(&file_from, Some(&empty_folder_to)),
+            Err(FsError::Forbidden)
+        );
+        assert_eq!(
+            replaced_by_rename(&folder_from, Some(&existing)),
+            Err(FsError::Forbidden)
+        );
     }
 
-    fn read_dir<'a>(
-        &'a self,
-        path: &'a DavPath,
-        _meta: ReadDirMeta,
-    ) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
-        async move {
-            let result = self.search(path).await?.ok_or(FsError::NotFound)?;
+    #[tokio::test]
+    async fn test_resolve_dispatches_on_mount_name() {
+        let fs = mounted_fs(&["media", "projects"]);
 
-            let childrens = if let DirEntry::Folder(folder) = result {
-                folder.children.into_values()
-            } else {
-                return Err(FsError::Forbidden);
-            };
+        assert!(fs.resolve(ROOT_DIR).unwrap().is_none());
+
+        let (mount, path) = fs.resolve("/projects/a/b.txt").unwrap().unwrap();
+        assert_eq!(path, "/a/b.txt");
+        assert_eq!(
+            mount.dircache.write().await.find_dir(ROOT_DIR).unwrap(),
+            "projects-code"
+        );
+
+        let (_, path) = fs.resolve("/media").unwrap().unwrap();
+        assert_eq!(path, ROOT_DIR);
+
+        assert!(matches!(
+            fs.resolve("/other/a.txt"),
+            Err(GofileError::NotFound)
+        ));
+    }
 
-            let stream = futures_util::stream::iter(
-                childrens.map(|entry| Ok::<Box<dyn DavDirEntry>, FsError>(Box::new(entry.clone()))),
+    #[test]
+    fn test_single_mount_is_served_as_root() {
+        let fs = mounted_fs(&[ROOT_DIR]);
+
+        let (_, path) = fs.resolve("/projects/a.txt").unwrap().unwrap();
+        assert_eq!(path, "/projects/a.txt");
+        assert!(!fs.is_mount_point(&DavPath::new("/").unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_mount_points_cannot_be_modified() {
+        let fs = mounted_fs(&["media", "projects"]);
+
+        for path in ["/", "/media", "/projects/"] {
+            assert!(
+                fs.is_mount_point(&DavPath::new(path).unwrap()).unwrap(),
+                "{path}"
             );
-            Ok(Box::pin(stream) as FsStream<Box<dyn DavDirEntry>>)
         }
-        .boxed()
+        assert!(
+            !fs.is_mount_point(&DavPath::new("/media/a.txt").unwrap())
+                .unwrap()
+        );
     }
 
-    fn metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
-        async move {
-            let result = self.search(path).await?.ok_or(FsError::NotFound)?;
+    #[tokio::test]
+    async fn test_uploads_to_a_path_are_serialized() {
+        let locks = PathLocks::default();
 
-            result.metadata().await
-        }
-        .boxed()
+        let first = locks.lock("/a.txt".to_string()).await;
+        let _other = locks.lock("/b.txt".to_string()).await;
+
+        let second = tokio::spawn({
+            let locks = locks.clone();
+            async move { locks.lock("/a.txt".to_string()).await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!second.is_finished());
+
+        drop(first);
+        let second = second.await.unwrap();
+        assert_eq!(locks.0.lock().unwrap().len(), 2);
+
+        // forgotten once released
+        drop(second);
+        assert!(!locks.0.lock().unwrap().contains_key("/a.txt"));
     }
 
-    fn create_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
-        async move {
-            if !self.write_enabled {
-                return Err(FsError::Forbidden);
-            }
+    #[tokio::test]
+    async fn test_flush_uploads_once() {
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let mut file = DavFileWrite::new(fs, DavPath::new("/a.txt").unwrap(), false, None);
+        file.flushed = true;
 
-            let folder_name = path
-                .file_name()
-                .map(String::from)
-                .ok_or(FsError::GeneralFailure)?;
+        // no empty upload replacing the file uploaded by the first flush
+        file.flush().await.unwrap();
+        assert!(file.upload_lock.is_none());
+    }
 
-            let parent_folder_entry = self.try_find_folder(&path.parent()).await?;
+    #[tokio::test]
+    async fn test_dropped_write_aborts_upload() {
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let mut file = DavFileWrite::new(fs, DavPath::new("/a.txt").unwrap(), false, None);
+        let upload = tokio::spawn(std::future::pending());
+        let abort = upload.abort_handle();
+        file.handle = Some(upload);
+
+        // never flushed, the client won't learn about the upload
+        drop(file);
+        tokio::task::yield_now().await;
+        assert!(abort.is_finished());
+    }
 
-            let exist = parent_folder_entry
-                .children
-                .values()
-                .any(|v| v.name() == folder_name);
+    #[tokio::test]
+    async fn test_flush_survives_failed_delete_of_replaced_file() {
+        // deleting fails, gofile.io can't be reached
+        let fs = api_fs("http://127.0.0.1:1", "Veil7n");
+        let mount = &fs.mounts[ROOT_DIR];
+        let replaced = file("a.txt");
+        let mut root = with_children(folder("root"), vec![DirEntry::File(replaced.clone())]);
+        fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+        mount.prefetched.write().await.insert_file(ROOT_DIR, root);
+
+        let mut write = DavFileWrite::new(fs.clone(), DavPath::new("/a.txt").unwrap(), false, None);
+        write.replaces = Some(replaced.id);
+        write.handle = Some(tokio::spawn(async {
+            Ok(FileUploaded {
+                create_time: 0,
+                download_page: String::new(),
+                id: Uuid::new_v4(),
+                md5: String::new(),
+                mimetype: String::new(),
+                mod_time: 0,
+                name: "a.txt".to_string(),
+                parent_folder: String::new(),
+                parent_folder_code: String::new(),
+                servers: Vec::new(),
+                size: 0,
+                r#type: "file".to_string(),
+            })
+        }));
+
+        assert!(mount.filecache.read().await.find_file("/a.txt").is_some());
+
+        // the new file is in place, listings show it next to the old one
+        write.flush().await.unwrap();
+        assert!(mount.filecache.read().await.find_file("/a.txt").is_none());
+        assert!(mount.prefetched.read().await.find_file(ROOT_DIR).is_none());
+    }
 
-            if exist {
-                return Ok(());
-            }
+    #[tokio::test]
+    async fn test_upload_buffer_bounds_queued_bytes() {
+        use std::sync::atomic::{AtomicU64, Ordering};
 
-            self.client
-                .create_folder(parent_folder_entry.id, folder_name)
-                .await?;
+        const LIMIT: u64 = 64;
 
-            Ok(())
+        let (tx, stream) = upload_channel(Some(LIMIT as usize));
+        let received = Arc::new(AtomicU64::new(0));
+
+        let consumer = tokio::spawn({
+            let received = received.clone();
+            async move {
+                let mut stream = std::pin::pin!(stream);
+                while let Some(chunk) = stream.next().await {
+                    received.fetch_add(chunk.unwrap().len() as u64, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(2)).await;
+                }
+            }
+        });
+
+        let mut sent = 0;
+        let mut max_queued = 0;
+        for _ in 0..50 {
+            tx.send(Ok(Bytes::from_static(&[0; 16]))).await.unwrap();
+            sent += 16;
+            max_queued = max_queued.max(sent - received.load(Ordering::SeqCst));
         }
-        .boxed()
+        drop(tx);
+        consumer.await.unwrap();
+
+        assert!(max_queued <= LIMIT, "{max_queued} bytes queued");
+        assert_eq!(received.load(Ordering::SeqCst), sent);
+
+        // a chunk larger than the window still goes through
+        let (tx, stream) = upload_channel(Some(4));
+        tx.send(Ok(Bytes::from_static(b"0123456789")))
+            .await
+            .unwrap();
+        drop(tx);
+        let chunks: Vec<_> = stream.collect().await;
+        assert_eq!(chunks[0].as_ref().unwrap(), &b"0123456789"[..]);
+
+        // past what a u32 counts, the window is as large as it gets instead of empty
+        let (tx, stream) = upload_channel(Some(4 << 30));
+        tx.send(Ok(Bytes::from_static(b"0123"))).await.unwrap();
+        drop(tx);
+        let chunks: Vec<_> = stream.collect().await;
+        assert_eq!(chunks.len(), 1);
     }
 
-    fn rename<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
-        async move {
-            if !self.write_enabled {
-                return Err(FsError::Forbidden);
-            }
+    #[test]
+    fn test_upload_size() {
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let path = DavPath::new("/a.txt").unwrap();
 
-            let name_from = from
-                .file_name()
-                .map(String::from)
-                .ok_or(FsError::GeneralFailure)?;
+        let file = DavFileWrite::new(fs.clone(), path.clone(), false, Some(42));
+        assert_eq!(file.upload_size(), Some(42));
+        let file = DavFileWrite::new(fs.clone(), path.clone(), false, None);
+        assert_eq!(file.upload_size(), None);
 
-            let name_to = to
-                .file_name()
-                .map(String::from)
-                .ok_or(FsError::GeneralFailure)?;
+        // the current contents go first, streamed chunked
+        let file = DavFileWrite::new(fs, path, true, Some(42));
+        assert_eq!(file.upload_size(), None);
+    }
 
-            let parent_folder_from_entry = self.try_find_folder(&from.parent()).await?;
+    #[tokio::test]
+    async fn test_listings_wait_for_a_slot() {
+        let fs = mounted_fs(&[ROOT_DIR]).max_concurrent_listings(1);
+        let permits = fs.listing_permits.clone().unwrap();
 
-            let contents_from = parent_folder_from_entry
-                .children
-                .values()
-                .find(|v| v.name() == name_from)
-                .ok_or(FsError::GeneralFailure)?;
+        let _held = permits.acquire().await.unwrap();
+        let listing =
+            tokio::time::timeout(Duration::from_millis(50), fs.get_contents(Uuid::new_v4()));
+        assert!(listing.await.is_err());
+    }
 
-            let mut files_to_delete = Vec::with_capacity(2);
+    #[tokio::test]
+    async fn test_pending_writes_drain() {
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let pending = fs.pending_writes();
 
-            if from.parent() == to.parent() {
-                // share parent folder - simple rename
+        let file = DavFileWrite::new(fs.clone(), DavPath::new("/a.txt").unwrap(), false, None);
+        assert_eq!(pending.count(), 1);
 
-                let contents_to = parent_folder_from_entry
-                    .children
-                    .values()
-                    .find(|v| v.name() == name_to);
+        let drained = tokio::spawn(async move { pending.drained().await });
+        tokio::task::yield_now().await;
+        assert!(!drained.is_finished());
 
-                match (contents_from, contents_to) {
-                    (DirEntry::File(_), Some(DirEntry::File(file_to))) => {
-                        files_to_delete.push(file_to.id);
-                    }
-                    (DirEntry::Folder(_), Some(DirEntry::Folder(_))) => {
-                        return Err(FsError::Exists);
-                    }
-                    (_, None) => (),
-                    _ => return Err(FsError::GeneralFailure),
-                }
+        drop(file);
+        drained.await.unwrap();
+        assert_eq!(fs.pending_writes().count(), 0);
+    }
 
-                let new_attribute = Attribute::Name(&name_to);
+    #[tokio::test]
+    async fn test_dry_run_skips_writes() {
+        let mut fs = mounted_fs(&[ROOT_DIR]).dry_run(true);
+        fs.write_enabled = true;
+        let mount = &fs.mounts[ROOT_DIR];
+
+        let mut root = with_children(folder("root"), vec![DirEntry::File(file("a.txt"))]);
+        fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+
+        // gofile.io is never asked, the cached listing stays as it is
+        let path = DavPath::new("/a.txt").unwrap();
+        fs.remove_file(&path).await.unwrap();
+        assert!(mount.filecache.read().await.find_file("/a.txt").is_some());
+
+        let mut upload = DavFileWrite::new(fs.clone(), path, false, None);
+        upload
+            .write_bytes(Bytes::from_static(b"data"))
+            .await
+            .unwrap();
+        upload.flush().await.unwrap();
+        assert_eq!(upload.written, 4);
+    }
 
-                self.client
-                    .update_attribute(contents_from.id(), new_attribute)
-                    .await?;
-            } else {
-                let parent_folder_to_entry = self.try_find_folder(&to.parent()).await?;
+    #[tokio::test]
+    async fn test_prewarm() {
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let mount = &fs.mounts[ROOT_DIR];
+
+        let docs = FolderEntry {
+            code: "docs-code".to_string(),
+            ..folder("docs")
+        };
+        let root = with_children(
+            folder("root"),
+            vec![DirEntry::Folder(docs), DirEntry::File(file("a.txt"))],
+        );
+
+        assert_eq!(fs.prewarm(ROOT_DIR, root.clone()).await, 2);
+        assert_eq!(fs.prewarm("missing", root.clone()).await, 0);
+        // nothing expires before the listing is used
+        assert!(mount.prefetched.read().await.find_file(ROOT_DIR).is_none());
+
+        // served without asking gofile.io
+        let path = DavPath::new("/").unwrap();
+        let Some(DirEntry::Folder(found)) = fs.search(&path).await.unwrap() else {
+            panic!("expected the prewarmed root");
+        };
+        assert_eq!(found.id, root.id);
+        assert_eq!(
+            mount.dircache.write().await.find_dir("/docs").cloned(),
+            Some("docs-code".to_string())
+        );
+        assert!(mount.prewarmed.lock().await.is_none());
+        assert!(mount.prefetched.read().await.find_file(ROOT_DIR).is_some());
+
+        // a change before the first lookup drops it
+        fs.prewarm(ROOT_DIR, root).await;
+        fs.invalidate(&DavPath::new("/b.txt").unwrap())
+            .await
+            .unwrap();
+        assert!(mount.prewarmed.lock().await.is_none());
+    }
 
-                let contents_to = parent_folder_to_entry
-                    .children
-                    .values()
-                    .find(|v| v.name() == name_to);
+    #[tokio::test]
+    async fn test_prefetched_listing_is_served_until_invalidated() {
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let mount = &fs.mounts[ROOT_DIR];
+
+        let docs = with_children(folder("docs"), vec![DirEntry::File(file("a.txt"))]);
+        mount
+            .dircache
+            .write()
+            .await
+            .insert_dir("/docs", "docs-code".to_string());
+        mount
+            .prefetched
+            .write()
+            .await
+            .insert_file("/docs", docs.clone());
+
+        // served without asking gofile.io
+        let path = DavPath::new("/docs").unwrap();
+        let Some(DirEntry::Folder(found)) = fs.search(&path).await.unwrap() else {
+            panic!("expected the prefetched folder");
+        };
+        assert_eq!(found.id, docs.id);
 
-                match (contents_from, contents_to) {
-                    (DirEntry::File(_), Some(DirEntry::File(file_to))) => {
-                        files_to_delete.push(file_to.id);
-                    }
-                    (DirEntry::Folder(_), _) => {
-                        // currently no move for folders
-                        return Err(FsError::NotImplemented);
-                    }
-                    (_, None) => (),
-                    _ => return Err(FsError::GeneralFailure),
-                }
+        fs.invalidate(&DavPath::new("/docs/b.txt").unwrap())
+            .await
+            .unwrap();
+        assert!(mount.prefetched.read().await.find_file("/docs").is_none());
+    }
 
-                // file to file move - simple copy + delete
-                files_to_delete.push(contents_from.id());
-                self.copy(from, to).await?;
-            }
+    #[actix_web::test]
+    async fn test_move_fallback_over_existing_file() {
+        let (url, api) = serve_api("Veil7n");
+        let docs = api.add_folder("Veil7n", "docs");
+        let moved = api.add_file("Veil7n", "a.txt");
+        let replaced = api.add_file(&docs.to_string(), "a.txt");
+        let fs = api_fs(&url, "Veil7n");
+
+        // the move is refused, the file is copied over the other one instead
+        let from = DavPath::new("/a.txt").unwrap();
+        let to = DavPath::new("/docs/a.txt").unwrap();
+        fs.rename(&from, &to).await.unwrap();
+
+        assert!(!api.exists(moved) && !api.exists(replaced));
+        let copy = api.find_file("a.txt").unwrap();
+        assert_eq!(copy.parent_folder, docs.to_string());
+
+        // deleted by the copy only
+        let requested = api.delete_requested.lock().unwrap().clone();
+        let replaced = replaced.to_string();
+        assert_eq!(requested.iter().filter(|id| **id == replaced).count(), 1);
+    }
 
-            if !files_to_delete.is_empty() {
-                self.client.delete_contents(&files_to_delete).await?;
-            }
+    #[actix_web::test]
+    async fn test_created_dir_is_listed() {
+        let (url, api) = serve_api("Veil7n");
+        api.add_folder("Veil7n", "docs");
+        let fs = api_fs(&url, "Veil7n").prefetch_depth(1);
 
-            Ok(())
+        let Some(DirEntry::Folder(root)) = fs.search(&DavPath::new("/").unwrap()).await.unwrap()
+        else {
+            panic!("expected the root folder");
+        };
+        fs.prefetch(ROOT_DIR, &root).await;
+
+        fs.create_dir(&DavPath::new("/docs/new").unwrap())
+            .await
+            .unwrap();
+
+        // not the listing prefetched before
+        let Some(DirEntry::Folder(docs)) =
+            fs.search(&DavPath::new("/docs").unwrap()).await.unwrap()
+        else {
+            panic!("expected the docs folder");
+        };
+        assert!(find_child(&docs, "new", false).is_some());
+        let new = fs
+            .search(&DavPath::new("/docs/new").unwrap())
+            .await
+            .unwrap();
+        assert!(matches!(new, Some(DirEntry::Folder(_))));
+    }
+
+    #[actix_web::test]
+    async fn test_prefetch_after_invalidation_is_dropped() {
+        let (url, api) = serve_api("Veil7n");
+        let docs = api.add_folder("Veil7n", "docs");
+        api.add_folder(&docs.to_string(), "old");
+        let fs = api_fs(&url, "Veil7n").prefetch_depth(1);
+        let mount = &fs.mounts[ROOT_DIR];
+
+        let Some(DirEntry::Folder(root)) = fs.search(&DavPath::new("/").unwrap()).await.unwrap()
+        else {
+            panic!("expected the root folder");
+        };
+
+        // the listing of /docs comes back after the change
+        *api.listing_delay.lock().unwrap() = Duration::from_millis(200);
+        let prefetch = tokio::spawn({
+            let fs = fs.clone();
+            async move { fs.prefetch(ROOT_DIR, &root).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        fs.invalidate(&DavPath::new("/docs/old").unwrap())
+            .await
+            .unwrap();
+        prefetch.await.unwrap();
+
+        assert!(mount.prefetched.read().await.find_file("/docs").is_none());
+        assert!(mount.dircache.write().await.find_dir("/docs/old").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_prefetches_are_bounded() {
+        let (url, api) = serve_api("Veil7n");
+        api.add_folder("Veil7n", "docs");
+        let fs = api_fs(&url, "Veil7n").prefetch_depth(1);
+        let mount = &fs.mounts[ROOT_DIR];
+        let root = DavPath::new("/").unwrap();
+
+        // every prefetch slot taken, listing prefetches nothing
+        let running = fs
+            .prefetches
+            .clone()
+            .acquire_many_owned(MAX_PREFETCHES as u32)
+            .await
+            .unwrap();
+        let _listed = fs.read_dir(&root, ReadDirMeta::None).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(mount.prefetched.read().await.find_file("/docs").is_none());
+
+        drop(running);
+        let _listed = fs.read_dir(&root, ReadDirMeta::None).await.unwrap();
+        for _ in 0..100 {
+            if mount.prefetched.read().await.find_file("/docs").is_some() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
         }
-        .boxed()
+        panic!("/docs wasn't prefetched");
     }
 
-    fn remove_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
-        self.remove(path, true).map_err(Into::into).boxed()
+    #[tokio::test]
+    async fn test_gofile_props() {
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let mount = &fs.mounts[ROOT_DIR];
+
+        let photo = FileEntry {
+            thumbnail: Some("https://store1.gofile.io/thumb?id=1&size=small".to_string()),
+            download_count: 7,
+            ..file("photo.jpg")
+        };
+        let albums = FolderEntry {
+            public: true,
+            is_owner: true,
+            ..folder("albums")
+        };
+        let mut root = with_children(
+            folder("root"),
+            vec![
+                DirEntry::File(photo),
+                DirEntry::File(file("notes.txt")),
+                DirEntry::Folder(albums.clone()),
+            ],
+        );
+        fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+        mount
+            .prefetched
+            .write()
+            .await
+            .insert_file("/albums", albums);
+
+        let path = DavPath::new("/photo.jpg").unwrap();
+        let names: Vec<_> = fs
+            .get_props(&path, false)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|prop| prop.name)
+            .collect();
+        assert_eq!(names, ["download-count", "is-owner", "md5", "thumbnail"]);
+
+        let thumbnail = fs
+            .get_prop(&path, gofile_prop("thumbnail", None))
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(thumbnail).unwrap(),
+            r#"<G:thumbnail xmlns:G="http://gofile.io/ns">https://store1.gofile.io/thumb?id=1&amp;size=small</G:thumbnail>"#
+        );
+
+        // only listed when the file has one
+        let path = DavPath::new("/notes.txt").unwrap();
+        assert!(
+            fs.get_prop(&path, gofile_prop("thumbnail", None))
+                .await
+                .is_err()
+        );
+        assert_eq!(fs.get_props(&path, true).await.unwrap().len(), 3);
+
+        // folders only report who owns them and whether they are shared
+        let path = DavPath::new("/albums").unwrap();
+        let names: Vec<_> = fs
+            .get_props(&path, false)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|prop| prop.name)
+            .collect();
+        assert_eq!(names, ["is-owner", "public"]);
+
+        let public = fs
+            .get_prop(&path, gofile_prop("public", None))
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(public).unwrap(),
+            r#"<G:public xmlns:G="http://gofile.io/ns">true</G:public>"#
+        );
+        assert!(
+            fs.get_prop(&path, gofile_prop("download-count", None))
+                .await
+                .is_err()
+        );
     }
 
-    fn remove_file<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
-        self.remove(path, false).map_err(Into::into).boxed()
+    #[tokio::test]
+    async fn test_patch_public() {
+        let mut fs = mounted_fs(&[ROOT_DIR]).dry_run(true);
+        fs.write_enabled = true;
+        let mount = &fs.mounts[ROOT_DIR];
+
+        let owned = FolderEntry {
+            is_owner: true,
+            ..folder("albums")
+        };
+        let shared = folder("shared");
+        let mut root = with_children(
+            folder("root"),
+            vec![
+                DirEntry::Folder(owned.clone()),
+                DirEntry::Folder(shared.clone()),
+            ],
+        );
+        fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+        let mut prefetched = mount.prefetched.write().await;
+        prefetched.insert_file("/albums", owned);
+        prefetched.insert_file("/shared", shared);
+        drop(prefetched);
+
+        let statuses = |results: Vec<(StatusCode, DavProp)>| {
+            results
+                .into_iter()
+                .map(|(status, prop)| (status.as_u16(), prop.name))
+                .collect::<Vec<_>>()
+        };
+        let set = |name: &str, value: &str| (true, gofile_prop(name, Some(value)));
+
+        let albums = DavPath::new("/albums").unwrap();
+        let results = fs
+            .patch_props(&albums, vec![set("public", "false")])
+            .await
+            .unwrap();
+        assert_eq!(statuses(results), [(200, "public".to_string())]);
+
+        let results = fs
+            .patch_props(&albums, vec![set("public", "maybe")])
+            .await
+            .unwrap();
+        assert_eq!(statuses(results), [(409, "public".to_string())]);
+
+        // all or nothing
+        let results = fs
+            .patch_props(&albums, vec![set("public", "1"), set("md5", "0")])
+            .await
+            .unwrap();
+        assert_eq!(
+            statuses(results),
+            [(424, "public".to_string()), (403, "md5".to_string())]
+        );
+
+        let results = fs
+            .patch_props(&albums, vec![(false, gofile_prop("public", None))])
+            .await
+            .unwrap();
+        assert_eq!(statuses(results), [(403, "public".to_string())]);
+
+        // only the owner can share a folder
+        let path = DavPath::new("/shared").unwrap();
+        let results = fs
+            .patch_props(&path, vec![set("public", "true")])
+            .await
+            .unwrap();
+        assert_eq!(statuses(results), [(403, "public".to_string())]);
+
+        fs.write_enabled = false;
+        let results = fs
+            .patch_props(&albums, vec![set("public", "true")])
+            .await
+            .unwrap();
+        assert_eq!(statuses(results), [(403, "public".to_string())]);
     }
 
-    fn copy<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
-        async move {
-            if !self.write_enabled {
-                return Err(FsError::Forbidden);
-            }
+    #[tokio::test]
+    async fn test_open_writes() {
+        let writes = OpenWrites::default();
+        let upload = tokio::spawn(std::future::pending::<()>());
+
+        let id = writes.open("/a.txt".to_string(), upload.abort_handle(), Arc::default());
+        let flushed = writes.open("/b.txt".to_string(), upload.abort_handle(), Arc::default());
+        assert!(writes.take_idle(Duration::from_secs(60)).is_empty());
+        assert!(writes.close(flushed));
 
-            // https://github.com/messense/dav-server-rs/blob/4a1ae81485f46fe308c1eae5e474a0800fa68109/src/handle_gethead.rs#L29C1-L30C1
-            const READ_BUF_SIZE: usize = 16384;
+        // waiting on gofile.io isn't idle
+        let sending = writes.sending(id);
+        assert!(writes.take_idle(Duration::ZERO).is_empty());
+        drop(sending);
 
-            let file_from = self.try_find_file(from).await?;
+        let idle = writes.take_idle(Duration::ZERO);
+        assert_eq!(idle.len(), 1);
+        assert_eq!(idle[0].path, "/a.txt");
 
-            let mut file_from = DavFileRead::new(self.clone(), file_from);
-            let mut file_to = DavFileWrite::new(self.clone(), to.clone());
+        // the flush finds it reaped
+        assert!(!writes.close(id));
+        upload.abort();
+    }
+
+    #[tokio::test]
+    async fn test_reap_idle_writes() {
+        let fs = mounted_fs(&[ROOT_DIR]).write_idle_timeout(Duration::from_millis(100));
+        let writes = fs.open_writes.clone();
+
+        let abandoned = tokio::spawn(std::future::pending::<()>());
+        let waiting = tokio::spawn(std::future::pending::<()>());
+        let abandoned_id = writes.open(
+            "/a.txt".to_string(),
+            abandoned.abort_handle(),
+            Arc::default(),
+        );
+        let waiting_id = writes.open("/b.txt".to_string(), waiting.abort_handle(), Arc::default());
+        let sending = writes.sending(waiting_id);
+
+        let reaper = tokio::spawn(fs.reap_idle_writes());
+        // the first check runs right away, the next one after a second
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        assert!(abandoned.await.unwrap_err().is_cancelled());
+        assert!(!writes.close(abandoned_id));
+        // still waiting for gofile.io to take a chunk
+        assert!(!waiting.is_finished());
+        drop(sending);
+        assert!(writes.close(waiting_id));
+
+        reaper.abort();
+        waiting.abort();
+    }
 
-            let total_size = file_from.file.size;
+    #[tokio::test]
+    async fn test_quota_is_cached() {
+        let fs = mounted_fs(&[ROOT_DIR]);
+        *fs.quota.lock().await = Some((Instant::now(), Ok((1024, 4096))));
 
-            let mut pos: u64 = 0;
+        // shared by every clone, gofile.io isn't asked again
+        assert_eq!(fs.clone().get_quota().await.unwrap(), (1024, Some(4096)));
+    }
 
-            while pos != total_size {
-                let offset = std::io::SeekFrom::Start(pos);
-                file_from.seek(offset).await?;
+    #[tokio::test]
+    async fn test_quota_failure_is_cached() {
+        let fs = api_fs("http://127.0.0.1:1", "Veil7n");
+        assert!(fs.get_quota().await.is_err());
 
-                let data = file_from.read_bytes(READ_BUF_SIZE).await?;
-                let bytes_read = data.len();
+        // kept as long as a fetched quota: told apart from the first failure, the next
+        // call answers with it instead of asking the API again
+        let cached = fs.quota.lock().await.take();
+        let Some((fetched, Err(_))) = cached else {
+            panic!("failure not cached: {cached:?}");
+        };
+        *fs.quota.lock().await = Some((fetched, Err(Arc::new(GofileError::NotFound))));
+        assert_eq!(fs.get_quota().await, Err(FsError::NotFound));
+    }
 
-                file_to.write_bytes(data).await?;
+    #[tokio::test]
+    async fn test_toggle_writes() {
+        let mut fs = mounted_fs(&[ROOT_DIR]).dry_run(true);
+        fs.write_enabled = true;
+        let mount = &fs.mounts[ROOT_DIR];
+
+        let mut root = with_children(
+            folder("root"),
+            vec![
+                DirEntry::File(file("a.txt")),
+                DirEntry::Folder(folder("albums")),
+            ],
+        );
+        fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+
+        let new = DavPath::new("/new").unwrap();
+        let a = DavPath::new("/a.txt").unwrap();
+        let b = DavPath::new("/b.txt").unwrap();
+        let write = OpenOptions {
+            write: true,
+            create: true,
+            ..Default::default()
+        };
 
-                pos += bytes_read as u64;
-            }
+        assert!(fs.toggle_writes());
+        assert!(matches!(fs.create_dir(&new).await, Err(FsError::Forbidden)));
+        assert!(matches!(
+            fs.open(&b, write.clone()).await,
+            Err(FsError::Forbidden)
+        ));
+        assert!(matches!(fs.remove_file(&a).await, Err(FsError::Forbidden)));
+        assert!(matches!(fs.rename(&a, &b).await, Err(FsError::Forbidden)));
+        assert!(matches!(fs.copy(&a, &b).await, Err(FsError::Forbidden)));
+
+        // reads carry on, and every clone is paused
+        assert!(fs.metadata(&a).await.is_ok());
+        let clone = fs.clone();
+        assert!(matches!(
+            clone.create_dir(&new).await,
+            Err(FsError::Forbidden)
+        ));
 
-            file_to.flush().await?;
+        assert!(!clone.toggle_writes());
+        assert!(fs.open(&b, write).await.is_ok());
+        assert!(fs.remove_file(&a).await.is_ok());
+    }
 
+    #[test]
+    fn test_find_child_ignoring_case() {
+        let lower = DirEntry::File(file("readme.txt"));
+        let upper = DirEntry::File(file("README.TXT"));
+        let first_by_id = lower.id().min(upper.id());
+        let parent = with_children(folder("root"), vec![lower, upper]);
+
+        assert!(find_child(&parent, "Readme.txt", false).is_none());
+        assert_eq!(
+            find_child(&parent, "Readme.txt", true).unwrap().id(),
+            first_by_id
+        );
+
+        // an exact match wins over the ambiguity
+        assert_eq!(
+            find_child(&parent, "README.TXT", true).unwrap().name(),
+            "README.TXT"
+        );
+        assert_eq!(
+            check_file_name_conflict(&parent, "ReadMe.txt", true),
             Ok(())
-        }
-        .boxed()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_case_insensitive_cache_lookup() {
+        let fs = mounted_fs(&[ROOT_DIR]).case_insensitive(true);
+        let mount = &fs.mounts[ROOT_DIR];
+
+        let notes = file("Notes.txt");
+        let mut root = with_children(
+            folder("root"),
+            vec![
+                DirEntry::File(notes.clone()),
+                DirEntry::File(file("a.txt")),
+                DirEntry::File(file("A.txt")),
+            ],
+        );
+        fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+
+        // served from the cache, with its actual name
+        let path = DavPath::new("/NOTES.TXT").unwrap();
+        let Some(DirEntry::File(found)) = fs.search(&path).await.unwrap() else {
+            panic!("expected the cached file");
+        };
+        assert_eq!(found.id, notes.id);
+        assert_eq!(found.name, "Notes.txt");
+
+        // names differing only in case are left to the listing
+        assert!(mount.filecache.read().await.find_file("/a.txt").is_none());
+
+        fs.invalidate(&path).await.unwrap();
+        assert!(
+            mount
+                .filecache
+                .read()
+                .await
+                .find_file("/notes.txt")
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_in_trash() {
+        let id = Uuid::new_v4();
+        assert_eq!(TrashLocation::parse(&id.to_string()), TrashLocation::Id(id));
+        assert_eq!(
+            TrashLocation::parse(".trash"),
+            TrashLocation::Name(".trash".to_string())
+        );
+
+        let fs = mounted_fs(&[ROOT_DIR]);
+        let mount = &fs.mounts[ROOT_DIR];
+        let trash_entry = FolderEntry {
+            code: "trash-code".to_string(),
+            ..folder(".trash")
+        };
+        let docs = FolderEntry {
+            code: "docs-code".to_string(),
+            ..folder("docs")
+        };
+        let trash = TrashFolder {
+            id: trash_entry.id,
+            code: trash_entry.code.clone(),
+        };
+        let mut root = with_children(
+            folder("root"),
+            vec![DirEntry::Folder(trash_entry), DirEntry::Folder(docs)],
+        );
+        fs.cache_listing(mount, ROOT_DIR, &mut root).await;
+        mount
+            .dircache
+            .write()
+            .await
+            .insert_dir("/.trash/docs", "trashed-docs-code".to_string());
+
+        assert!(fs.in_trash(mount, "/.trash", &trash).await);
+        assert!(fs.in_trash(mount, "/.trash/docs/a.txt", &trash).await);
+        assert!(!fs.in_trash(mount, "/docs", &trash).await);
+        assert!(!fs.in_trash(mount, "/docs/a.txt", &trash).await);
     }
 }
@@ -1,23 +1,404 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    num::NonZeroUsize,
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
 
 pub const ROOT_DIR: &str = "";
 
+/// Saved mappings older than this are dropped on load, the folder may have moved since
+pub const SAVED_DIR_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// How long an expired file entry is kept for its id, past that it's pruned
+const EXPIRED_FILE_KEPT: Duration = Duration::from_secs(10 * 60);
+
+/// Path -> folder id/code mapping.
+///
+/// The root entry is stored outside of the LRU so it is never evicted.
+/// Other entries remember when they were inserted, see [`SavedDirCaches`].
 pub struct DirCache<T> {
-    cache: HashMap<String, T>,
+    root: T,
+    cache: LruCache<String, (SystemTime, T)>,
 }
 
 impl<T> DirCache<T> {
     pub fn new(root_id: T) -> Self {
-        let mut cache = HashMap::new();
-        cache.insert(ROOT_DIR.to_string(), root_id);
-        Self { cache }
+        Self {
+            root: root_id,
+            cache: LruCache::unbounded(),
+        }
     }
 
-    pub fn find_dir(&self, path: impl AsRef<str>) -> Option<&T> {
-        self.cache.get(path.as_ref())
+    /// Creates a cache holding at most `capacity` entries besides the root.
+    /// Once full, the least recently used entry is evicted.
+    pub fn with_capacity(root_id: T, capacity: NonZeroUsize) -> Self {
+        Self {
+            root: root_id,
+            cache: LruCache::new(capacity),
+        }
+    }
+
+    /// Looks up a path and marks it as recently used.
+    pub fn find_dir(&mut self, path: impl AsRef<str>) -> Option<&T> {
+        let path = path.as_ref();
+
+        if path == ROOT_DIR {
+            return Some(&self.root);
+        }
+
+        self.cache.get(path).map(|(_, value)| value)
     }
 
     pub fn insert_dir(&mut self, path: impl Into<String>, value: T) -> Option<T> {
-        self.cache.insert(path.into(), value)
+        let path = path.into();
+
+        if path == ROOT_DIR {
+            return Some(std::mem::replace(&mut self.root, value));
+        }
+
+        self.cache
+            .put(path, (SystemTime::now(), value))
+            .map(|(_, value)| value)
+    }
+
+    /// Number of cached entries besides the root.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Drops `path` and everything below it. The root entry is kept.
+    pub fn invalidate_prefix(&mut self, path: impl AsRef<str>) {
+        let path = path.as_ref();
+        let prefix = format!("{path}/");
+
+        let stale: Vec<String> = self
+            .cache
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| *key == path || key.starts_with(&prefix))
+            .cloned()
+            .collect();
+
+        for key in stale {
+            self.cache.pop(&key);
+        }
+    }
+}
+
+/// The [`DirCache`] of every mount, as kept on disk by `--cache-file` across restarts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedDirCaches {
+    mounts: BTreeMap<String, SavedDirCache>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedDirCache {
+    root: String,
+    // least recently used first, so restoring them in order keeps the LRU order
+    entries: Vec<SavedDir>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedDir {
+    path: String,
+    id: String,
+    // seconds since the Unix epoch
+    inserted: u64,
+}
+
+impl SavedDirCaches {
+    /// Reads the caches saved by [`Self::save_to`], none when the file doesn't exist yet.
+    pub fn load_from(path: impl AsRef<Path>) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes the caches to `path`, replacing the previous file only once complete.
+    pub fn save_to(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let part = path.with_extension("part");
+
+        fs::write(&part, serde_json::to_vec(self)?)?;
+        fs::rename(&part, path)
+    }
+
+    /// Keeps the entries of `cache`, the one of the mount named `mount`.
+    pub fn insert(&mut self, mount: &str, cache: &DirCache<String>) {
+        let entries = cache
+            .cache
+            .iter()
+            .rev()
+            .map(|(path, (inserted, id))| SavedDir {
+                path: path.clone(),
+                id: id.clone(),
+                inserted: inserted
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+            })
+            .collect();
+
+        let saved = SavedDirCache {
+            root: cache.root.clone(),
+            entries,
+        };
+        self.mounts.insert(mount.to_string(), saved);
+    }
+
+    /// Fills `cache` with the entries saved for `mount` that are younger than
+    /// [`SAVED_DIR_TTL`], returning how many. Nothing is restored when the mount now
+    /// serves another folder.
+    pub fn restore(&self, mount: &str, cache: &mut DirCache<String>) -> usize {
+        let Some(saved) = self
+            .mounts
+            .get(mount)
+            .filter(|saved| saved.root == cache.root)
+        else {
+            return 0;
+        };
+
+        let now = SystemTime::now();
+        let mut restored = 0;
+        for entry in &saved.entries {
+            let inserted = UNIX_EPOCH + Duration::from_secs(entry.inserted);
+            if now.duration_since(inserted).unwrap_or_default() >= SAVED_DIR_TTL {
+                continue;
+            }
+
+            cache
+                .cache
+                .put(entry.path.clone(), (inserted, entry.id.clone()));
+            restored += 1;
+        }
+
+        restored.min(cache.len())
+    }
+}
+
+/// Path -> file metadata mapping.
+///
+/// Entries expire after `ttl` so changes made outside of this server are eventually picked up.
+/// Expired entries are kept a while longer for [`Self::find_expired`].
+pub struct FileCache<T> {
+    ttl: Duration,
+    keep_expired: Duration,
+    cache: HashMap<String, (Instant, T)>,
+}
+
+impl<T: Clone> FileCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            keep_expired: EXPIRED_FILE_KEPT,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn find_file(&self, path: impl AsRef<str>) -> Option<T> {
+        self.cache
+            .get(path.as_ref())
+            .filter(|(inserted, _)| inserted.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// The entry at `path` once it expired, still good for its id.
+    pub fn find_expired(&self, path: impl AsRef<str>) -> Option<T> {
+        self.cache
+            .get(path.as_ref())
+            .filter(|(inserted, _)| inserted.elapsed() >= self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    pub fn insert_file(&mut self, path: impl Into<String>, value: T) {
+        self.cache.insert(path.into(), (Instant::now(), value));
+    }
+
+    /// Drops the entries directly under `parent`, and the ones expired for longer than
+    /// they are kept. Called before a fresh listing of `parent` is inserted.
+    pub fn invalidate_children(&mut self, parent: impl AsRef<str>) {
+        let prefix = format!("{}/", parent.as_ref());
+        let kept = self.ttl + self.keep_expired;
+
+        self.cache.retain(|path, (inserted, _)| {
+            let is_child = path
+                .strip_prefix(&prefix)
+                .is_some_and(|name| !name.contains('/'));
+
+            !is_child && inserted.elapsed() < kept
+        });
+    }
+
+    /// Drops `path` and everything below it.
+    pub fn invalidate_prefix(&mut self, path: impl AsRef<str>) {
+        let path = path.as_ref();
+        let prefix = format!("{path}/");
+
+        self.cache
+            .retain(|key, _| key != path && !key.starts_with(&prefix));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_keeps_hot_paths() {
+        let mut cache = DirCache::with_capacity("root".to_string(), NonZeroUsize::new(2).unwrap());
+
+        cache.insert_dir("/hot", "hot".to_string());
+        cache.insert_dir("/cold", "cold".to_string());
+
+        // bump recency of "/hot" so "/cold" becomes the eviction candidate
+        assert_eq!(cache.find_dir("/hot").map(String::as_str), Some("hot"));
+
+        cache.insert_dir("/new", "new".to_string());
+
+        assert_eq!(cache.find_dir("/hot").map(String::as_str), Some("hot"));
+        assert_eq!(cache.find_dir("/new").map(String::as_str), Some("new"));
+        assert!(cache.find_dir("/cold").is_none());
+    }
+
+    #[test]
+    fn test_lru_never_evicts_root() {
+        let mut cache = DirCache::with_capacity("root".to_string(), NonZeroUsize::MIN);
+
+        for i in 0..10 {
+            cache.insert_dir(format!("/dir{i}"), i.to_string());
+        }
+
+        assert_eq!(cache.find_dir(ROOT_DIR).map(String::as_str), Some("root"));
+        assert_eq!(cache.find_dir("/dir9").map(String::as_str), Some("9"));
+        assert!(cache.find_dir("/dir8").is_none());
+    }
+
+    #[test]
+    fn test_unbounded_cache() {
+        let mut cache = DirCache::new("root".to_string());
+
+        for i in 0..1000 {
+            cache.insert_dir(format!("/dir{i}"), i.to_string());
+        }
+
+        assert_eq!(cache.find_dir("/dir0").map(String::as_str), Some("0"));
+    }
+
+    #[test]
+    fn test_dir_cache_invalidate_prefix() {
+        let mut cache = DirCache::new("root".to_string());
+        cache.insert_dir("/a", "a".to_string());
+        cache.insert_dir("/a/b", "b".to_string());
+        cache.insert_dir("/ab", "ab".to_string());
+
+        cache.invalidate_prefix("/a");
+
+        assert!(cache.find_dir("/a").is_none());
+        assert!(cache.find_dir("/a/b").is_none());
+        assert_eq!(cache.find_dir("/ab").map(String::as_str), Some("ab"));
+        assert_eq!(cache.find_dir(ROOT_DIR).map(String::as_str), Some("root"));
+    }
+
+    #[test]
+    fn test_saved_dir_caches() {
+        let path =
+            std::env::temp_dir().join(format!("gofile-dav-{}-dircache.json", std::process::id()));
+
+        let mut cache = DirCache::new("root".to_string());
+        cache.insert_dir("/a", "a".to_string());
+        cache.insert_dir("/a/b", "b".to_string());
+        cache.cache.put(
+            "/old".to_string(),
+            (SystemTime::now() - SAVED_DIR_TTL, "old".to_string()),
+        );
+
+        let mut saved = SavedDirCaches::default();
+        saved.insert("media", &cache);
+        saved.save_to(&path).unwrap();
+
+        let saved = SavedDirCaches::load_from(&path).unwrap();
+        let mut restored = DirCache::new("root".to_string());
+        assert_eq!(saved.restore("media", &mut restored), 2);
+        assert_eq!(restored.find_dir("/a/b").map(String::as_str), Some("b"));
+        assert!(restored.find_dir("/old").is_none());
+
+        // another mount, or the same name now serving another folder
+        assert_eq!(
+            saved.restore("docs", &mut DirCache::new("root".to_string())),
+            0
+        );
+        assert_eq!(
+            saved.restore("media", &mut DirCache::new("other".to_string())),
+            0
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        let missing = SavedDirCaches::load_from(&path).unwrap();
+        assert_eq!(
+            missing.restore("media", &mut DirCache::new("root".to_string())),
+            0
+        );
+    }
+
+    #[test]
+    fn test_file_cache_expires() {
+        let mut cache = FileCache::new(Duration::ZERO);
+        cache.insert_file("/a.txt", 1);
+
+        assert_eq!(cache.find_file("/a.txt"), None);
+        assert_eq!(cache.find_expired("/a.txt"), Some(1));
+
+        // relisting another folder keeps it
+        cache.invalidate_children("/dir");
+        assert_eq!(cache.find_expired("/a.txt"), Some(1));
+        cache.invalidate_children("");
+        assert_eq!(cache.find_expired("/a.txt"), None);
+
+        let mut cache = FileCache::new(Duration::from_secs(60));
+        cache.insert_file("/a.txt", 1);
+        assert_eq!(cache.find_expired("/a.txt"), None);
+
+        // not kept forever, listing any folder prunes it
+        let mut cache = FileCache::new(Duration::ZERO);
+        cache.keep_expired = Duration::ZERO;
+        cache.insert_file("/dir/a.txt", 1);
+        cache.invalidate_children("/other");
+        assert_eq!(cache.find_expired("/dir/a.txt"), None);
+        assert!(cache.cache.is_empty());
+    }
+
+    #[test]
+    fn test_file_cache_invalidate_children() {
+        let mut cache = FileCache::new(Duration::from_secs(60));
+        cache.insert_file("/dir/a.txt", 1);
+        cache.insert_file("/dir/sub/b.txt", 2);
+        cache.insert_file("/dir2/c.txt", 3);
+
+        cache.invalidate_children("/dir");
+
+        assert_eq!(cache.find_file("/dir/a.txt"), None);
+        assert_eq!(cache.find_file("/dir/sub/b.txt"), Some(2));
+        assert_eq!(cache.find_file("/dir2/c.txt"), Some(3));
+    }
+
+    #[test]
+    fn test_file_cache_invalidate_prefix() {
+        let mut cache = FileCache::new(Duration::from_secs(60));
+        cache.insert_file("/dir/a.txt", 1);
+        cache.insert_file("/dir/sub/b.txt", 2);
+        cache.insert_file("/dir2/c.txt", 3);
+
+        cache.invalidate_prefix("/dir");
+
+        assert_eq!(cache.find_file("/dir/a.txt"), None);
+        assert_eq!(cache.find_file("/dir/sub/b.txt"), None);
+        assert_eq!(cache.find_file("/dir2/c.txt"), Some(3));
     }
 }
@@ -0,0 +1,241 @@
+use std::{
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bytes::Bytes;
+use log::{debug, warn};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+use uuid::Uuid;
+
+const PART_EXTENSION: &str = "part";
+
+/// On-disk cache of downloaded files, keyed by their md5.
+///
+/// Only complete files are served from the cache. Once the cache grows past `max_size`
+/// the least recently used files are evicted.
+#[derive(Debug)]
+pub struct DownloadCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl DownloadCache {
+    pub fn new(dir: impl Into<PathBuf>, max_size: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(Self { dir, max_size })
+    }
+
+    /// Opens the cached copy of a file, `None` if it isn't fully cached.
+    pub async fn open(&self, md5: &str, size: u64) -> Option<CachedFile> {
+        let path = self.path(md5)?;
+        let file = File::open(&path).await.ok()?;
+
+        if file.metadata().await.ok()?.len() != size {
+            return None;
+        }
+
+        // the modification time doubles as the last access time for eviction
+        let std_file = file.try_clone().await.ok()?.into_std().await;
+        if let Err(e) = std_file.set_modified(SystemTime::now()) {
+            debug!("failed to touch cached file {}: {e}", path.display());
+        }
+
+        Some(CachedFile { file })
+    }
+
+    /// Starts caching a download of the file with the given md5, read from its first byte.
+    pub async fn writer(&self, md5: &str) -> Option<CacheWriter> {
+        let dest = self.path(md5)?;
+        // unique per download so concurrent reads of the same file don't interleave
+        let part = dest.with_extension(format!("{}.{PART_EXTENSION}", Uuid::new_v4()));
+
+        match File::create(&part).await {
+            Ok(file) => Some(CacheWriter { file, part, dest }),
+            Err(e) => {
+                warn!("failed to create cache file {}: {e}", part.display());
+                None
+            }
+        }
+    }
+
+    fn path(&self, md5: &str) -> Option<PathBuf> {
+        // md5 comes from the API, make sure it can't escape the cache directory
+        let valid = !md5.is_empty() && md5.chars().all(|c| c.is_ascii_hexdigit());
+
+        valid.then(|| self.dir.join(md5.to_ascii_lowercase()))
+    }
+
+    /// Removes the least recently used files until the cache fits in `max_size`.
+    async fn evict(&self) -> io::Result<()> {
+        let mut files = Vec::new();
+        let mut total_size = 0;
+
+        let mut entries = fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if is_part_file(&path) {
+                continue;
+            }
+
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+
+            total_size += metadata.len();
+            files.push((metadata.modified()?, metadata.len(), path));
+        }
+
+        files.sort();
+
+        for (_, size, path) in files {
+            if total_size <= self.max_size {
+                break;
+            }
+
+            fs::remove_file(&path).await?;
+            total_size -= size;
+            debug!("evicted {} from the download cache", path.display());
+        }
+
+        Ok(())
+    }
+}
+
+fn is_part_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == PART_EXTENSION)
+}
+
+/// A complete file served from the [`DownloadCache`]
+#[derive(Debug)]
+pub struct CachedFile {
+    file: File,
+}
+
+impl CachedFile {
+    pub async fn read_at(&mut self, position: u64, count: usize) -> io::Result<Bytes> {
+        self.file.seek(SeekFrom::Start(position)).await?;
+
+        let mut buf = Vec::with_capacity(count);
+        (&mut self.file)
+            .take(count as u64)
+            .read_to_end(&mut buf)
+            .await?;
+
+        Ok(buf.into())
+    }
+}
+
+/// Writes a download into the [`DownloadCache`]. The partial file is removed if the
+/// writer is dropped before [`CacheWriter::finish`].
+#[derive(Debug)]
+pub struct CacheWriter {
+    file: File,
+    part: PathBuf,
+    dest: PathBuf,
+}
+
+impl CacheWriter {
+    pub async fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.file.write_all(bytes).await
+    }
+
+    /// Moves the complete file into place and evicts old files if needed.
+    pub async fn finish(mut self, cache: &DownloadCache) -> io::Result<()> {
+        self.file.flush().await?;
+        fs::rename(&self.part, &self.dest).await?;
+
+        cache.evict().await
+    }
+}
+
+impl Drop for CacheWriter {
+    fn drop(&mut self) {
+        // a no-op once `finish` renamed it
+        let _ = std::fs::remove_file(&self.part);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MD5_A: &str = "0cc175b9c0f1b6a831c399e269772661";
+    const MD5_B: &str = "92eb5ffee6ae2fec3ad71c777531578f";
+
+    fn temp_cache(name: &str, max_size: u64) -> DownloadCache {
+        let dir = std::env::temp_dir().join(format!("gofile-dav-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        DownloadCache::new(dir, max_size).unwrap()
+    }
+
+    async fn store(cache: &DownloadCache, md5: &str, data: &[u8]) {
+        let mut writer = cache.writer(md5).await.unwrap();
+        writer.write(data).await.unwrap();
+        writer.finish(cache).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cache_roundtrip() {
+        let cache = temp_cache("roundtrip", 1024);
+
+        assert!(cache.open(MD5_A, 10).await.is_none());
+
+        store(&cache, MD5_A, b"0123456789").await;
+
+        let mut file = cache.open(MD5_A, 10).await.unwrap();
+        assert_eq!(file.read_at(4, 3).await.unwrap(), &b"456"[..]);
+        assert_eq!(file.read_at(8, 10).await.unwrap(), &b"89"[..]);
+
+        // a size mismatch means the file on Gofile changed
+        assert!(cache.open(MD5_A, 11).await.is_none());
+
+        std::fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unfinished_download_is_not_cached() {
+        let cache = temp_cache("unfinished", 1024);
+
+        let mut writer = cache.writer(MD5_A).await.unwrap();
+        writer.write(b"01234").await.unwrap();
+        drop(writer);
+
+        assert!(cache.open(MD5_A, 5).await.is_none());
+        assert_eq!(std::fs::read_dir(&cache.dir).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cache_evicts_least_recently_used() {
+        let cache = temp_cache("evict", 15);
+
+        store(&cache, MD5_A, b"0123456789").await;
+        store(&cache, MD5_B, b"0123456789").await;
+
+        assert!(cache.open(MD5_A, 10).await.is_none());
+        assert!(cache.open(MD5_B, 10).await.is_some());
+
+        std::fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn test_md5_cannot_escape_cache_dir() {
+        let cache = temp_cache("escape", 0);
+
+        assert!(cache.path("../../etc/passwd").is_none());
+        assert!(cache.path("").is_none());
+        assert!(cache.path(MD5_A).is_some());
+
+        std::fs::remove_dir_all(&cache.dir).unwrap();
+    }
+}
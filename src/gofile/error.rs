@@ -1,4 +1,7 @@
+use std::{sync::Arc, time::Duration};
+
 use thiserror::Error;
+use uuid::Uuid;
 
 pub type GofileResult<T> = std::result::Result<T, GofileError>;
 
@@ -18,6 +21,8 @@ pub enum GofileError {
     },
     #[error("API returned an error: {status}")]
     Api { status: String },
+    #[error("the account token was rejected by gofile.io")]
+    InvalidToken,
     #[error("password required")]
     PasswordRequired,
     #[error("password wrong")]
@@ -26,8 +31,31 @@ pub enum GofileError {
     NotFound,
     #[error("forbidden")]
     Forbidden,
+    #[error("gofile.io is under maintenance")]
+    Maintenance,
+    #[error("the gofile.io account is out of storage ({status})")]
+    QuotaExceeded { status: String },
+    #[error("bypass unavailable for {id}: {reason}")]
+    BypassUnavailable { id: Uuid, reason: &'static str },
+    #[error("gofile.io failed to delete {}", .failed.join(", "))]
+    DeleteFailed { failed: Vec<String> },
+    #[error("rate limited by gofile.io")]
+    RateLimited { retry_after: Option<Duration> },
     #[error(transparent)]
     Unexpected(#[from] anyhow::Error),
+    /// The error of a request several callers waited for
+    #[error(transparent)]
+    Shared(Arc<GofileError>),
+}
+
+impl GofileError {
+    /// The error itself, looking through [`GofileError::Shared`]
+    pub fn unshared(&self) -> &GofileError {
+        match self {
+            GofileError::Shared(error) => error.unshared(),
+            error => error,
+        }
+    }
 }
 
 impl From<reqwest::Error> for GofileError {
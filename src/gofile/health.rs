@@ -0,0 +1,55 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use super::error::{GofileError, GofileResult};
+
+/// Whether gofile.io can be reached, shared by the clones of a
+/// [`Client`](super::Client) and the readiness probe
+#[derive(Clone, Default)]
+pub struct Health {
+    started: Arc<AtomicBool>,
+    last_call_failed: Arc<AtomicBool>,
+}
+
+impl Health {
+    /// Records that the startup checks against gofile.io succeeded
+    pub fn set_started(&self) {
+        self.started.store(true, Ordering::Relaxed);
+    }
+
+    /// Records the outcome of a gofile.io API call
+    pub fn report<T>(&self, result: &GofileResult<T>) {
+        let failed = result.as_ref().is_err_and(is_upstream_failure);
+        self.last_call_failed.store(failed, Ordering::Relaxed);
+    }
+
+    /// Whether startup finished and the last gofile.io API call didn't fail
+    pub fn is_ready(&self) -> bool {
+        self.started.load(Ordering::Relaxed) && !self.last_call_failed.load(Ordering::Relaxed)
+    }
+}
+
+/// Errors meaning gofile.io couldn't be used, as opposed to it rejecting a request
+fn is_upstream_failure(error: &GofileError) -> bool {
+    matches!(
+        error,
+        GofileError::Http { .. } | GofileError::Maintenance | GofileError::RateLimited { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upstream_failure() {
+        assert!(is_upstream_failure(&GofileError::Maintenance));
+        assert!(is_upstream_failure(&GofileError::RateLimited {
+            retry_after: None
+        }));
+        assert!(!is_upstream_failure(&GofileError::NotFound));
+        assert!(!is_upstream_failure(&GofileError::InvalidToken));
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use futures_util::{
+    FutureExt,
+    future::{BoxFuture, Shared},
+};
+
+use super::error::{GofileError, GofileResult};
+
+type SharedRequest<T> = Shared<BoxFuture<'static, Result<T, Arc<GofileError>>>>;
+// Each request has an id of its own, so a request forgotten by `forget_all` never
+// removes the one started after it for the same key
+type Requests<T> = Arc<Mutex<HashMap<String, (u64, SharedRequest<T>)>>>;
+
+/// Requests to Gofile running on behalf of every caller asking for the same key.
+///
+/// A caller asking for a key already being fetched waits for that request instead of
+/// sending its own, and gets a copy of its result. The key is forgotten once the caller
+/// that started the request is done with it, completed or cancelled, so later callers
+/// fetch again.
+pub struct InFlight<T> {
+    requests: Requests<T>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl<T> Default for InFlight<T> {
+    fn default() -> Self {
+        Self {
+            requests: Arc::default(),
+            next_id: Arc::default(),
+        }
+    }
+}
+
+impl<T> Clone for InFlight<T> {
+    fn clone(&self) -> Self {
+        Self {
+            requests: self.requests.clone(),
+            next_id: self.next_id.clone(),
+        }
+    }
+}
+
+/// Forgets the request of the caller that started it once that caller is done with it
+struct Started<T> {
+    requests: Requests<T>,
+    key: String,
+    id: u64,
+}
+
+impl<T> Drop for Started<T> {
+    fn drop(&mut self) {
+        let mut requests = self.requests.lock().unwrap();
+        if requests
+            .get(&self.key)
+            .is_some_and(|(id, _)| *id == self.id)
+        {
+            requests.remove(&self.key);
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> InFlight<T> {
+    /// Runs `fetch` unless a request for `key` is running already, then waits for it.
+    ///
+    /// Errors reach every caller. Callers that shared a failed request get it as
+    /// [`GofileError::Shared`], a caller alone with it gets the error as it was.
+    pub async fn run<F>(&self, key: String, fetch: F) -> GofileResult<T>
+    where
+        F: Future<Output = GofileResult<T>> + Send + 'static,
+    {
+        let (request, _started) = {
+            // never held across an await
+            let mut requests = self.requests.lock().unwrap();
+            match requests.get(&key) {
+                Some((_, request)) => (request.clone(), None),
+                None => {
+                    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                    let request = async move { fetch.await.map_err(Arc::new) }
+                        .boxed()
+                        .shared();
+                    requests.insert(key.clone(), (id, request.clone()));
+
+                    let started = Started {
+                        requests: self.requests.clone(),
+                        key,
+                        id,
+                    };
+                    (request, Some(started))
+                }
+            }
+        };
+
+        request
+            .await
+            .map_err(|e| Arc::try_unwrap(e).unwrap_or_else(GofileError::Shared))
+    }
+
+    /// Forgets every running request, which may have been sent before a change their
+    /// result wouldn't show. Their callers still get them, later callers fetch again.
+    pub fn forget_all(&self) {
+        self.requests.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrent_callers_share_one_request() {
+        let in_flight = InFlight::default();
+        let sent = Arc::new(AtomicUsize::new(0));
+
+        let fetch = |result: GofileResult<u32>| {
+            let sent = sent.clone();
+            async move {
+                sent.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                result
+            }
+        };
+
+        let results = futures_util::future::join_all(
+            (0..5).map(|_| in_flight.run("root".to_string(), fetch(Ok(7)))),
+        )
+        .await;
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|result| matches!(result, Ok(7))));
+
+        // done, the next caller fetches again
+        assert_eq!(
+            in_flight
+                .run("root".to_string(), fetch(Ok(8)))
+                .await
+                .unwrap(),
+            8
+        );
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+
+        // the error reaches every caller
+        let results = futures_util::future::join_all(
+            (0..3).map(|_| in_flight.run("root".to_string(), fetch(Err(GofileError::NotFound)))),
+        )
+        .await;
+        assert_eq!(sent.load(Ordering::SeqCst), 3);
+        for result in results {
+            let error = result.unwrap_err();
+            assert!(matches!(error.unshared(), GofileError::NotFound));
+        }
+        assert!(in_flight.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_forgotten_requests_are_not_joined() {
+        let in_flight = InFlight::default();
+        let sent = Arc::new(AtomicUsize::new(0));
+        let fetch = |value: u32, delay: u64| {
+            let sent = sent.clone();
+            async move {
+                sent.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                Ok(value)
+            }
+        };
+
+        // started before a change, forgotten after it
+        let stale = tokio::spawn({
+            let in_flight = in_flight.clone();
+            let request = fetch(1, 50);
+            async move { in_flight.run("root".to_string(), request).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        in_flight.forget_all();
+
+        let fresh = tokio::spawn({
+            let in_flight = in_flight.clone();
+            let request = fetch(2, 100);
+            async move { in_flight.run("root".to_string(), request).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(stale.await.unwrap().unwrap(), 1);
+
+        // the stale request leaves the fresh one in place for the next caller
+        assert_eq!(
+            in_flight
+                .run("root".to_string(), fetch(3, 0))
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(fresh.await.unwrap().unwrap(), 2);
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_is_forgotten() {
+        let in_flight = InFlight::default();
+
+        let pending = in_flight.run(
+            "root".to_string(),
+            std::future::pending::<GofileResult<u32>>(),
+        );
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), pending)
+                .await
+                .is_err()
+        );
+
+        // the next caller doesn't resume the abandoned request
+        assert!(in_flight.requests.lock().unwrap().is_empty());
+        assert_eq!(
+            in_flight
+                .run("root".to_string(), async { Ok(7) })
+                .await
+                .unwrap(),
+            7
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::error;
+use reqwest::StatusCode;
+use reqwest_middleware::Error;
+use reqwest_retry::{Retryable, RetryableStrategy, default_on_request_failure};
+
+/// How long gofile.io is assumed to stay in maintenance after it was last reported,
+/// also sent to WebDAV clients as `Retry-After`
+pub const MAINTENANCE_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+// Maintenance is a property of the upstream service, so a single process-wide state is enough
+static LAST_REPORTED: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Records that gofile.io answered with a maintenance response
+pub fn report() {
+    let mut last_reported = LAST_REPORTED.lock().unwrap_or_else(|e| e.into_inner());
+
+    if !last_reported.is_some_and(|at| at.elapsed() < MAINTENANCE_RETRY_AFTER) {
+        error!("gofile.io is under maintenance, requests will fail until it is over");
+    }
+
+    *last_reported = Some(Instant::now());
+}
+
+/// Whether a maintenance response was seen within [`MAINTENANCE_RETRY_AFTER`]
+pub fn is_active() -> bool {
+    LAST_REPORTED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .is_some_and(|at| at.elapsed() < MAINTENANCE_RETRY_AFTER)
+}
+
+/// Detects a maintenance response by its status or an HTML page announcing it
+pub fn is_maintenance_response(status: StatusCode, body: &[u8]) -> bool {
+    if status == StatusCode::SERVICE_UNAVAILABLE {
+        return true;
+    }
+
+    let body = String::from_utf8_lossy(body);
+    let body = body.trim_start();
+
+    body.starts_with('<') && body.to_ascii_lowercase().contains("maintenance")
+}
+
+/// Leaves `503 Service Unavailable` to [`MaintenanceStrategy`], otherwise retries like the default
+pub struct TransientStrategy;
+
+impl RetryableStrategy for TransientStrategy {
+    fn handle(&self, res: &Result<reqwest::Response, Error>) -> Option<Retryable> {
+        match res {
+            Ok(response) if response.status() == StatusCode::SERVICE_UNAVAILABLE => None,
+            Ok(response) => reqwest_retry::default_on_request_success(response),
+            Err(error) => default_on_request_failure(error),
+        }
+    }
+}
+
+/// Only retries `503 Service Unavailable`, meant to be paired with a slower backoff
+pub struct MaintenanceStrategy;
+
+impl RetryableStrategy for MaintenanceStrategy {
+    fn handle(&self, res: &Result<reqwest::Response, Error>) -> Option<Retryable> {
+        match res {
+            Ok(response) if response.status() == StatusCode::SERVICE_UNAVAILABLE => {
+                Some(Retryable::Transient)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maintenance_response() {
+        let page = b"<!DOCTYPE html><html><body><h1>Gofile is under Maintenance</h1></body></html>";
+
+        assert!(is_maintenance_response(StatusCode::OK, page));
+        assert!(is_maintenance_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            b""
+        ));
+        assert!(!is_maintenance_response(
+            StatusCode::OK,
+            br#"{"status":"ok","data":{"name":"maintenance.txt"}}"#
+        ));
+    }
+}
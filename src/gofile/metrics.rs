@@ -0,0 +1,130 @@
+use std::{sync::LazyLock, time::Duration};
+
+use prometheus::{
+    HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder, register_histogram_vec,
+    register_int_counter_vec, register_int_gauge_vec,
+};
+
+use super::error::{GofileError, GofileResult};
+
+// Only exported when `--metrics` is given, but always recorded: updating them is cheap
+static DAV_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "gofile_dav_requests_total",
+        "WebDAV requests handled, by method",
+        &["method"]
+    )
+    .unwrap()
+});
+
+static DAV_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "gofile_dav_request_duration_seconds",
+        "Time until the response to a WebDAV request started, by method",
+        &["method"]
+    )
+    .unwrap()
+});
+
+static API_CALLS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "gofile_dav_api_calls_total",
+        "Calls to the gofile.io API, by outcome",
+        &["outcome"]
+    )
+    .unwrap()
+});
+
+static DIR_CACHE_ENTRIES: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "gofile_dav_dir_cache_entries",
+        "Folder paths cached per mount, besides its root",
+        &["mount"]
+    )
+    .unwrap()
+});
+
+/// Methods counted under their own name, anything else is counted as `OTHER`
+const DAV_METHODS: &[&str] = &[
+    "GET",
+    "HEAD",
+    "OPTIONS",
+    "PROPFIND",
+    "PROPPATCH",
+    "PUT",
+    "PATCH",
+    "DELETE",
+    "MKCOL",
+    "COPY",
+    "MOVE",
+    "LOCK",
+    "UNLOCK",
+];
+
+/// Records a WebDAV request and how long its handler took
+pub fn observe_dav_request(method: &str, elapsed: Duration) {
+    let method = DAV_METHODS
+        .iter()
+        .find(|m| **m == method)
+        .copied()
+        .unwrap_or("OTHER");
+
+    DAV_REQUESTS.with_label_values(&[method]).inc();
+    DAV_REQUEST_DURATION
+        .with_label_values(&[method])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Records the outcome of a single gofile.io API call
+pub fn observe_api_call<T>(result: &GofileResult<T>) {
+    API_CALLS.with_label_values(&[api_outcome(result)]).inc();
+}
+
+fn api_outcome<T>(result: &GofileResult<T>) -> &'static str {
+    match result {
+        Ok(_) => "ok",
+        Err(GofileError::NotFound) => "notFound",
+        Err(GofileError::RateLimited { .. }) => "rateLimit",
+        Err(_) => "error",
+    }
+}
+
+/// Gauge tracking the size of the folder cache of the mount named `mount`
+pub fn dir_cache_entries(mount: &str) -> IntGauge {
+    DIR_CACHE_ENTRIES.with_label_values(&[mount])
+}
+
+/// Renders every metric in the Prometheus text format
+pub fn render() -> String {
+    // only fails on a broken metric definition
+    TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .expect("metrics should encode")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_outcome() {
+        assert_eq!(api_outcome(&Ok(())), "ok");
+        assert_eq!(api_outcome::<()>(&Err(GofileError::NotFound)), "notFound");
+        assert_eq!(
+            api_outcome::<()>(&Err(GofileError::RateLimited { retry_after: None })),
+            "rateLimit"
+        );
+        assert_eq!(api_outcome::<()>(&Err(GofileError::Forbidden)), "error");
+    }
+
+    #[test]
+    fn test_render() {
+        observe_dav_request("PROPFIND", Duration::from_millis(5));
+        observe_dav_request("BREW", Duration::from_millis(5));
+
+        let text = render();
+        assert!(text.contains(r#"gofile_dav_requests_total{method="PROPFIND"}"#));
+        assert!(text.contains(r#"gofile_dav_requests_total{method="OTHER"}"#));
+        assert!(text.contains("gofile_dav_request_duration_seconds_bucket"));
+    }
+}
@@ -1,10 +1,21 @@
 mod client;
 mod dav;
 mod dircache;
+mod download_cache;
 pub mod error;
+pub mod health;
+mod in_flight;
+pub mod maintenance;
+pub mod metrics;
 pub mod model;
+mod progress;
+#[cfg(test)]
+pub mod testing;
+mod throttle;
+mod virtual_file;
 mod wt_generator;
 
 pub use client::Client;
-pub use dav::DavFs;
-pub use dircache::DirCache;
+pub use dav::{DavFs, FrozenStrategy, PendingWrites, report_timeouts};
+pub use dircache::{DirCache, SavedDirCaches};
+pub use download_cache::DownloadCache;
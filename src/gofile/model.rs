@@ -1,5 +1,7 @@
 use std::{collections::HashMap, str::FromStr};
 
+use anyhow::{Context, anyhow};
+
 use super::error::{GofileError, GofileResult};
 use serde::{Deserialize, Deserializer, Serialize, de};
 use url::Url;
@@ -31,6 +33,41 @@ impl FromStr for IdOrCode {
     }
 }
 
+// Hosts of the share URLs `IdOrCode::from_url_or_id` recognizes
+const GOFILE_HOSTS: &[&str] = &["gofile.io", "www.gofile.io"];
+
+impl IdOrCode {
+    /// Parses an id or code, or the URL of a folder as copied from gofile.io:
+    /// `https://gofile.io/d/<code>` or `https://gofile.io/folder/<id>`, the scheme optional.
+    pub fn from_url_or_id(s: &str) -> GofileResult<Self> {
+        let s = s.trim();
+        if !s.contains("://") && !s.contains('/') {
+            return Ok(s.into());
+        }
+
+        let url = if s.contains("://") {
+            Url::parse(s)
+        } else {
+            Url::parse(&format!("https://{s}"))
+        }
+        .with_context(|| format!("invalid Gofile URL {s:?}"))?;
+
+        let segments: Vec<_> = url
+            .path_segments()
+            .into_iter()
+            .flatten()
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        match (url.host_str(), segments.as_slice()) {
+            (Some(host), ["d" | "folder", id]) if GOFILE_HOSTS.contains(&host) => Ok((*id).into()),
+            _ => Err(
+                anyhow!("unsupported Gofile URL {s:?}, expected https://gofile.io/d/<code>").into(),
+            ),
+        }
+    }
+}
+
 impl From<Uuid> for IdOrCode {
     fn from(uuid: Uuid) -> Self {
         IdOrCode::Uuid4 { uuid }
@@ -52,28 +89,31 @@ impl From<&str> for IdOrCode {
 /// Top-level response
 #[derive(Debug, Serialize)]
 pub enum ApiResponse<T = serde_json::Value> {
-    Ok { data: T },
+    Ok { data: T, metadata: Metadata },
     NotFound,
     RateLimit,
     InvalidToken,
     NotPremium,
+    QuotaExceeded { status: String },
     Other { status: String },
 }
 
 impl<T> ApiResponse<T> {
     pub fn into_result(self) -> GofileResult<T> {
+        self.into_paged_result().map(|(data, _)| data)
+    }
+
+    /// Like [`ApiResponse::into_result`], keeping the paging metadata
+    pub fn into_paged_result(self) -> GofileResult<(T, Metadata)> {
         match self {
-            ApiResponse::Ok { data } => Ok(data),
+            ApiResponse::Ok { data, metadata } => Ok((data, metadata)),
             ApiResponse::NotFound => Err(GofileError::NotFound),
-            ApiResponse::RateLimit => Err(GofileError::Api {
-                status: "error-rateLimit".into(),
-            }),
-            ApiResponse::InvalidToken => Err(GofileError::Api {
-                status: "error-token".into(),
-            }),
+            ApiResponse::RateLimit => Err(GofileError::RateLimited { retry_after: None }),
+            ApiResponse::InvalidToken => Err(GofileError::InvalidToken),
             ApiResponse::NotPremium => Err(GofileError::Api {
                 status: "error-notPremium".into(),
             }),
+            ApiResponse::QuotaExceeded { status } => Err(GofileError::QuotaExceeded { status }),
             ApiResponse::Other { status } => Err(GofileError::Api { status }),
         }
     }
@@ -104,20 +144,35 @@ where
                     .ok_or_else(|| de::Error::missing_field("data"))?;
 
                 let data = T::deserialize(data_value).map_err(de::Error::custom)?;
+                // absent from most endpoints and from the bypass service
+                let metadata = map
+                    .remove("metadata")
+                    .and_then(|value| Metadata::deserialize(value).ok())
+                    .unwrap_or_default();
 
-                Ok(ApiResponse::Ok { data })
+                Ok(ApiResponse::Ok { data, metadata })
             }
 
             "error-notFound" => Ok(ApiResponse::NotFound),
             "error-rateLimit" => Ok(ApiResponse::RateLimit),
             "error-token" => Ok(ApiResponse::InvalidToken),
             "error-notPremium" => Ok(ApiResponse::NotPremium),
+            _ if is_quota_status(&status) => Ok(ApiResponse::QuotaExceeded { status }),
 
             _ => Ok(ApiResponse::Other { status }),
         }
     }
 }
 
+/// Whether an error status tells the account ran out of space or hit a limit of its tier,
+/// e.g. `error-storageLimit` or `error-quotaExceeded`
+fn is_quota_status(status: &str) -> bool {
+    let status = status.to_ascii_lowercase();
+    ["quota", "storage", "space", "tier"]
+        .iter()
+        .any(|word| status.contains(word))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Contents {
@@ -150,7 +205,7 @@ impl Contents {
     pub fn size(&self) -> u64 {
         match self {
             Self::File(file_entry) => file_entry.size,
-            Self::Folder(folder_entry) => folder_entry.total_size,
+            Self::Folder(folder_entry) => folder_entry.size(),
         }
     }
 
@@ -186,6 +241,20 @@ pub struct AccountInfo {
     pub tier: String,
     pub token: String,
     pub email: String,
+    #[serde(default)]
+    pub stats_current: AccountStats,
+    /// Bytes the tier of the account can store, unlimited if not set
+    #[serde(default)]
+    pub subscription_limit_storage: Option<u64>,
+}
+
+/// Usage of an account so far
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountStats {
+    /// Bytes stored
+    #[serde(default)]
+    pub storage: u64,
 }
 
 pub type CreateGuestAccountResponse = ApiResponse<CreateGuestAccount>;
@@ -406,6 +475,30 @@ pub struct FolderEntryOk {
     pub children: HashMap<Uuid, ContentsWithPassword>,
 }
 
+impl FolderEntry {
+    /// `total_size` as reported by Gofile. Some responses report 0 for folders that aren't
+    /// empty, the sizes of the loaded children are summed up instead then. Only the
+    /// children listed with the folder count, not what lies deeper.
+    pub fn size(&self) -> u64 {
+        if self.total_size != 0 {
+            return self.total_size;
+        }
+
+        self.children.values().map(Contents::size).sum()
+    }
+
+    /// Why the bypass service can't serve the files of this folder, if it can't
+    pub fn bypass_refusal(&self) -> Option<&'static str> {
+        if !self.public {
+            Some("the folder is private")
+        } else if self.password {
+            Some("the folder is password protected")
+        } else {
+            None
+        }
+    }
+}
+
 impl FolderEntryOk {
     // Not suitable for Into<> semantics because it adds empty children
     pub fn into_folder_entry_empty(self) -> FolderEntry {
@@ -463,9 +556,21 @@ pub struct FolderCreated {
 
 pub type FolderCreatedResponse = ApiResponse<FolderCreated>;
 
+/// Attribute accepted by `/contents/{id}/update`
+// Not every attribute is used by the WebDAV layer
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum Attribute<'a> {
     Name(&'a str),
+    Description(&'a str),
+    /// Comma-separated list of tags
+    Tags(&'a str),
+    Public(bool),
+    /// Expiration date as a Unix timestamp
+    Expiry(u64),
+    Password(&'a str),
+    /// Modification time as a Unix timestamp
+    ModTime(u64),
 }
 
 impl<'a> Serialize for Attribute<'a> {
@@ -480,6 +585,31 @@ impl<'a> Serialize for Attribute<'a> {
                 map.serialize_entry("attribute", "name")?;
                 map.serialize_entry("attributeValue", v)?;
             }
+            Self::Description(v) => {
+                map.serialize_entry("attribute", "description")?;
+                map.serialize_entry("attributeValue", v)?;
+            }
+            Self::Tags(v) => {
+                map.serialize_entry("attribute", "tags")?;
+                map.serialize_entry("attributeValue", v)?;
+            }
+            Self::Public(v) => {
+                // The API expects the flag as a string
+                map.serialize_entry("attribute", "public")?;
+                map.serialize_entry("attributeValue", &v.to_string())?;
+            }
+            Self::Expiry(v) => {
+                map.serialize_entry("attribute", "expiry")?;
+                map.serialize_entry("attributeValue", v)?;
+            }
+            Self::Password(v) => {
+                map.serialize_entry("attribute", "password")?;
+                map.serialize_entry("attributeValue", v)?;
+            }
+            Self::ModTime(v) => {
+                map.serialize_entry("attribute", "modTime")?;
+                map.serialize_entry("attributeValue", v)?;
+            }
         }
         map.end()
     }
@@ -529,17 +659,42 @@ pub struct DeleteContentsPayload<'a> {
 pub type DeletedContents = HashMap<String, ApiResponse>;
 pub type DeleteContentsResponse = ApiResponse<DeletedContents>;
 
-// TODO: The API currently allows requesting an arbitrary number of items per page, but this may change.
+/// The contents of a delete call gofile.io reported it failed to delete, each with the
+/// reason, sorted. Contents that were gone already count as deleted.
+pub fn failed_deletions(deleted: DeletedContents) -> Vec<String> {
+    let mut failed: Vec<_> = deleted
+        .into_iter()
+        .filter_map(|(id, response)| match response.into_result() {
+            Ok(_) | Err(GofileError::NotFound) => None,
+            Err(e) => Some(format!("{id} ({e})")),
+        })
+        .collect();
+    failed.sort();
+
+    failed
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveContentsPayload<'a> {
+    /// Comma-separated list of content IDs to move.
+    pub contents_id: &'a str,
+    /// Destination folder ID.
+    pub folder_id: &'a str,
+}
 
-// #[derive(Debug, Serialize, Deserialize)]
-// #[serde(rename_all = "camelCase")]
-// pub struct Metadata {
-//     pub total_count: u64,
-//     pub total_pages: u64,
-//     pub page: u64,
-//     pub page_size: u64,
-//     pub has_next_page: bool,
-// }
+pub type MoveContentsResponse = ApiResponse;
+
+/// Paging information of a folder listing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Metadata {
+    pub total_count: u64,
+    pub total_pages: u64,
+    pub page: u64,
+    pub page_size: u64,
+    pub has_next_page: bool,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -554,12 +709,16 @@ pub type BypassFiles = Vec<BypassFile>;
 pub type BypassFilesResponse = ApiResponse<BypassFiles>;
 
 #[cfg(test)]
+// the fields are compared like the others parsed next to them
+#[allow(clippy::bool_assert_comparison)]
 mod test {
     use super::*;
     use rstest::*;
 
     use serde_json::json;
 
+    use crate::gofile::testing;
+
     #[test]
     fn test_ok_api_response() {
         let value = json!({
@@ -609,6 +768,38 @@ mod test {
         assert!(folder_ok.children.is_empty());
     }
 
+    #[test]
+    fn test_paging_metadata() {
+        let value = json!({
+            "status": "ok",
+            "data": {},
+            "metadata": {
+                "totalCount": 2500,
+                "totalPages": 3,
+                "page": 1,
+                "pageSize": 1000,
+                "hasNextPage": true
+            }
+        });
+
+        let (_, metadata) = serde_json::from_value::<ApiResponse>(value)
+            .unwrap()
+            .into_paged_result()
+            .unwrap();
+        assert!(metadata.has_next_page);
+        assert_eq!(metadata.total_pages, 3);
+
+        // partial or missing metadata means a single page
+        for metadata in [json!({}), json!(null)] {
+            let value = json!({ "status": "ok", "data": {}, "metadata": metadata });
+            let (_, metadata) = serde_json::from_value::<ApiResponse>(value)
+                .unwrap()
+                .into_paged_result()
+                .unwrap();
+            assert!(!metadata.has_next_page);
+        }
+    }
+
     #[test]
     fn test_other_api_response() {
         let value = json!({"status":"error-notPremium","data":{}});
@@ -620,6 +811,108 @@ mod test {
         )
     }
 
+    #[test]
+    fn test_account_info() {
+        let account = json!({
+            "id": "4e0c5bd8-2e7f-4a0b-9c83-0f1c8a7e5d21",
+            "rootFolder": "b5f1a0f7-0c3e-4e47-8a5a-2c63d1f0e8a4",
+            "tier": "premium",
+            "token": "Maie2RlOFDDDRao0Y5ll54EAtv2imUlZ",
+            "email": "user@example.com",
+            "statsCurrent": {"fileCount": 3, "folderCount": 1, "storage": 1024},
+            "subscriptionLimitStorage": 4096,
+        });
+        let parsed = serde_json::from_value::<AccountInfo>(account.clone()).unwrap();
+        assert_eq!(parsed.stats_current.storage, 1024);
+        assert_eq!(parsed.subscription_limit_storage, Some(4096));
+
+        // guest accounts come without usage or limit
+        let mut account = account;
+        let fields = account.as_object_mut().unwrap();
+        fields.remove("statsCurrent");
+        fields.remove("subscriptionLimitStorage");
+        let parsed = serde_json::from_value::<AccountInfo>(account).unwrap();
+        assert_eq!(parsed.stats_current.storage, 0);
+        assert_eq!(parsed.subscription_limit_storage, None);
+    }
+
+    #[test]
+    fn test_failed_deletions() {
+        let value = json!({
+            "status": "ok",
+            "data": {
+                "a": {"status": "ok", "data": {}},
+                "b": {"status": "error-notFound", "data": {}},
+                "c": {"status": "error-notPremium", "data": {}},
+            }
+        });
+        let deleted = serde_json::from_value::<DeleteContentsResponse>(value)
+            .unwrap()
+            .into_result()
+            .unwrap();
+
+        // gone already is as good as deleted
+        assert_eq!(
+            failed_deletions(deleted),
+            ["c (API returned an error: error-notPremium)"]
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_api_response() {
+        let value = json!({"status":"error-rateLimit","data":{}});
+        let result = serde_json::from_value::<ContentsWithPasswordResponse>(value)
+            .unwrap()
+            .into_result();
+
+        assert!(
+            matches!(result, Err(GofileError::RateLimited { retry_after: None })),
+            "expected GofileError::RateLimited",
+        )
+    }
+
+    #[rstest]
+    #[case("error-storageLimit")]
+    #[case("error-quotaExceeded")]
+    #[case("error-notEnoughSpace")]
+    #[case("error-tierLimit")]
+    fn test_quota_api_response(#[case] status: &str) {
+        let value = json!({"status": status, "data": {}});
+        let result = serde_json::from_value::<FileUploadedResponse>(value)
+            .unwrap()
+            .into_result();
+
+        match result {
+            Err(GofileError::QuotaExceeded { status: got }) => assert_eq!(got, status),
+            other => panic!("expected GofileError::QuotaExceeded, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_folder_size() {
+        let file = |size: u64| {
+            Contents::File(FileEntry {
+                size,
+                ..testing::file("file")
+            })
+        };
+        let folder = |total_size: u64, children: Vec<Contents>| FolderEntry {
+            total_size,
+            children: children.into_iter().map(|c| (c.id(), c)).collect(),
+            ..FolderEntry::default()
+        };
+
+        // trusted when reported
+        assert_eq!(folder(100, vec![file(3)]).size(), 100);
+
+        let subfolder = Contents::Folder(FolderEntry {
+            id: Uuid::new_v4(),
+            ..folder(20, vec![])
+        });
+        assert_eq!(folder(0, vec![file(3), file(4), subfolder]).size(), 27);
+        assert_eq!(folder(0, vec![]).size(), 0);
+    }
+
     #[test]
     fn test_unexpected_api_response() {
         for input in [r#"{"verde": true}"#, r#""#] {
@@ -695,4 +988,47 @@ mod test {
         assert_eq!(restricted_file.password_status, expected);
         assert_eq!(restricted_file.can_access, false);
     }
+
+    #[rstest]
+    #[case("Veil7n", "Veil7n")]
+    #[case(
+        "6c9e22a7-7d6c-4986-8e93-b118558be0bb",
+        "6c9e22a7-7d6c-4986-8e93-b118558be0bb"
+    )]
+    #[case("https://gofile.io/d/Veil7n", "Veil7n")]
+    #[case("https://www.gofile.io/d/Veil7n/?page=2#top", "Veil7n")]
+    #[case("gofile.io/d/Veil7n", "Veil7n")]
+    #[case(
+        "https://gofile.io/folder/6c9e22a7-7d6c-4986-8e93-b118558be0bb",
+        "6c9e22a7-7d6c-4986-8e93-b118558be0bb"
+    )]
+    fn test_id_or_code_from_url(#[case] input: &str, #[case] expected: &str) {
+        let parsed = IdOrCode::from_url_or_id(input).unwrap();
+        assert_eq!(parsed.to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("https://example.com/d/Veil7n")]
+    #[case("https://gofile.io/myfiles")]
+    #[case("https://gofile.io/d/Veil7n/extra")]
+    #[case("https://")]
+    fn test_id_or_code_from_unsupported_url(#[case] input: &str) {
+        assert!(IdOrCode::from_url_or_id(input).is_err());
+    }
+
+    #[rstest]
+    #[case(Attribute::Name("new.txt"), json!({"attribute": "name", "attributeValue": "new.txt"}))]
+    #[case(Attribute::Description("notes"), json!({"attribute": "description", "attributeValue": "notes"}))]
+    #[case(Attribute::Tags("a,b"), json!({"attribute": "tags", "attributeValue": "a,b"}))]
+    #[case(Attribute::Public(true), json!({"attribute": "public", "attributeValue": "true"}))]
+    #[case(Attribute::Public(false), json!({"attribute": "public", "attributeValue": "false"}))]
+    #[case(Attribute::Expiry(1762184779), json!({"attribute": "expiry", "attributeValue": 1762184779}))]
+    #[case(Attribute::Password("secret"), json!({"attribute": "password", "attributeValue": "secret"}))]
+    #[case(Attribute::ModTime(1700000000), json!({"attribute": "modTime", "attributeValue": 1700000000}))]
+    fn test_attribute_serialization(
+        #[case] attribute: Attribute,
+        #[case] expected: serde_json::Value,
+    ) {
+        assert_eq!(serde_json::to_value(attribute).unwrap(), expected);
+    }
 }
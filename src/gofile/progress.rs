@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use log::{Level, debug, log_enabled};
+
+/// Time between two progress lines of a transfer
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+/// Bytes between two progress lines of a transfer, whichever comes first
+const PROGRESS_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Logs how far a transfer got, at debug level so it stays quiet unless asked for with `-v`.
+///
+/// A line goes out every [`PROGRESS_INTERVAL`] or every [`PROGRESS_BYTES`] moved,
+/// whichever comes first.
+#[derive(Debug)]
+pub struct Progress {
+    label: String,
+    total: Option<u64>,
+    logged_at: Instant,
+    logged_bytes: u64,
+}
+
+impl Progress {
+    /// `label` names the transfer in the log, `total` is its size when known
+    pub fn new(label: String, total: Option<u64>) -> Self {
+        Self {
+            label,
+            total,
+            logged_at: Instant::now(),
+            logged_bytes: 0,
+        }
+    }
+
+    /// Records that the transfer is at `done` bytes, logging it when a line is due.
+    pub fn update(&mut self, done: u64) {
+        if !log_enabled!(Level::Debug) {
+            return;
+        }
+
+        let now = Instant::now();
+        if self.is_due(done, now) {
+            debug!("{}", self.line(done));
+            self.logged_at = now;
+            self.logged_bytes = done;
+        }
+    }
+
+    fn is_due(&self, done: u64, now: Instant) -> bool {
+        now.saturating_duration_since(self.logged_at) >= PROGRESS_INTERVAL
+            || done.abs_diff(self.logged_bytes) >= PROGRESS_BYTES
+    }
+
+    fn line(&self, done: u64) -> String {
+        match self.total {
+            Some(total) if total > 0 => format!(
+                "{}: {done} of {total} bytes ({}%)",
+                self.label,
+                done.min(total) * 100 / total
+            ),
+            _ => format!("{}: {done} bytes", self.label),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_due() {
+        let progress = Progress::new("upload of /a.bin".to_string(), None);
+        let start = progress.logged_at;
+
+        assert!(!progress.is_due(1024, start));
+        assert!(progress.is_due(PROGRESS_BYTES, start));
+        assert!(progress.is_due(1024, start + PROGRESS_INTERVAL));
+    }
+
+    #[test]
+    fn test_line() {
+        let progress = Progress::new("download of a.bin".to_string(), Some(400));
+        assert_eq!(
+            progress.line(100),
+            "download of a.bin: 100 of 400 bytes (25%)"
+        );
+
+        let progress = Progress::new("upload of /a.bin".to_string(), None);
+        assert_eq!(progress.line(100), "upload of /a.bin: 100 bytes");
+    }
+}
@@ -0,0 +1,366 @@
+//! Entries as gofile.io lists them, and a local stand-in for its API, for tests
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use actix_web::{
+    App, HttpResponse, HttpServer,
+    web::{self, Bytes},
+};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use tokio::sync::RwLock;
+
+use super::{Client, DavFs, DirCache, dircache::ROOT_DIR, model::FileEntry};
+
+/// An empty file named `name`, as listed by gofile.io
+pub fn file(name: &str) -> FileEntry {
+    serde_json::from_value(json!({
+        "canAccess": true,
+        "id": Uuid::new_v4(),
+        "name": name,
+        "createTime": 1719990416,
+        "modTime": 1719990416,
+        "size": 0,
+        "md5": "d41d8cd98f00b204e9800998ecf8427e",
+        "link": format!("https://store1.gofile.io/download/web/0/{name}"),
+        "downloadCount": 0,
+        "servers": ["store1"],
+        "serverSelected": "store1",
+        "parentFolder": "parent"
+    }))
+    .unwrap()
+}
+
+struct ApiFolder {
+    name: String,
+    code: String,
+    children: Vec<Uuid>,
+}
+
+/// An API knowing folders and the files uploaded to them, see [`serve_api`]
+#[derive(Default)]
+pub struct Api {
+    folders: Mutex<HashMap<Uuid, ApiFolder>>,
+    // Keyed by id, the parent folder id in `parent_folder`
+    files: Mutex<HashMap<Uuid, FileEntry>>,
+    /// Folders created through the API
+    pub created: AtomicUsize,
+    /// Calls made to delete contents
+    pub delete_calls: AtomicUsize,
+    /// Ids asked to be deleted, in order
+    pub delete_requested: Mutex<Vec<String>>,
+    /// Waited before listing a folder
+    pub listing_delay: Mutex<Duration>,
+}
+
+impl Api {
+    /// Adds a folder named `name` to the one of id or code `parent`, returns its id
+    pub fn add_folder(&self, parent: &str, name: &str) -> Uuid {
+        let id = Uuid::new_v4();
+        let mut folders = self.folders.lock().unwrap();
+        let parent = find(&folders, parent).expect("no such parent folder");
+        folders.get_mut(&parent).unwrap().children.push(id);
+        folders.insert(
+            id,
+            ApiFolder {
+                name: name.to_string(),
+                code: id.to_string(),
+                children: Vec::new(),
+            },
+        );
+
+        id
+    }
+
+    /// Adds an empty file named `name` to the folder of id or code `parent`, returns its id
+    pub fn add_file(&self, parent: &str, name: &str) -> Uuid {
+        let parent = find(&self.folders.lock().unwrap(), parent).expect("no such parent folder");
+        let file = FileEntry {
+            parent_folder: parent.to_string(),
+            ..file(name)
+        };
+        let id = file.id;
+        self.files.lock().unwrap().insert(id, file);
+
+        id
+    }
+
+    /// Whether contents of id `id` are still there
+    pub fn exists(&self, id: Uuid) -> bool {
+        self.folders.lock().unwrap().contains_key(&id)
+            || self.files.lock().unwrap().contains_key(&id)
+    }
+
+    /// Deletes the file or folder of id `id`, a folder with everything inside it
+    fn delete(&self, id: Uuid) -> bool {
+        if self.files.lock().unwrap().remove(&id).is_some() {
+            return true;
+        }
+        let Some(folder) = self.folders.lock().unwrap().remove(&id) else {
+            return false;
+        };
+
+        for folder in self.folders.lock().unwrap().values_mut() {
+            folder.children.retain(|child| *child != id);
+        }
+        let parent = id.to_string();
+        self.files
+            .lock()
+            .unwrap()
+            .retain(|_, file| file.parent_folder != parent);
+        for child in folder.children {
+            self.delete(child);
+        }
+
+        true
+    }
+
+    /// The file named `name` uploaded to any folder
+    pub fn find_file(&self, name: &str) -> Option<FileEntry> {
+        let files = self.files.lock().unwrap();
+        files.values().find(|file| file.name == name).cloned()
+    }
+
+    fn describe(&self, id: Uuid) -> Value {
+        let ApiFolder { name, code, .. } = &self.folders.lock().unwrap()[&id];
+        json!({
+            "canAccess": true,
+            "id": id,
+            "type": "folder",
+            "name": name,
+            "createTime": 0,
+            "modTime": 0,
+            "code": code,
+            "public": false,
+            "totalSize": 0,
+        })
+    }
+
+    fn listing(&self, id_or_code: &str) -> Value {
+        let found = {
+            let folders = self.folders.lock().unwrap();
+            find(&folders, id_or_code).map(|id| (id, folders[&id].children.clone()))
+        };
+        let Some((id, children)) = found else {
+            return json!({ "status": "error-notFound" });
+        };
+
+        let mut listing = self.describe(id);
+        let mut entries: serde_json::Map<_, _> = children
+            .into_iter()
+            .map(|child| (child.to_string(), self.describe(child)))
+            .collect();
+        let parent = id.to_string();
+        for file in self.files.lock().unwrap().values() {
+            if file.parent_folder == parent {
+                let mut entry = serde_json::to_value(file).unwrap();
+                entry["type"] = json!("file");
+                entries.insert(file.id.to_string(), entry);
+            }
+        }
+        listing["children"] = entries.into();
+        json!({ "status": "ok", "data": listing })
+    }
+}
+
+fn find(folders: &HashMap<Uuid, ApiFolder>, id_or_code: &str) -> Option<Uuid> {
+    folders
+        .iter()
+        .find(|(id, folder)| id.to_string() == id_or_code || folder.code == id_or_code)
+        .map(|(id, _)| *id)
+}
+
+async fn create_folder(api: web::Data<Api>, body: web::Json<Value>) -> HttpResponse {
+    // slow enough for uploads next to each other to race
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let parent = body["parentFolderId"].as_str().unwrap();
+    let name = body["folderName"].as_str().unwrap();
+    let id = api.add_folder(parent, name);
+    api.created.fetch_add(1, Ordering::SeqCst);
+
+    HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "data": {
+            "code": id,
+            "createTime": 0,
+            "id": id,
+            "modTime": 0,
+            "name": name,
+            "owner": Uuid::nil(),
+            "parentFolder": parent,
+            "type": "folder",
+        },
+    }))
+}
+
+/// Takes the multipart upload of a single file, its contents only counted
+async fn upload_file(api: web::Data<Api>, body: Bytes) -> HttpResponse {
+    let field = |name: &str| {
+        let start = find_bytes(&body, format!("name=\"{name}\"\r\n\r\n").as_bytes())?;
+        let value = &body[start..];
+        let end = find_bytes(value, b"\r\n")? - 2;
+        Some(String::from_utf8_lossy(&value[..end]).into_owned())
+    };
+    let parent = field("folderId").unwrap();
+    let name_start = find_bytes(&body, b"filename=\"").unwrap();
+    let name_len = find_bytes(&body[name_start..], b"\"").unwrap() - 1;
+    let name = String::from_utf8_lossy(&body[name_start..name_start + name_len]).into_owned();
+    let contents_start = name_start + find_bytes(&body[name_start..], b"\r\n\r\n").unwrap();
+    let contents_end = body.windows(4).rposition(|w| w == b"\r\n--").unwrap();
+
+    let id = Uuid::new_v4();
+    let file = FileEntry {
+        id,
+        name: name.clone(),
+        size: (contents_end - contents_start) as u64,
+        parent_folder: parent.clone(),
+        ..file(&name)
+    };
+    api.files.lock().unwrap().insert(id, file.clone());
+
+    HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "data": {
+            "createTime": file.create_time,
+            "downloadPage": format!("https://gofile.io/d/{parent}"),
+            "id": id,
+            "md5": file.md5,
+            "mimetype": "application/octet-stream",
+            "modTime": file.mod_time,
+            "name": name,
+            "parentFolder": parent,
+            "parentFolderCode": parent,
+            "servers": file.servers,
+            "size": file.size,
+            "type": "file",
+        },
+    }))
+}
+
+async fn delete_contents(api: web::Data<Api>, body: web::Json<Value>) -> HttpResponse {
+    api.delete_calls.fetch_add(1, Ordering::SeqCst);
+
+    let deleted: serde_json::Map<_, _> = body["contentsId"]
+        .as_str()
+        .unwrap()
+        .split(',')
+        .map(|id| {
+            api.delete_requested.lock().unwrap().push(id.to_string());
+            let status = match id.parse() {
+                Ok(id) if api.delete(id) => "ok",
+                _ => "error-notFound",
+            };
+            (id.to_string(), json!({ "status": status, "data": {} }))
+        })
+        .collect();
+
+    HttpResponse::Ok().json(json!({ "status": "ok", "data": deleted }))
+}
+
+/// Where `needle` ends in `haystack`
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|start| start + needle.len())
+}
+
+/// Updates the modification time of a file, the only attribute known here
+async fn update_file(
+    api: web::Data<Api>,
+    id: web::Path<Uuid>,
+    body: web::Json<Value>,
+) -> HttpResponse {
+    let mut files = api.files.lock().unwrap();
+    let (Some(file), Some("modTime")) = (files.get_mut(&id), body["attribute"].as_str()) else {
+        return HttpResponse::Ok().json(json!({ "status": "error-notFound" }));
+    };
+    file.mod_time = body["attributeValue"].as_u64().unwrap();
+
+    HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "data": {
+            "type": "file",
+            "createTime": file.create_time,
+            "id": file.id,
+            "md5": file.md5,
+            "mimetype": "application/octet-stream",
+            "modTime": file.mod_time,
+            "name": file.name,
+            "parentFolder": file.parent_folder,
+        },
+    }))
+}
+
+/// Serves an API listing folders by id or code, creating and deleting contents and taking uploads,
+/// starting with an empty root folder of code `root`. Returns its URL.
+pub fn serve_api(root: &str) -> (String, Arc<Api>) {
+    let api = Arc::new(Api::default());
+    api.folders.lock().unwrap().insert(
+        Uuid::new_v4(),
+        ApiFolder {
+            name: "root".to_string(),
+            code: root.to_string(),
+            children: Vec::new(),
+        },
+    );
+
+    let data = web::Data::from(api.clone());
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(data.clone())
+            .route("/contents/createfolder", web::post().to(create_folder))
+            .route("/uploadfile", web::post().to(upload_file))
+            .route("/contents", web::delete().to(delete_contents))
+            // gofile.io only moves contents for premium accounts
+            .route(
+                "/contents/move",
+                web::put().to(|| async {
+                    HttpResponse::Ok().json(json!({ "status": "error-notPremium", "data": {} }))
+                }),
+            )
+            .route("/contents/{id}/update", web::put().to(update_file))
+            .route(
+                "/contents/{id}",
+                web::get().to(|api: web::Data<Api>, id: web::Path<String>| async move {
+                    let delay = *api.listing_delay.lock().unwrap();
+                    tokio::time::sleep(delay).await;
+                    HttpResponse::Ok().json(api.listing(&id))
+                }),
+            )
+    })
+    .workers(1)
+    .bind("127.0.0.1:0")
+    .unwrap();
+
+    let addr = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+
+    (format!("http://{addr}"), api)
+}
+
+/// A client of the API at `url`, failing right away instead of retrying
+pub fn api_client(url: &str) -> Client {
+    Client::builder()
+        .with_token("token")
+        .with_api_endpoint(url)
+        .with_retry(0, Duration::ZERO, Duration::ZERO)
+        .build()
+}
+
+/// A writable filesystem serving the folder of code `root` of the API at `url` as its root
+pub fn api_fs(url: &str, root: &str) -> DavFs {
+    let dircache = DirCache::new(root.to_string());
+    let mounts = [(ROOT_DIR.to_string(), Arc::new(RwLock::new(dircache)))];
+
+    DavFs::new(api_client(url), mounts, true)
+}
@@ -0,0 +1,84 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Token bucket shared by every download, refilled at `rate` bytes per second.
+///
+/// The bucket holds at most one second worth of bytes. Taking more than what is
+/// available puts it in debt, and the caller waits until the debt is paid back,
+/// so concurrent downloads share the rate instead of each getting all of it.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let rate = bytes_per_sec as f64;
+
+        Self {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: rate,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until `bytes` can go out without exceeding the rate.
+    pub async fn acquire(&self, bytes: usize) {
+        let wait = self.reserve(bytes, Instant::now());
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes `bytes` out of the bucket, returning how long until it is no longer in debt.
+    fn reserve(&self, bytes: usize, now: Instant) -> Duration {
+        // never held across an await
+        let mut bucket = self.bucket.lock().unwrap();
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.rate);
+        bucket.updated = now;
+        bucket.tokens -= bytes as f64;
+
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve() {
+        let limiter = RateLimiter::new(1000);
+        let start = Instant::now();
+
+        // a full second worth of bytes goes out right away
+        assert_eq!(limiter.reserve(1000, start), Duration::ZERO);
+        assert_eq!(limiter.reserve(500, start), Duration::from_millis(500));
+
+        // the debt is shared by the next caller
+        assert_eq!(limiter.reserve(500, start), Duration::from_secs(1));
+
+        // refilled, but never past one second worth of bytes
+        let later = start + Duration::from_secs(10);
+        assert_eq!(limiter.reserve(1000, later), Duration::ZERO);
+        assert_eq!(limiter.reserve(100, later), Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,163 @@
+use std::{io::SeekFrom, time::SystemTime};
+
+use bytes::Bytes;
+use dav_server::fs::{DavDirEntry, DavFile as FsDavFile, DavMetaData, FsError, FsFuture, FsResult};
+use futures_util::FutureExt;
+
+/// A read-only in-memory file generated by the server itself, e.g. `/.mounts.json`.
+#[derive(Debug, Clone)]
+pub struct VirtualFile {
+    name: String,
+    data: Bytes,
+    modified: SystemTime,
+}
+
+impl VirtualFile {
+    pub fn new(name: impl Into<String>, data: impl Into<Bytes>) -> Self {
+        Self {
+            name: name.into(),
+            data: data.into(),
+            modified: SystemTime::now(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl DavMetaData for VirtualFile {
+    fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn is_dir(&self) -> bool {
+        false
+    }
+
+    fn modified(&self) -> FsResult<SystemTime> {
+        Ok(self.modified)
+    }
+
+    fn created(&self) -> FsResult<SystemTime> {
+        Ok(self.modified)
+    }
+}
+
+impl DavDirEntry for VirtualFile {
+    fn name(&self) -> Vec<u8> {
+        self.name.as_bytes().to_vec()
+    }
+
+    fn metadata(&'_ self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        async move { Ok(Box::new(self.clone()) as Box<dyn DavMetaData>) }.boxed()
+    }
+}
+
+/// A directory generated by the server itself, e.g. the mount points of a multi-mount setup.
+#[derive(Debug, Clone)]
+pub struct VirtualDir {
+    name: String,
+    modified: SystemTime,
+}
+
+impl VirtualDir {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            modified: SystemTime::now(),
+        }
+    }
+}
+
+impl DavMetaData for VirtualDir {
+    fn len(&self) -> u64 {
+        0
+    }
+
+    fn is_dir(&self) -> bool {
+        true
+    }
+
+    fn modified(&self) -> FsResult<SystemTime> {
+        Ok(self.modified)
+    }
+
+    fn created(&self) -> FsResult<SystemTime> {
+        Ok(self.modified)
+    }
+}
+
+impl DavDirEntry for VirtualDir {
+    fn name(&self) -> Vec<u8> {
+        self.name.as_bytes().to_vec()
+    }
+
+    fn metadata(&'_ self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        async move { Ok(Box::new(self.clone()) as Box<dyn DavMetaData>) }.boxed()
+    }
+}
+
+#[derive(Debug)]
+pub struct VirtualFileRead {
+    file: VirtualFile,
+    position: usize,
+}
+
+impl VirtualFileRead {
+    pub fn new(file: VirtualFile) -> Self {
+        Self { file, position: 0 }
+    }
+}
+
+impl FsDavFile for VirtualFileRead {
+    fn metadata(&mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        async move { Ok(Box::new(self.file.clone()) as Box<dyn DavMetaData>) }.boxed()
+    }
+
+    fn read_bytes(&mut self, count: usize) -> FsFuture<'_, Bytes> {
+        async move {
+            let end = (self.position + count).min(self.file.data.len());
+            let bytes = self.file.data.slice(self.position..end);
+            self.position = end;
+
+            Ok(bytes)
+        }
+        .boxed()
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> FsFuture<'_, u64> {
+        async move {
+            let len = self.file.data.len() as i128;
+            let new_pos = match pos {
+                SeekFrom::Start(offset) => offset as i128,
+                SeekFrom::End(offset) => len + offset as i128,
+                SeekFrom::Current(offset) => self.position as i128 + offset as i128,
+            };
+
+            if new_pos < 0 || new_pos > len {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "seek position out of bounds",
+                )
+                .into());
+            }
+
+            self.position = new_pos as usize;
+            Ok(self.position as u64)
+        }
+        .boxed()
+    }
+
+    fn write_buf(&mut self, _buf: Box<dyn bytes::Buf + Send>) -> FsFuture<'_, ()> {
+        async { Err(FsError::Forbidden) }.boxed()
+    }
+
+    fn write_bytes(&mut self, _buf: Bytes) -> FsFuture<'_, ()> {
+        async { Err(FsError::Forbidden) }.boxed()
+    }
+
+    fn flush(&mut self) -> FsFuture<'_, ()> {
+        async { Err(FsError::Forbidden) }.boxed()
+    }
+}
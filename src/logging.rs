@@ -0,0 +1,204 @@
+use std::{fmt::Display, io::Write, str::FromStr};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use env_logger::Env;
+use log::{
+    LevelFilter, Record,
+    kv::{self, Key, Value, VisitSource},
+};
+use serde_json::{Map, Value as JsonValue, json};
+
+const CRATE_NAME: &str = env!("CARGO_CRATE_NAME");
+// Top-level modules (and log targets) of this crate that may be given without the crate prefix
+const CRATE_TARGETS: &[&str] = &["gofile", "server"];
+
+/// How log lines are written to stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// A `<module>=<level>` log directive
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleLevel {
+    pub module: String,
+    pub level: LevelFilter,
+}
+
+impl FromStr for ModuleLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (module, level) = s
+            .split_once('=')
+            .context("expected <module>=<level>, e.g. gofile::client=debug")?;
+
+        let module = module.trim();
+        if module.is_empty() {
+            anyhow::bail!("module must not be empty");
+        }
+
+        let level = level
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid log level {level:?}"))?;
+
+        Ok(Self {
+            module: qualify_module(module),
+            level,
+        })
+    }
+}
+
+/// Prefixes crate-relative module paths (`gofile::client`, `server`) with the crate name
+fn qualify_module(module: &str) -> String {
+    let first_segment = module.split("::").next().unwrap_or(module);
+
+    if CRATE_TARGETS.contains(&first_segment) {
+        format!("{CRATE_NAME}::{module}")
+    } else {
+        module.to_string()
+    }
+}
+
+/// Builds an `env_logger` filter string from the global level and per-module overrides
+pub fn filter(level: LevelFilter, modules: &[ModuleLevel]) -> String {
+    let mut directives = vec![level.to_string().to_lowercase()];
+
+    directives.extend(
+        modules
+            .iter()
+            .map(|m| format!("{}={}", m.module, m.level.to_string().to_lowercase())),
+    );
+
+    directives.join(",")
+}
+
+/// Moves `level` up by `verbose` steps and down by `quiet` steps, staying within `off..=trace`
+pub fn adjust(level: LevelFilter, verbose: u8, quiet: u8) -> LevelFilter {
+    let levels: Vec<_> = LevelFilter::iter().collect();
+    let index = (level as usize + verbose as usize)
+        .saturating_sub(quiet as usize)
+        .min(levels.len() - 1);
+
+    levels[index]
+}
+
+/// Initializes the logger. `RUST_LOG` still takes precedence when set.
+pub fn init(level: LevelFilter, modules: &[ModuleLevel], format: LogFormat) {
+    let mut builder =
+        env_logger::Builder::from_env(Env::default().default_filter_or(filter(level, modules)));
+
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = json_line(buf.timestamp_millis(), record);
+            writeln!(buf, "{line}")
+        });
+    }
+
+    builder.init();
+}
+
+/// A log record as a JSON object, its key-values (e.g. of request logs) as extra fields
+fn json_line(timestamp: impl Display, record: &Record) -> JsonValue {
+    let mut line = Map::new();
+    line.insert("timestamp".into(), json!(timestamp.to_string()));
+    line.insert("level".into(), json!(record.level().as_str()));
+    line.insert("target".into(), json!(record.target()));
+    line.insert("message".into(), json!(record.args().to_string()));
+
+    let _ = record.key_values().visit(&mut JsonFields(&mut line));
+
+    JsonValue::Object(line)
+}
+
+struct JsonFields<'a>(&'a mut Map<String, JsonValue>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(n) = value.to_u64() {
+            json!(n)
+        } else if let Some(n) = value.to_f64() {
+            json!(n)
+        } else {
+            json!(value.to_string())
+        };
+
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_filter() {
+        assert_eq!(filter(LevelFilter::Info, &[]), "info");
+    }
+
+    #[test]
+    fn test_adjust() {
+        assert_eq!(adjust(LevelFilter::Info, 0, 0), LevelFilter::Info);
+        assert_eq!(adjust(LevelFilter::Info, 1, 0), LevelFilter::Debug);
+        assert_eq!(adjust(LevelFilter::Info, 2, 0), LevelFilter::Trace);
+        assert_eq!(adjust(LevelFilter::Info, 5, 0), LevelFilter::Trace);
+        assert_eq!(adjust(LevelFilter::Info, 0, 1), LevelFilter::Warn);
+        assert_eq!(adjust(LevelFilter::Info, 0, 9), LevelFilter::Off);
+        assert_eq!(adjust(LevelFilter::Warn, 1, 0), LevelFilter::Info);
+    }
+
+    #[test]
+    fn test_filter_with_modules() {
+        let modules = [
+            "gofile::client=debug".parse::<ModuleLevel>().unwrap(),
+            "server=warn".parse().unwrap(),
+            "actix_web=error".parse().unwrap(),
+            "gofile_dav::gofile::dav=trace".parse().unwrap(),
+        ];
+
+        assert_eq!(
+            filter(LevelFilter::Warn, &modules),
+            "warn,gofile_dav::gofile::client=debug,gofile_dav::server=warn,actix_web=error,gofile_dav::gofile::dav=trace"
+        );
+    }
+
+    #[test]
+    fn test_json_line() {
+        let fields: &[(&str, Value)] = &[
+            ("method", Value::from("GET")),
+            ("status", Value::from(404u16)),
+        ];
+        let record = Record::builder()
+            .args(format_args!("GET /a.txt 404"))
+            .level(log::Level::Info)
+            .target("gofile_dav::server")
+            .key_values(&fields)
+            .build();
+
+        assert_eq!(
+            json_line("2024-07-03T07:06:56.000Z", &record),
+            json!({
+                "timestamp": "2024-07-03T07:06:56.000Z",
+                "level": "INFO",
+                "target": "gofile_dav::server",
+                "message": "GET /a.txt 404",
+                "method": "GET",
+                "status": 404,
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_module_level() {
+        for input in ["gofile::client", "=debug", "gofile::client=loud"] {
+            assert!(input.parse::<ModuleLevel>().is_err(), "{input}");
+        }
+    }
+}
@@ -1,112 +1,714 @@
-use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
-use env_logger::Env;
+use clap::{ArgAction, Args, Parser, Subcommand};
 
-use std::{net::TcpListener, sync::Arc};
+use std::{
+    net::TcpListener,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 mod config;
 mod gofile;
+mod logging;
+mod search;
+mod server;
 mod upgrade;
-use anyhow::bail;
-use config::Config;
+use anyhow::{Context, bail};
+use config::{Config, FolderPassword, FrozenStrategy, Method, Mode, RootMount};
 
-use actix_web::{App, HttpServer, middleware::Logger, web};
+use actix_web::{
+    App, HttpServer,
+    middleware::{Condition, Logger, from_fn},
+    web,
+};
 use dav_server::{
-    DavConfig, DavHandler, DavMethodSet,
-    actix::{DavRequest, DavResponse},
-    fakels::FakeLs,
-    ls::DavLockSystem,
-    memls::MemLs,
+    DavConfig, DavMethod, DavMethodSet, fakels::FakeLs, ls::DavLockSystem, memls::MemLs,
 };
-use gofile::{Client, DavFs, DirCache, error::GofileError, model::Contents};
-use log::{info, warn};
+use gofile::{
+    Client, DavFs, DirCache, DownloadCache, PendingWrites, SavedDirCaches,
+    error::GofileError,
+    model::{Contents, FolderEntry, IdOrCode},
+};
+use log::{LevelFilter, info, warn};
+use logging::{LogFormat, ModuleLevel};
+use reqwest::header::HeaderValue;
+use server::{BasicAuth, CacheControl, RootPage};
 use sha2::{Digest, Sha256};
 use tokio::sync::RwLock;
+use url::Url;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Cli {
     #[command(subcommand)]
     command: Command,
+
+    /// Minimum log level (overridden by RUST_LOG)
+    #[arg(long, env, global = true, default_value_t = LevelFilter::Info)]
+    log_level: LevelFilter,
+
+    /// Log level for a single module, e.g. gofile::client=debug or server=warn (repeatable)
+    #[arg(long, global = true, value_name = "MODULE=LEVEL")]
+    log_module_level: Vec<ModuleLevel>,
+
+    /// Write logs as text or as one JSON object per line
+    #[arg(long, env, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Log more, one level above --log-level per occurrence (-v debug, -vv trace)
+    #[arg(long, short, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Log less, one level below --log-level per occurrence (-q warn, -qq error)
+    #[arg(long, short, global = true, action = ArgAction::Count)]
+    quiet: u8,
+}
+
+/// Flags of every command talking to Gofile
+#[derive(Args, Debug)]
+struct GofileArgs {
+    /// TOML config file, values given on the command line take precedence
+    #[arg(long, short, env)]
+    config: Option<PathBuf>,
+
+    /// Gofile API token
+    #[arg(long, short = 't', env)]
+    api_token: Option<String>,
+
+    /// File holding the Gofile API token, keeping it out of the process list
+    #[arg(long, env, value_name = "PATH", conflicts_with = "api_token")]
+    api_token_file: Option<PathBuf>,
+
+    /// File keeping the guest account token across restarts (when no API token is given)
+    #[arg(long, env, value_name = "PATH")]
+    token_store: Option<PathBuf>,
+
+    /// Root folder ID, code or share URL, e.g. https://gofile.io/d/Veil7n
+    #[arg(env)]
+    root_id: Option<String>,
+
+    /// Root password
+    #[arg(long, short = 'P', env)]
+    password: Option<String>,
+
+    /// File holding the root password, keeping it out of the process list
+    #[arg(long, env, value_name = "PATH", conflicts_with = "password")]
+    password_file: Option<PathBuf>,
+
+    /// Send the passwords as given, e.g. when already hashed, instead of their SHA-256
+    #[arg(long, env)]
+    password_raw: bool,
+
+    /// User agent of every request to Gofile, including downloads and uploads [default: a desktop Chrome]
+    #[arg(long, short = 'U', env)]
+    user_agent: Option<String>,
+
+    /// HTTP(S) or SOCKS5 proxy for every request to Gofile and the bypass service
+    #[arg(long, env, value_name = "URL")]
+    proxy: Option<String>,
+}
+
+impl GofileArgs {
+    /// Merges these flags over the config file (if any) over the defaults
+    fn into_config(self) -> anyhow::Result<Config> {
+        let GofileArgs {
+            config,
+            api_token,
+            api_token_file,
+            token_store,
+            root_id,
+            password,
+            password_file,
+            password_raw,
+            user_agent,
+            proxy,
+        } = self;
+
+        let mut config = match config {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+
+        let api_token = match api_token_file {
+            Some(path) => Some(read_secret(&path)?),
+            None => api_token,
+        };
+        config.api_token = api_token.or(config.api_token);
+        config.token_store = token_store.or(config.token_store);
+        config.root_id = root_id.or(config.root_id);
+        let password = match password_file {
+            Some(path) => Some(read_secret(&path)?),
+            None => password,
+        };
+        config.password_raw |= password_raw;
+        config.password = password
+            .or(config.password)
+            .map(|p| hash_password(p, config.password_raw));
+        config.user_agent = user_agent.or(config.user_agent);
+        config.proxy = proxy.or(config.proxy);
+
+        Ok(config)
+    }
 }
 
+// Parsed once at startup, boxing the serve args isn't worth it
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 enum Command {
-    #[command(group(
-        ArgGroup::new("id_or_auth")
-        .args(&["root_id", "api_token"])
-        .required(true)
-        .multiple(true)
-    ))]
     /// Run webdav server
     Serve {
-        /// Gofile API token
-        #[arg(long, short = 't', env)]
-        api_token: Option<String>,
+        #[command(flatten)]
+        gofile: GofileArgs,
 
-        /// Root folder ID
-        #[arg(env)]
-        root_id: Option<String>,
+        /// Serve a folder as a top-level directory, e.g. media=Veil7n (repeatable, replaces ROOT_ID)
+        #[arg(long, value_name = "NAME=ID", conflicts_with = "root_id")]
+        root: Vec<RootMount>,
 
-        /// Root password
-        #[arg(long, short = 'P', env)]
-        password: Option<String>,
+        /// Password of a single folder, e.g. Veil7n=secret, used instead of --password (repeatable)
+        #[arg(long, value_name = "ID=PASSWORD")]
+        folder_password: Vec<FolderPassword>,
 
-        /// Mode
-        #[arg(long, short, env, value_enum, default_value_t = Mode::ReadOnly)]
-        mode: Mode,
+        /// Mode [default: read-only]
+        #[arg(long, short, env, value_enum)]
+        mode: Option<Mode>,
 
-        /// Port for the application
-        #[arg(long, short, env, default_value_t = 4914)]
-        port: u16,
+        /// Port for the application [default: 4914]
+        #[arg(long, short, env)]
+        port: Option<u16>,
 
-        /// Host for the application
-        #[arg(long, short = 'H', env, default_value = "127.0.0.1")]
-        host: String,
+        /// Host for the application, or a comma-separated list of them [default: 127.0.0.1]
+        #[arg(long, short = 'H', env)]
+        host: Option<String>,
 
-        /// User agent
-        #[arg(long, short = 'U', env)]
-        user_agent: Option<String>,
+        /// Listen on a Unix domain socket instead of --host and --port
+        #[arg(long, env, value_name = "PATH")]
+        unix_socket: Option<PathBuf>,
 
-        /// Use public service gofile-bypass.cybar.xyz for downloads
-        #[arg(long, short, env)]
-        bypass: bool,
+        /// Octal permissions of --unix-socket, e.g. 660
+        #[arg(long, env, value_name = "MODE", value_parser = parse_file_mode)]
+        unix_socket_mode: Option<u32>,
+
+        /// Seconds to wait for a connection to Gofile [default: 10]
+        #[arg(long, env, value_name = "SECS")]
+        connect_timeout: Option<u64>,
+
+        /// Seconds to wait for an API response, or for more data of a download [default: 60]
+        #[arg(long, env, value_name = "SECS")]
+        request_timeout: Option<u64>,
+
+        /// Idle connections kept open per host for reuse [default: unlimited]
+        #[arg(long, env, value_name = "N")]
+        pool_max_idle: Option<usize>,
+
+        /// Seconds an idle connection is kept open for reuse [default: 90]
+        #[arg(long, env, value_name = "SECS")]
+        pool_idle_timeout: Option<u64>,
+
+        /// Seconds a WebDAV operation may take before it fails, reads and writes of a file
+        /// only fail when a chunk takes that long (unlimited if not set)
+        #[arg(long, env, value_name = "SECS")]
+        operation_timeout: Option<u64>,
+
+        /// Seconds an upload may go without data from the client before it is aborted,
+        /// deleting what it left on gofile.io (never if not set)
+        #[arg(long, env, value_name = "SECS")]
+        write_idle_timeout: Option<u64>,
+
+        /// Use public service gofile-bypass.cybar.xyz for downloads, --bypass=false overrides the config file
+        #[arg(long, short, env, num_args = 0..=1, require_equals = true, default_missing_value = "true", value_name = "BOOL")]
+        bypass: Option<bool>,
+
+        /// Fail on private or password protected folders instead of serving them without the bypass,
+        /// --require-bypass=false overrides the config file
+        #[arg(long, env, num_args = 0..=1, require_equals = true, default_missing_value = "true", value_name = "BOOL")]
+        require_bypass: Option<bool>,
+
+        /// Base URL of the bypass service [default: https://gf.1drv.eu.org]
+        #[arg(long, env, value_name = "URL")]
+        bypass_url: Option<Url>,
+
+        /// Bypass proxy host known to be broken, replaces the built-in list (repeatable)
+        #[arg(long, value_name = "HOST")]
+        bypass_broken_host: Vec<String>,
+
+        /// Maximum number of cached folder paths (unbounded if not set)
+        #[arg(long, env)]
+        dir_cache_size: Option<NonZeroUsize>,
+
+        /// Password-protected subfolders fetched in parallel while listing a folder [default: 8]
+        #[arg(long, env)]
+        folder_concurrency: Option<usize>,
+
+        /// Folder listings requested from Gofile at once, whatever the number of clients (unlimited if not set)
+        #[arg(long, env, value_name = "N")]
+        max_concurrent_listings: Option<usize>,
+
+        /// Entries requested per page when listing a folder (whole folder at once if not set)
+        #[arg(long, env, value_name = "N")]
+        page_size: Option<u64>,
+
+        /// Subfolder levels listed in the background when a folder is listed [default: 0]
+        #[arg(long, env, value_name = "N")]
+        prefetch_depth: Option<usize>,
+
+        /// List the root folder before accepting connections instead of on the first request
+        #[arg(long, env)]
+        prewarm: bool,
+
+        /// Retries of a failed Gofile request before giving up [default: 10]
+        #[arg(long, env)]
+        max_retries: Option<u32>,
+
+        /// Shortest delay between retries in milliseconds [default: 500]
+        #[arg(long, env, value_name = "MS")]
+        retry_min_ms: Option<u64>,
+
+        /// Longest delay between retries in milliseconds [default: 20000]
+        #[arg(long, env, value_name = "MS")]
+        retry_max_ms: Option<u64>,
+
+        /// Keep downloaded files in this directory so repeated reads are served locally
+        #[arg(long, env, value_name = "DIR")]
+        cache_dir: Option<PathBuf>,
+
+        /// Size of --cache-dir in MiB before the least recently used files are evicted [default: 1024]
+        #[arg(long, env, value_name = "MIB")]
+        cache_max_mb: Option<u64>,
+
+        /// Keep the resolved folder paths in this file across restarts
+        #[arg(long, env, value_name = "PATH")]
+        cache_file: Option<PathBuf>,
+
+        /// Check the md5 of files downloaded in full and fail the read on a mismatch
+        #[arg(long, env)]
+        verify_md5: bool,
+
+        /// Bytes per second downloaded from Gofile, shared by every client (unlimited if not set)
+        #[arg(long, env, value_name = "BYTES")]
+        max_download_rate: Option<u64>,
+
+        /// Refuse to download files larger than this many bytes (unlimited if not set)
+        #[arg(long, env, value_name = "BYTES")]
+        max_file_size: Option<u64>,
+
+        /// Chunks of a file downloaded at once, spread over the servers hosting it [default: 1]
+        #[arg(long, env, value_name = "N")]
+        parallel_download: Option<usize>,
+
+        /// Times in a row a dropped download is reopened where it stopped before the read fails [default: 3]
+        #[arg(long, env, value_name = "N")]
+        download_reconnects: Option<u32>,
+
+        /// Bytes downloaded ahead of what a client has read, 0 only downloads what is asked for [default: 0]
+        #[arg(long, env, value_name = "BYTES")]
+        read_ahead: Option<usize>,
+
+        /// Bytes a client can write ahead of the upload to Gofile before waiting [default: one chunk]
+        #[arg(long, env, value_name = "BYTES")]
+        upload_buffer: Option<usize>,
+
+        /// Frozen files are hidden, listed but unreadable, or listed as empty .frozen files [default: hide]
+        #[arg(long, env, value_enum)]
+        frozen_strategy: Option<FrozenStrategy>,
+
+        /// Delete a folder with everything inside it in a few batched calls rather than entry by entry (read-write mode)
+        #[arg(long, env)]
+        allow_recursive_delete: bool,
+
+        /// Refuse uploads, new folders, copies and moves onto an existing path with 412 (read-write mode)
+        #[arg(long, env)]
+        no_overwrite: bool,
+
+        /// Create the missing folders above an uploaded file instead of failing (read-write mode)
+        #[arg(long, env)]
+        create_parents: bool,
+
+        /// Move deleted files to this folder (id, or name under the root) instead of deleting them
+        #[arg(long, env, value_name = "ID_OR_NAME")]
+        trash_folder: Option<String>,
+
+        /// Log the writes clients ask for instead of doing them (read-write mode)
+        #[arg(long, env)]
+        dry_run: bool,
+
+        /// Allow writes to a root folder owned by another account, gofile.io refuses what it must (read-write mode)
+        #[arg(long, env)]
+        force_write: bool,
+
+        /// Match paths ignoring ASCII case, for Windows clients and sync tools
+        #[arg(long, env)]
+        case_insensitive: bool,
+
+        /// PEM certificate chain, serves over HTTPS when given with --tls-key
+        #[arg(long, env, value_name = "PEM", requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+
+        /// PEM private key for --tls-cert
+        #[arg(long, env, value_name = "PEM", requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+
+        /// Serve the /healthz and /readyz probes, reachable without --dav-user credentials
+        #[arg(long, env)]
+        probes: bool,
+
+        /// Serve Prometheus metrics at /metrics
+        #[arg(long, env)]
+        metrics: bool,
+
+        /// Serve folder listings as JSON at /api/list/{path}
+        #[arg(long, env)]
+        json_api: bool,
+
+        /// Seconds to wait for in-flight requests and uploads on SIGTERM/SIGINT [default: 30]
+        #[arg(long, env, value_name = "SECS")]
+        shutdown_timeout: Option<u64>,
+
+        /// Worker threads handling requests [default: one per CPU core]
+        #[arg(long, env, value_name = "N")]
+        workers: Option<usize>,
+
+        /// Connections each worker accepts at once, more wait to be accepted [default: 25000]
+        #[arg(long, env, value_name = "N")]
+        max_connections: Option<usize>,
+
+        /// Username required to access the WebDAV endpoint (requires --dav-password)
+        #[arg(long, env, requires = "dav_password")]
+        dav_user: Option<String>,
+
+        /// Password required to access the WebDAV endpoint (requires --dav-user)
+        #[arg(long, env, requires = "dav_user")]
+        dav_password: Option<String>,
+
+        /// Redirect a plain `GET /` to this URL instead of showing an info page (read-only mode)
+        #[arg(long, env, value_name = "URL")]
+        root_redirect: Option<Url>,
+
+        /// Cache-Control header sent with files, e.g. "public, max-age=3600" (none if not set)
+        #[arg(long, env, value_name = "VALUE")]
+        cache_control: Option<String>,
+
+        /// Allow a WebDAV method on top of the mode's preset (repeatable)
+        #[arg(long, value_enum, value_name = "METHOD")]
+        enable_method: Vec<Method>,
+
+        /// Reject a WebDAV method allowed by the mode's preset, e.g. delete (repeatable)
+        #[arg(long, value_enum, value_name = "METHOD")]
+        disable_method: Vec<Method>,
+
+        /// Check the token and the root folders, print what would be served and exit
+        #[arg(long, env)]
+        check_only: bool,
+    },
+
+    /// Print the account and root folder the server would use
+    Info {
+        #[command(flatten)]
+        gofile: GofileArgs,
+    },
+
+    /// Print the files and folders whose name matches a pattern
+    Search {
+        /// Glob with * and ?, or a substring of the name. Case is ignored
+        pattern: String,
+
+        #[command(flatten)]
+        gofile: GofileArgs,
+
+        /// Folder levels walked below the root (unlimited if not set)
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+
+        /// Also report files hidden from listings, inaccessible or frozen ones
+        #[arg(long)]
+        include_hidden: bool,
+    },
+
+    /// Print the direct download link of a file
+    Link {
+        /// Path of the file as served over WebDAV, e.g. /videos/intro.mkv
+        path: String,
+
+        #[command(flatten)]
+        gofile: GofileArgs,
+
+        /// Print the link of the bypass service instead of Gofile's, --bypass=false overrides the config file
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true", value_name = "BOOL")]
+        bypass: Option<bool>,
+
+        /// Fail on private or password protected folders instead of listing them without the bypass,
+        /// --require-bypass=false overrides the config file
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true", value_name = "BOOL")]
+        require_bypass: Option<bool>,
+    },
+
+    /// Print the type, size, dates and state of a file or folder
+    Stat {
+        /// Path as served over WebDAV, e.g. /videos/intro.mkv
+        path: String,
+
+        #[command(flatten)]
+        gofile: GofileArgs,
     },
 
     /// Upgrade the binary
-    Upgrade,
-}
+    Upgrade {
+        /// Only print whether a newer release exists, exiting with 1 if so
+        #[arg(long, conflicts_with_all = ["version", "rollback"])]
+        check: bool,
 
-#[derive(Clone, Debug, ValueEnum)]
-enum Mode {
-    ReadOnly,
-    ReadWrite,
+        /// Put back the binary replaced by the last upgrade
+        #[arg(long, conflicts_with = "version")]
+        rollback: bool,
+
+        /// Release tag to install instead of the latest, e.g. v0.1.9
+        #[arg(long, value_name = "TAG")]
+        version: Option<String>,
+    },
 }
 
 impl TryFrom<Command> for Config {
-    type Error = &'static str;
+    type Error = anyhow::Error;
 
+    /// Merges the command line over the config file (if any) over the defaults
     fn try_from(cmd: Command) -> Result<Self, Self::Error> {
         match cmd {
             Command::Serve {
-                api_token,
-                root_id,
+                gofile,
+                root,
                 port,
                 host,
-                user_agent,
+                unix_socket,
+                unix_socket_mode,
+                connect_timeout,
+                request_timeout,
+                pool_max_idle,
+                pool_idle_timeout,
+                operation_timeout,
+                write_idle_timeout,
                 bypass,
-                password,
+                require_bypass,
+                bypass_url,
+                bypass_broken_host,
+                folder_password,
                 mode,
-            } => Ok(Config {
-                root_id,
-                api_token,
-                port,
-                host,
-                user_agent,
-                bypass,
-                password: password.map(|p| format!("{:x}", Sha256::digest(&p))),
-                write_enabled: matches!(mode, Mode::ReadWrite),
-            }),
-            Command::Upgrade => Err("Cannot create Config from Upgrade command"),
+                dir_cache_size,
+                folder_concurrency,
+                max_concurrent_listings,
+                page_size,
+                prefetch_depth,
+                prewarm,
+                max_retries,
+                retry_min_ms,
+                retry_max_ms,
+                cache_dir,
+                cache_max_mb,
+                cache_file,
+                verify_md5,
+                max_download_rate,
+                max_file_size,
+                parallel_download,
+                download_reconnects,
+                read_ahead,
+                upload_buffer,
+                allow_recursive_delete,
+                no_overwrite,
+                create_parents,
+                trash_folder,
+                dry_run,
+                force_write,
+                case_insensitive,
+                frozen_strategy,
+                tls_cert,
+                tls_key,
+                shutdown_timeout,
+                workers,
+                max_connections,
+                probes,
+                metrics,
+                json_api,
+                dav_user,
+                dav_password,
+                root_redirect,
+                cache_control,
+                enable_method,
+                disable_method,
+                check_only: _,
+            } => {
+                let mut config = gofile.into_config()?;
+
+                if !root.is_empty() {
+                    config.roots = root;
+                }
+                config.port = port.unwrap_or(config.port);
+                config.host = host.unwrap_or(config.host);
+                config.unix_socket = unix_socket.or(config.unix_socket);
+                config.unix_socket_mode = unix_socket_mode.or(config.unix_socket_mode);
+                config.connect_timeout_secs =
+                    connect_timeout.unwrap_or(config.connect_timeout_secs);
+                config.request_timeout_secs =
+                    request_timeout.unwrap_or(config.request_timeout_secs);
+                config.pool_max_idle = pool_max_idle.or(config.pool_max_idle);
+                config.pool_idle_timeout_secs = pool_idle_timeout.or(config.pool_idle_timeout_secs);
+                config.operation_timeout_secs = operation_timeout.or(config.operation_timeout_secs);
+                config.write_idle_timeout_secs =
+                    write_idle_timeout.or(config.write_idle_timeout_secs);
+                config.bypass = bypass.unwrap_or(config.bypass);
+                config.require_bypass = require_bypass.unwrap_or(config.require_bypass);
+                config.bypass_url = bypass_url.map(|url| url.to_string()).or(config.bypass_url);
+                if !bypass_broken_host.is_empty() {
+                    config.bypass_broken_hosts = Some(bypass_broken_host);
+                }
+                if !folder_password.is_empty() {
+                    config.folder_passwords = folder_password;
+                }
+                for folder in &mut config.folder_passwords {
+                    folder.password =
+                        hash_password(std::mem::take(&mut folder.password), config.password_raw);
+                }
+                if let Some(mode) = mode {
+                    config.write_enabled = matches!(mode, Mode::ReadWrite);
+                }
+                config.dir_cache_size = dir_cache_size.or(config.dir_cache_size);
+                config.folder_concurrency = folder_concurrency.or(config.folder_concurrency);
+                config.max_concurrent_listings =
+                    max_concurrent_listings.or(config.max_concurrent_listings);
+                config.page_size = page_size.or(config.page_size);
+                config.prefetch_depth = prefetch_depth.unwrap_or(config.prefetch_depth);
+                config.prewarm |= prewarm;
+                config.max_retries = max_retries.unwrap_or(config.max_retries);
+                config.retry_min_ms = retry_min_ms.unwrap_or(config.retry_min_ms);
+                config.retry_max_ms = retry_max_ms.unwrap_or(config.retry_max_ms);
+                config.cache_dir = cache_dir.or(config.cache_dir);
+                config.cache_max_mb = cache_max_mb.unwrap_or(config.cache_max_mb);
+                config.cache_file = cache_file.or(config.cache_file);
+                config.verify_md5 |= verify_md5;
+                config.max_download_rate = max_download_rate.or(config.max_download_rate);
+                config.max_file_size = max_file_size.or(config.max_file_size);
+                config.parallel_download = parallel_download.or(config.parallel_download);
+                config.download_reconnects = download_reconnects.or(config.download_reconnects);
+                config.read_ahead = read_ahead.or(config.read_ahead);
+                config.upload_buffer = upload_buffer.or(config.upload_buffer);
+                config.allow_recursive_delete |= allow_recursive_delete;
+                config.no_overwrite |= no_overwrite;
+                config.create_parents |= create_parents;
+                config.trash_folder = trash_folder.or(config.trash_folder);
+                config.dry_run |= dry_run;
+                config.force_write |= force_write;
+                config.case_insensitive |= case_insensitive;
+                config.frozen_strategy = frozen_strategy.unwrap_or(config.frozen_strategy);
+                config.tls_cert = tls_cert.or(config.tls_cert);
+                config.tls_key = tls_key.or(config.tls_key);
+                config.probes |= probes;
+                config.metrics |= metrics;
+                config.json_api |= json_api;
+                config.shutdown_timeout_secs =
+                    shutdown_timeout.unwrap_or(config.shutdown_timeout_secs);
+                config.workers = workers.or(config.workers);
+                config.max_connections = max_connections.or(config.max_connections);
+                config.dav_user = dav_user.or(config.dav_user);
+                config.dav_password = dav_password.or(config.dav_password);
+                config.root_redirect = root_redirect
+                    .map(|url| url.to_string())
+                    .or(config.root_redirect);
+                config.cache_control = cache_control.or(config.cache_control);
+                if !enable_method.is_empty() {
+                    config.enabled_methods = enable_method.into_iter().map(Into::into).collect();
+                }
+                if !disable_method.is_empty() {
+                    config.disabled_methods = disable_method.into_iter().map(Into::into).collect();
+                }
+
+                if config.root_id.is_none() && config.roots.is_empty() && config.api_token.is_none()
+                {
+                    bail!("either a root folder ID or an API token is required");
+                }
+
+                if config.root_id.is_some() && !config.roots.is_empty() {
+                    bail!("root_id and roots cannot be used together");
+                }
+
+                for (i, root) in config.roots.iter().enumerate() {
+                    if config.roots[..i].iter().any(|r| r.name == root.name) {
+                        bail!("mount name {:?} is used more than once", root.name);
+                    }
+                }
+
+                if config.retry_min_ms > config.retry_max_ms {
+                    bail!("retry_min_ms must not be greater than retry_max_ms");
+                }
+
+                if config.connect_timeout_secs == 0 || config.request_timeout_secs == 0 {
+                    bail!("connect_timeout_secs and request_timeout_secs must be greater than 0");
+                }
+
+                if config.pool_idle_timeout_secs == Some(0) {
+                    bail!("pool_idle_timeout_secs must be greater than 0");
+                }
+
+                if config.operation_timeout_secs == Some(0) {
+                    bail!("operation_timeout_secs must be greater than 0");
+                }
+
+                if config.write_idle_timeout_secs == Some(0) {
+                    bail!("write_idle_timeout_secs must be greater than 0");
+                }
+
+                if config.page_size == Some(0) {
+                    bail!("page_size must be greater than 0");
+                }
+
+                if let Some(trash) = &config.trash_folder
+                    && (trash.is_empty() || trash.contains('/'))
+                {
+                    bail!("trash_folder must be a folder id or a name without '/'");
+                }
+
+                if config.parallel_download == Some(0) {
+                    bail!("parallel_download must be greater than 0");
+                }
+
+                if config.max_download_rate == Some(0) {
+                    bail!("max_download_rate must be greater than 0");
+                }
+                if config.max_file_size == Some(0) {
+                    bail!("max_file_size must be greater than 0");
+                }
+
+                if config.workers == Some(0)
+                    || config.max_connections == Some(0)
+                    || config.max_concurrent_listings == Some(0)
+                {
+                    bail!(
+                        "workers, max_connections and max_concurrent_listings must be greater than 0"
+                    );
+                }
+
+                if config.unix_socket.is_some() && config.tls_cert.is_some() {
+                    bail!(
+                        "TLS is not supported on a unix socket, terminate it in the reverse proxy"
+                    );
+                }
+
+                if config.tls_cert.is_some() != config.tls_key.is_some() {
+                    bail!("tls_cert and tls_key must be set together");
+                }
+
+                if config.dav_user.is_some() != config.dav_password.is_some() {
+                    bail!("dav_user and dav_password must be set together");
+                }
+
+                if config.require_bypass && !config.bypass {
+                    bail!("require_bypass needs bypass");
+                }
+
+                Ok(config)
+            }
+            Command::Info { gofile }
+            | Command::Search { gofile, .. }
+            | Command::Link { gofile, .. }
+            | Command::Stat { gofile, .. } => gofile.into_config(),
+            Command::Upgrade { .. } => bail!("Cannot create Config from Upgrade command"),
         }
     }
 }
@@ -114,113 +716,1275 @@ impl TryFrom<Command> for Config {
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
-    if let Command::Upgrade = cli.command {
-        return upgrade::self_upgrade();
+    if let Command::Upgrade {
+        check,
+        rollback,
+        version,
+    } = &cli.command
+    {
+        if *rollback {
+            return upgrade::rollback();
+        }
+
+        if *check {
+            if upgrade::check_upgrade()? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+
+        return upgrade::self_upgrade(version.as_deref());
     }
 
-    env_logger::init_from_env(Env::default().default_filter_or("info"));
+    logging::init(
+        logging::adjust(cli.log_level, cli.verbose, cli.quiet),
+        &cli.log_module_level,
+        cli.log_format,
+    );
+
+    let is_info = matches!(cli.command, Command::Info { .. });
+    let check_only = matches!(
+        cli.command,
+        Command::Serve {
+            check_only: true,
+            ..
+        }
+    );
+    let query = match &cli.command {
+        Command::Search {
+            pattern,
+            max_depth,
+            include_hidden,
+            ..
+        } => Some(search::Query {
+            pattern: pattern.clone(),
+            max_depth: *max_depth,
+            include_hidden: *include_hidden,
+        }),
+        _ => None,
+    };
+    let link = match &cli.command {
+        Command::Link {
+            path,
+            bypass,
+            require_bypass,
+            ..
+        } => Some((path.clone(), *bypass, *require_bypass)),
+        _ => None,
+    };
+    let stat = match &cli.command {
+        Command::Stat { path, .. } => Some(path.clone()),
+        _ => None,
+    };
+    let mut config = Config::try_from(cli.command)?;
 
-    let config = Config::try_from(cli.command).map_err(|err| anyhow::anyhow!(err))?;
-    run(config)?;
+    if is_info {
+        print_info(config)?;
+    } else if let Some(query) = query {
+        print_matches(config, query)?;
+    } else if let Some((path, bypass, require_bypass)) = link {
+        config.bypass = bypass.unwrap_or(config.bypass);
+        config.require_bypass = require_bypass.unwrap_or(config.require_bypass);
+        if config.require_bypass && !config.bypass {
+            bail!("require_bypass needs bypass");
+        }
+        print_link(config, &path)?;
+    } else if let Some(path) = stat {
+        print_stat(config, &path)?;
+    } else {
+        run(config, cli.log_format, check_only)?;
+    }
 
     Ok(())
 }
 
-#[actix_web::main(gofile_dav)]
-async fn run(config: Config) -> anyhow::Result<()> {
-    let mut client = Client::builder();
+/// Builds the Gofile client described by the auth and connection settings of `config`
+fn build_client(config: &Config) -> anyhow::Result<Client> {
+    let mut client = Client::builder()
+        .with_retry(
+            config.max_retries,
+            Duration::from_millis(config.retry_min_ms),
+            Duration::from_millis(config.retry_max_ms),
+        )
+        .with_timeouts(
+            Duration::from_secs(config.connect_timeout_secs),
+            Duration::from_secs(config.request_timeout_secs),
+        );
     if config.bypass {
         warn!("Running with experimental bypass mode enabled");
-        client = client.use_bypass(config.bypass)
+        client = client
+            .use_bypass(config.bypass)
+            .require_bypass(config.require_bypass)
     }
 
-    if let Some(api_token) = config.api_token {
+    if let Some(bypass_url) = config.bypass_url.clone() {
+        client = client.with_bypass_endpoint(bypass_url)
+    }
+
+    if let Some(hosts) = config.bypass_broken_hosts.clone() {
+        client = client.with_broken_proxy_hosts(hosts)
+    }
+
+    if let Some(api_token) = config.api_token.clone() {
         client = client.with_token(api_token)
     }
 
+    if let Some(token_store) = config.token_store.clone() {
+        client = client.with_token_store(token_store)
+    }
+
     if let Some(password) = config.password.clone() {
         client = client.with_password(password)
     }
 
+    for folder in &config.folder_passwords {
+        client = client.with_folder_password(folder.id.clone(), folder.password.clone())
+    }
+
     if let Some(user_agent) = config.user_agent.clone() {
         client = client.with_user_agent(user_agent)
     }
 
-    let client = client.build();
+    if let Some(concurrency) = config.folder_concurrency {
+        client = client.with_folder_concurrency(concurrency)
+    }
 
-    let account = client.get_current_account_info().await?;
-    info!("Current account: {}", account.email);
+    if let Some(page_size) = config.page_size {
+        client = client.with_page_size(page_size)
+    }
+
+    if let Some(max_idle) = config.pool_max_idle {
+        client = client.with_pool_max_idle(max_idle)
+    }
+
+    if let Some(timeout) = config.pool_idle_timeout_secs {
+        client = client.with_pool_idle_timeout(Duration::from_secs(timeout))
+    }
+
+    if let Some(proxy) = config.proxy.as_deref() {
+        let proxy =
+            reqwest::Proxy::all(proxy).with_context(|| format!("invalid proxy URL {proxy:?}"))?;
+        client = client.with_proxy(proxy)
+    }
+
+    Ok(client.build())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn print_info(config: Config) -> anyhow::Result<()> {
+    let client = build_client(&config)?;
 
-    let root_id = if let Some(root_id) = config.root_id {
-        root_id
+    let account = client
+        .get_current_account_info()
+        .await
+        .context("failed to fetch the account, check the API token")?;
+
+    println!("Account id:  {}", account.id);
+    println!("Tier:        {}", account.tier);
+    println!("Email:       {}", account.email);
+    println!("Root folder: {}", account.root_folder);
+
+    if let Some(root_id) = config.root_id.clone() {
+        let folder = resolve_root(&client, &config, root_id).await?;
+
+        println!("Folder code: {}", folder.code);
+        println!("Children:    {}", folder.children.len());
+        println!("Total size:  {} bytes", folder.total_size);
+    }
+
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn print_matches(config: Config, query: search::Query) -> anyhow::Result<()> {
+    let client = build_client(&config)?;
+
+    for mount in config.mounts() {
+        let root_id = match mount.target {
+            Some(root_id) => root_id,
+            None => client
+                .get_current_account_info()
+                .await?
+                .root_folder
+                .to_string(),
+        };
+        let root = resolve_root(&client, &config, root_id).await?;
+        let root_path = if mount.name.is_empty() {
+            String::new()
+        } else {
+            format!("/{}", mount.name)
+        };
+
+        for found in search::search(&client, &root_path, root, &query).await {
+            let slash = if found.is_dir { "/" } else { "" };
+            println!("{}{slash}\t{}\t{}", found.path, found.id, found.size);
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn print_link(config: Config, path: &str) -> anyhow::Result<()> {
+    let client = build_client(&config)?;
+    let entry = resolve_path(&client, &config, path).await?;
+
+    // tell why the bypass left the file out when its folder is the reason
+    let refusal = match &entry {
+        Contents::File(file) if config.bypass && !file.bypassed => {
+            match client.get_contents(file.parent_folder.as_str()).await? {
+                Contents::Folder(parent) => parent.bypass_refusal(),
+                Contents::File(_) => None,
+            }
+        }
+        _ => None,
+    };
+
+    println!("{}", download_link(path, entry, config.bypass, refusal)?);
+
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn print_stat(config: Config, path: &str) -> anyhow::Result<()> {
+    let client = build_client(&config)?;
+    let entry = match resolve_path(&client, &config, path).await? {
+        // listings leave the children of subfolders out
+        Contents::Folder(folder) if folder.can_access => client
+            .get_contents(folder.id)
+            .await
+            .with_context(|| format!("failed to list {path}"))?,
+        entry => entry,
+    };
+
+    for (label, value) in stat_lines(&entry) {
+        println!("{:<12} {value}", format!("{label}:"));
+    }
+
+    Ok(())
+}
+
+/// Finds the entry served at the WebDAV `path`, the first component naming the root
+/// when several are configured
+async fn resolve_path(client: &Client, config: &Config, path: &str) -> anyhow::Result<Contents> {
+    let mounts = config.mounts();
+
+    // with several roots, the first component names the mount
+    let (mount, relative) = if config.roots.is_empty() {
+        (&mounts[0], path)
     } else {
-        client
+        let (name, relative) = path
+            .trim_start_matches('/')
+            .split_once('/')
+            .unwrap_or((path.trim_start_matches('/'), ""));
+        let mount = mounts
+            .iter()
+            .find(|mount| mount.name == name)
+            .with_context(|| format!("no root named {name:?}"))?;
+        (mount, relative)
+    };
+
+    let root_id = match mount.target.clone() {
+        Some(root_id) => root_id,
+        None => client
             .get_current_account_info()
             .await?
             .root_folder
-            .to_string()
+            .to_string(),
+    };
+    let root = resolve_root(client, config, root_id).await?;
+
+    search::resolve(client, root, relative)
+        .await?
+        .with_context(|| format!("{path} not found"))
+}
+
+/// What `gofile-dav stat` prints about `entry`, as label and value
+fn stat_lines(entry: &Contents) -> Vec<(&'static str, String)> {
+    let yes_no = |value: bool| if value { "yes" } else { "no" }.to_string();
+    let mut lines = vec![
+        ("Name", entry.name().to_string()),
+        ("Id", entry.id().to_string()),
+    ];
+
+    match entry {
+        Contents::File(file) => {
+            lines.extend([
+                ("Type", "file".to_string()),
+                ("Size", format!("{} bytes", file.size)),
+                ("Created", format_timestamp(file.create_time)),
+                ("Modified", format_timestamp(file.mod_time)),
+                ("MD5", file.md5.to_ascii_lowercase()),
+                ("Accessible", yes_no(file.can_access)),
+                ("Frozen", yes_no(file.is_frozen)),
+            ]);
+        }
+        Contents::Folder(folder) => {
+            lines.extend([
+                ("Type", "folder".to_string()),
+                ("Size", format!("{} bytes", folder.size())),
+                ("Created", format_timestamp(folder.create_time)),
+                ("Modified", format_timestamp(folder.mod_time)),
+                ("Children", folder.children.len().to_string()),
+                ("Accessible", yes_no(folder.can_access)),
+                ("Public", yes_no(folder.public)),
+            ]);
+        }
+    }
+
+    lines
+}
+
+/// Formats unix seconds as `2024-07-03 07:06:56 UTC`
+fn format_timestamp(secs: u64) -> String {
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // civil date from days since 1970-01-01, after Howard Hinnant's `civil_from_days`
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// The link `entry`, found at `path`, can be downloaded from without going through WebDAV.
+/// `refusal` tells why the bypass can't serve the folder of the file, if it can't.
+fn download_link(
+    path: &str,
+    entry: Contents,
+    bypass: bool,
+    refusal: Option<&str>,
+) -> anyhow::Result<Url> {
+    let file = match entry {
+        Contents::File(file) => file,
+        Contents::Folder(_) => bail!("{path} is a folder"),
+    };
+
+    if file.password || !file.can_access {
+        bail!("{path} is password protected");
+    }
+
+    if file.is_frozen {
+        bail!("{path} is frozen");
+    }
+
+    if bypass && !file.bypassed {
+        match refusal {
+            Some(reason) => bail!("bypass unavailable for {path}, {reason}"),
+            None => bail!("the bypass service has no link for {path}"),
+        }
+    }
+
+    Ok(file.link)
+}
+
+#[actix_web::main(gofile_dav)]
+async fn run(config: Config, log_format: LogFormat, check_only: bool) -> anyhow::Result<()> {
+    let methods = dav_methods(&config)?;
+    let tls_config = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => Some(server::load_tls_config(cert, key)?),
+        _ => None,
+    };
+    let mounts_manifest = (config.mounts().len() > 1)
+        .then(|| config.mounts_manifest())
+        .transpose()?;
+
+    let client = build_client(&config)?;
+
+    let account = client.get_current_account_info().await?;
+    info!("Current account: {}", account.email);
+
+    let saved_dircaches = match &config.cache_file {
+        Some(path) => SavedDirCaches::load_from(path).unwrap_or_else(|e| {
+            warn!("failed to load the folder cache {}: {e}", path.display());
+            SavedDirCaches::default()
+        }),
+        None => SavedDirCaches::default(),
     };
 
-    let root_id = match client.get_contents(root_id.as_str()).await {
+    // `--prewarm` is timed from the root listings on
+    let prewarm_started = Instant::now();
+    let mut mounts = Vec::new();
+    // root listings kept to seed the caches with, see `--prewarm`
+    let mut roots = Vec::new();
+    for mount in config.mounts() {
+        let root_id = if let Some(root_id) = mount.target {
+            root_id
+        } else {
+            account.root_folder.to_string()
+        };
+
+        let root = resolve_root(&client, &config, root_id).await?;
+        if check_only {
+            println!(
+                "Mount /{}: folder {} ({}), {} children",
+                mount.name,
+                root.name,
+                root.code,
+                root.children.len()
+            );
+        }
+
+        let mut dircache = match config.dir_cache_size {
+            Some(capacity) => DirCache::with_capacity(root.code.clone(), capacity),
+            None => DirCache::new(root.code.clone()),
+        };
+        let restored = saved_dircaches.restore(&mount.name, &mut dircache);
+        if restored > 0 {
+            info!(
+                "Restored {restored} cached folder paths of {:?}",
+                mount.name
+            );
+        }
+        if config.prewarm {
+            roots.push((mount.name.clone(), root));
+        }
+        mounts.push((mount.name, Arc::new(RwLock::new(dircache))));
+    }
+
+    if check_only {
+        let mode = if config.write_enabled {
+            "read-write"
+        } else {
+            "read-only"
+        };
+        println!(
+            "Account {} can serve {} mount(s) {mode}",
+            account.email,
+            mounts.len()
+        );
+        return Ok(());
+    }
+
+    let health = client.health();
+    health.set_started();
+    let probes = config.probes.then_some(health);
+
+    // saved to `--cache-file` once the server stops
+    let dircaches: Vec<_> = mounts
+        .iter()
+        .map(|(name, dircache)| (name.clone(), dircache.clone()))
+        .collect();
+
+    let mut filesystem = DavFs::new(client, mounts, config.write_enabled)
+        .verify_md5(config.verify_md5)
+        .allow_recursive_delete(config.allow_recursive_delete)
+        .create_only(config.no_overwrite)
+        .create_parents(config.create_parents)
+        .dry_run(config.dry_run)
+        .case_insensitive(config.case_insensitive)
+        .frozen_strategy(config.frozen_strategy.into())
+        .prefetch_depth(config.prefetch_depth);
+    if let Some(manifest) = mounts_manifest {
+        filesystem = filesystem.with_mounts_manifest(manifest);
+    }
+    if let Some(rate) = config.max_download_rate {
+        filesystem = filesystem.max_download_rate(rate);
+    }
+    if let Some(bytes) = config.max_file_size {
+        filesystem = filesystem.max_file_size(bytes);
+    }
+    if let Some(secs) = config.operation_timeout_secs {
+        filesystem = filesystem.operation_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = config.write_idle_timeout_secs {
+        filesystem = filesystem.write_idle_timeout(Duration::from_secs(secs));
+    }
+    if let Some(chunks) = config.parallel_download {
+        filesystem = filesystem.parallel_downloads(chunks);
+    }
+    if let Some(reconnects) = config.download_reconnects {
+        filesystem = filesystem.download_reconnects(reconnects);
+    }
+    if let Some(bytes) = config.read_ahead {
+        filesystem = filesystem.read_ahead(bytes);
+    }
+    if let Some(bytes) = config.upload_buffer {
+        filesystem = filesystem.upload_buffer(bytes);
+    }
+    if let Some(listings) = config.max_concurrent_listings {
+        filesystem = filesystem.max_concurrent_listings(listings);
+    }
+    if let Some(trash) = &config.trash_folder {
+        filesystem = filesystem.trash_folder(trash);
+    }
+    if let Some(cache_dir) = &config.cache_dir {
+        let cache = DownloadCache::new(cache_dir, config.cache_max_mb.saturating_mul(1024 * 1024))
+            .with_context(|| format!("failed to create cache directory {}", cache_dir.display()))?;
+        filesystem = filesystem.with_download_cache(cache);
+    }
+    if config.prewarm {
+        let mut entries = 0;
+        for (name, root) in roots {
+            entries += filesystem.prewarm(&name, root).await;
+        }
+        info!(
+            "Prewarmed {entries} entries in {:?}",
+            prewarm_started.elapsed()
+        );
+    }
+    let pending_writes = filesystem.pending_writes();
+    if config.write_enabled {
+        tokio::spawn(filesystem.clone().reap_idle_writes());
+    }
+    #[cfg(unix)]
+    if config.write_enabled {
+        server::toggle_writes_on_signal(filesystem.clone())
+            .context("failed to listen for SIGUSR1")?;
+    }
+    // looks up the types of the files served, see `server::dav_handler`
+    let mimetypes = web::Data::new(filesystem.clone());
+    let filesystem = Box::new(filesystem);
+    let locksystem = if config.write_enabled {
+        MemLs::new() as Box<dyn DavLockSystem>
+    } else {
+        FakeLs::new() as Box<dyn DavLockSystem>
+    };
+
+    let dav_server = DavConfig::new()
+        .methods(methods)
+        .filesystem(filesystem)
+        .locksystem(locksystem)
+        .build_handler();
+
+    if config.dry_run && !config.write_enabled {
+        warn!("--dry-run is ignored in read-only mode");
+    }
+
+    let root_page = match config.root_redirect {
+        Some(_) if config.write_enabled => {
+            warn!("--root-redirect is ignored in read-write mode");
+            None
+        }
+        Some(url) => Some(RootPage::Redirect(url)),
+        None if config.write_enabled => None,
+        None => Some(RootPage::Info),
+    };
+
+    let auth = config
+        .dav_user
+        .as_deref()
+        .zip(config.dav_password.as_deref())
+        .map(|(user, password)| BasicAuth::new(user, password));
+    let routes = server::Routes {
+        root_page,
+        auth,
+        probes,
+        metrics: config.metrics,
+        json_api: config.json_api,
+    };
+
+    let cache_control = config
+        .cache_control
+        .as_deref()
+        .map(HeaderValue::from_str)
+        .transpose()
+        .context("invalid --cache-control")?;
+    let cache_control = web::Data::new(CacheControl(cache_control));
+
+    let mut server = HttpServer::new(move || {
+        App::new()
+            .app_data(mimetypes.clone())
+            .app_data(cache_control.clone())
+            .wrap(from_fn(server::maintenance_unavailable))
+            .wrap(from_fn(server::basic_auth))
+            .wrap(Condition::new(
+                log_format == LogFormat::Json,
+                from_fn(server::log_request),
+            ))
+            .wrap(Condition::new(
+                log_format == LogFormat::Text,
+                Logger::default().log_target("gofile_dav::server"),
+            ))
+            .configure(|cfg| server::configure(cfg, dav_server.clone(), routes.clone()))
+    });
+
+    if let Some(workers) = config.workers {
+        server = server.workers(workers);
+    }
+    if let Some(max_connections) = config.max_connections {
+        server = server.max_connections(max_connections);
+    }
+
+    let server = match &config.unix_socket {
+        #[cfg(unix)]
+        Some(path) => {
+            server::remove_stale_socket(path)?;
+            let server = server
+                .bind_uds(path)
+                .with_context(|| format!("failed to bind unix socket {}", path.display()))?;
+
+            if let Some(mode) = config.unix_socket_mode {
+                server::set_socket_mode(path, mode)?;
+            }
+
+            server
+        }
+        #[cfg(not(unix))]
+        Some(_) => bail!("unix sockets are only supported on Unix"),
+        None => {
+            let mut server = server;
+            for addr in server::listen_addrs(&config.host, config.port)? {
+                let listener = TcpListener::bind(addr)
+                    .with_context(|| format!("failed to listen on {addr}"))?;
+                info!("Listening on {addr}");
+
+                server = match &tls_config {
+                    Some(tls_config) => server.listen_rustls_0_23(listener, tls_config.clone())?,
+                    None => server.listen(listener)?,
+                };
+            }
+
+            server
+        }
+    };
+
+    let server = server
+        .disable_signals()
+        .shutdown_timeout(config.shutdown_timeout_secs)
+        .run();
+    let result = serve_until_shutdown(server, &pending_writes, config.shutdown_timeout_secs).await;
+
+    if let Some(path) = &config.cache_file {
+        let mut saved = SavedDirCaches::default();
+        for (name, dircache) in &dircaches {
+            saved.insert(name, &*dircache.read().await);
+        }
+
+        if let Err(e) = saved.save_to(path) {
+            warn!("failed to save the folder cache {}: {e}", path.display());
+        }
+    }
+
+    if let Some(path) = &config.unix_socket
+        && let Err(e) = std::fs::remove_file(path)
+    {
+        warn!("failed to remove unix socket {}: {e}", path.display());
+    }
+
+    result
+}
+
+/// Reads a token or password kept in a file, without the surrounding whitespace
+fn read_secret(path: &Path) -> anyhow::Result<String> {
+    let secret = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let secret = secret.trim();
+
+    if secret.is_empty() {
+        bail!("{} is empty", path.display());
+    }
+
+    Ok(secret.to_string())
+}
+
+/// Gofile expects the SHA-256 of a password, unless `raw` asks to send it as given
+fn hash_password(password: String, raw: bool) -> String {
+    if raw {
+        password
+    } else {
+        format!("{:x}", Sha256::digest(&password))
+    }
+}
+
+/// Parses permissions given in octal, like `chmod`
+fn parse_file_mode(s: &str) -> Result<u32, String> {
+    u32::from_str_radix(s.trim_start_matches("0o"), 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| format!("invalid octal mode {s:?}, e.g. 660"))
+}
+
+/// Runs `server` until it stops on its own or a shutdown signal arrives, then stops it
+/// gracefully, waiting up to `timeout_secs` for `pending_writes` to finish.
+async fn serve_until_shutdown(
+    mut server: actix_web::dev::Server,
+    pending_writes: &PendingWrites,
+    timeout_secs: u64,
+) -> anyhow::Result<()> {
+    let handle = server.handle();
+
+    tokio::select! {
+        result = &mut server => return Ok(result?),
+        result = server::shutdown_signal() => result.context("failed to listen for signals")?,
+    }
+
+    let timeout = Duration::from_secs(timeout_secs);
+    info!(
+        "Shutting down, waiting up to {timeout:?} for {} in-flight writes",
+        pending_writes.count()
+    );
+
+    // the server has to keep being polled to process the stop command
+    let (_, drained, result) = tokio::join!(
+        handle.stop(true),
+        tokio::time::timeout(timeout, pending_writes.drained()),
+        &mut server,
+    );
+    result?;
+
+    if drained.is_err() {
+        bail!(
+            "{} writes were still draining after {timeout:?}, their uploads may be incomplete",
+            pending_writes.count()
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks that `root_id` is a folder usable in the configured mode and returns it
+async fn resolve_root(
+    client: &Client,
+    config: &Config,
+    root_id: String,
+) -> anyhow::Result<FolderEntry> {
+    let id = IdOrCode::from_url_or_id(&root_id)?;
+
+    match client.get_contents(id).await {
         Ok(contents) => match contents {
             Contents::File(file) => bail!("Expected folder but got file {}", file.id),
             Contents::Folder(folder) => {
-                if config.write_enabled && !folder.is_owner {
-                    bail!("Write can be used only on an owned folder")
-                }
+                check_root_ownership(config, &folder)?;
 
                 if config.password.is_some() && folder.is_owner {
                     warn!("no password needed for owned folder");
                 }
 
-                folder.code
+                Ok(folder)
             }
         },
         Err(GofileError::NotFound) => bail!("Contents not found {}", root_id),
-        Err(e) => return Err(e.into()),
-    };
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Refuses to write to `folder` when another account owns it, unless `force_write` says
+/// it was shared for writing. gofile.io then rejects the writes it doesn't allow.
+fn check_root_ownership(config: &Config, folder: &FolderEntry) -> anyhow::Result<()> {
+    if !config.write_enabled || folder.is_owner {
+        return Ok(());
+    }
 
-    let dircache = Arc::new(RwLock::new(DirCache::new(root_id)));
-    let filesystem = DavFs::new_boxed(client, dircache, config.write_enabled);
-    let (methods, locksystem) = if config.write_enabled {
-        (
-            DavMethodSet::WEBDAV_RW,
-            MemLs::new() as Box<dyn DavLockSystem>,
+    if !config.force_write {
+        bail!(
+            "Write can be used only on an owned folder, use --force-write for a folder shared with you"
         )
+    }
+
+    warn!(
+        "folder {} belongs to another account, writes gofile.io doesn't allow will be refused",
+        folder.id
+    );
+    Ok(())
+}
+
+/// Methods that mutate the tree and thus only make sense in read-write mode
+const WRITE_METHODS: &[DavMethod] = &[
+    DavMethod::Put,
+    DavMethod::Patch,
+    DavMethod::Delete,
+    DavMethod::MkCol,
+    DavMethod::Copy,
+    DavMethod::Move,
+    DavMethod::PropPatch,
+    DavMethod::Lock,
+    DavMethod::Unlock,
+];
+
+/// Methods every WebDAV client relies on to discover and list the tree
+const REQUIRED_METHODS: &[DavMethod] = &[DavMethod::Options, DavMethod::PropFind];
+
+/// Starts from the mode's preset and applies `--enable-method`/`--disable-method`
+fn dav_methods(config: &Config) -> anyhow::Result<DavMethodSet> {
+    let mut methods = if config.write_enabled {
+        DavMethodSet::WEBDAV_RW
     } else {
-        (
-            DavMethodSet::WEBDAV_RO,
-            FakeLs::new() as Box<dyn DavLockSystem>,
-        )
+        DavMethodSet::WEBDAV_RO
     };
 
-    let dav_server = DavConfig::new()
-        .methods(methods)
-        .filesystem(filesystem)
-        .locksystem(locksystem)
-        .build_handler();
+    for method in &config.enabled_methods {
+        if config.disabled_methods.contains(method) {
+            bail!("{method:?} cannot be both enabled and disabled");
+        }
 
-    let bind_addr = format!("{}:{}", config.host, config.port);
-    let listener = TcpListener::bind(&bind_addr)?;
+        if !config.write_enabled && WRITE_METHODS.contains(method) {
+            bail!("{method:?} can only be enabled in read-write mode");
+        }
 
-    HttpServer::new(move || {
-        App::new()
-            .wrap(Logger::default().log_target("gofile_dav::server"))
-            .app_data(web::Data::new(dav_server.clone()))
-            .service(web::resource("/{tail:.*}").to(dav_handler))
-    })
-    .listen(listener)?
-    .run()
-    .await?;
+        methods.add(*method);
+    }
 
-    Ok(())
+    for method in &config.disabled_methods {
+        if REQUIRED_METHODS.contains(method) {
+            bail!("{method:?} is required by WebDAV clients and cannot be disabled");
+        }
+
+        methods.remove(*method);
+    }
+
+    Ok(methods)
 }
 
-async fn dav_handler(req: DavRequest, davhandler: web::Data<DavHandler>) -> DavResponse {
-    if let Some(prefix) = req.prefix() {
-        let config = DavConfig::new().strip_prefix(prefix);
-        davhandler.handle_with(config, req.request).await.into()
-    } else {
-        davhandler.handle(req.request).await.into()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::gofile::{model::FileEntry, testing::file};
+
+    use actix_web::{http::StatusCode, test as actix_test};
+    use dav_server::memfs::MemFs;
+
+    fn method(name: &str) -> actix_web::http::Method {
+        actix_web::http::Method::from_bytes(name.as_bytes()).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_disabled_delete_in_read_write_mode() {
+        let config = Config {
+            write_enabled: true,
+            disabled_methods: vec![DavMethod::Delete],
+            ..Config::default()
+        };
+
+        let dav_server = DavConfig::new()
+            .methods(dav_methods(&config).unwrap())
+            .filesystem(MemFs::new())
+            .locksystem(MemLs::new())
+            .build_handler();
+
+        let app = actix_test::init_service(
+            App::new()
+                .configure(|cfg| server::configure(cfg, dav_server, server::Routes::default())),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::put()
+            .uri("/file.txt")
+            .set_payload("hello")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert!(resp.status().is_success(), "PUT returned {}", resp.status());
+
+        let req = actix_test::TestRequest::default()
+            .method(method("DELETE"))
+            .uri("/file.txt")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn test_cli_overrides_config_file() {
+        let path = std::env::temp_dir().join(format!("gofile-dav-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+            root_id = "Veil7n"
+            port = 8080
+            password = "secret"
+            bypass = true
+            require_bypass = true
+            "#,
+        )
+        .unwrap();
+        let parse = |args: &[&str]| {
+            let cli = Cli::try_parse_from(
+                ["gofile-dav", "serve", "--config", path.to_str().unwrap()]
+                    .iter()
+                    .chain(args),
+            )
+            .unwrap();
+            Config::try_from(cli.command).unwrap()
+        };
+
+        let config = parse(&["--port", "9000"]);
+        assert_eq!(config.root_id.as_deref(), Some("Veil7n"));
+        assert_eq!(config.port, 9000);
+        assert_eq!(
+            config.password,
+            Some(format!("{:x}", Sha256::digest("secret")))
+        );
+        assert!(config.bypass && config.require_bypass);
+
+        // flags set by the file can be turned off again
+        let config = parse(&["--bypass=false", "--require-bypass=false"]);
+        assert!(!config.bypass && !config.require_bypass);
+        let config = parse(&["--require-bypass=false"]);
+        assert!(config.bypass && !config.require_bypass);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_multiple_roots() {
+        let cli = Cli::try_parse_from([
+            "gofile-dav",
+            "serve",
+            "--root",
+            "projects=Veil7n",
+            "--root",
+            "media=6c9e22a7-7d6c-4986-8e93-b118558be0bb",
+        ])
+        .unwrap();
+        let config = Config::try_from(cli.command).unwrap();
+
+        let names: Vec<_> = config.mounts().into_iter().map(|m| m.name).collect();
+        assert_eq!(names, ["projects", "media"]);
+
+        let cli = Cli::try_parse_from([
+            "gofile-dav",
+            "serve",
+            "--root",
+            "media=Veil7n",
+            "--root",
+            "media=Ab12cd",
+        ])
+        .unwrap();
+        assert!(Config::try_from(cli.command).is_err());
+
+        let cli = Cli::try_parse_from(["gofile-dav", "serve", "Veil7n", "--root", "media=Ab12cd"]);
+        assert!(cli.is_err());
+    }
+
+    #[test]
+    fn test_folder_passwords() {
+        let cli = Cli::try_parse_from([
+            "gofile-dav",
+            "serve",
+            "Veil7n",
+            "--folder-password",
+            "Veil7n=secret",
+            "--folder-password",
+            "Ab12cd=a=b",
+        ])
+        .unwrap();
+        let config = Config::try_from(cli.command).unwrap();
+
+        assert_eq!(
+            config.folder_passwords,
+            [
+                FolderPassword {
+                    id: "Veil7n".to_string(),
+                    password: format!("{:x}", Sha256::digest("secret")),
+                },
+                FolderPassword {
+                    id: "Ab12cd".to_string(),
+                    password: format!("{:x}", Sha256::digest("a=b")),
+                },
+            ]
+        );
+
+        let cli = Cli::try_parse_from(["gofile-dav", "serve", "--folder-password", "=secret"]);
+        assert!(cli.is_err());
+    }
+
+    #[test]
+    fn test_info_uses_auth_flags() {
+        let cli =
+            Cli::try_parse_from(["gofile-dav", "info", "-t", "token", "-P", "pass", "Veil7n"])
+                .unwrap();
+        let config = Config::try_from(cli.command).unwrap();
+
+        assert_eq!(config.api_token.as_deref(), Some("token"));
+        assert_eq!(config.root_id.as_deref(), Some("Veil7n"));
+        assert_eq!(
+            config.password.as_deref(),
+            Some(format!("{:x}", Sha256::digest("pass")).as_str())
+        );
+    }
+
+    #[test]
+    fn test_verbosity_flags() {
+        let cli = Cli::try_parse_from(["gofile-dav", "info", "-vv"]).unwrap();
+        assert_eq!((cli.verbose, cli.quiet), (2, 0));
+
+        let cli = Cli::try_parse_from(["gofile-dav", "-q", "serve"]).unwrap();
+        assert_eq!((cli.verbose, cli.quiet), (0, 1));
+
+        assert!(Cli::try_parse_from(["gofile-dav", "serve", "-v", "-q"]).is_err());
+    }
+
+    #[test]
+    fn test_check_root_ownership() {
+        let shared = FolderEntry::default();
+        let config = |args: &[&str]| {
+            let cli =
+                Cli::try_parse_from(["gofile-dav", "serve", "Veil7n"].iter().chain(args)).unwrap();
+            Config::try_from(cli.command).unwrap()
+        };
+
+        assert!(check_root_ownership(&config(&[]), &shared).is_ok());
+        assert!(check_root_ownership(&config(&["-m", "read-write"]), &shared).is_err());
+        let forced = config(&["-m", "read-write", "--force-write"]);
+        assert!(check_root_ownership(&forced, &shared).is_ok());
+
+        let owned = FolderEntry {
+            is_owner: true,
+            ..FolderEntry::default()
+        };
+        assert!(check_root_ownership(&config(&["-m", "read-write"]), &owned).is_ok());
+    }
+
+    #[test]
+    fn test_password_raw() {
+        let cli = Cli::try_parse_from([
+            "gofile-dav",
+            "serve",
+            "Veil7n",
+            "-P",
+            "5e884898da28047151d0e56f8dc62927",
+            "--folder-password",
+            "abc=secret",
+            "--password-raw",
+        ])
+        .unwrap();
+        let config = Config::try_from(cli.command).unwrap();
+
+        assert_eq!(
+            config.password.as_deref(),
+            Some("5e884898da28047151d0e56f8dc62927")
+        );
+        assert_eq!(config.folder_passwords[0].password, "secret");
+    }
+
+    #[test]
+    fn test_secrets_from_files() {
+        let dir = std::env::temp_dir();
+        let token = dir.join(format!("gofile-dav-{}-api-token", std::process::id()));
+        let password = dir.join(format!("gofile-dav-{}-password", std::process::id()));
+        std::fs::write(&token, "Maie2RlOFDDDRao0Y5ll54EAtv2imUlZ\n").unwrap();
+        std::fs::write(&password, "  pass \n").unwrap();
+
+        let cli = Cli::try_parse_from([
+            "gofile-dav".as_ref(),
+            "info".as_ref(),
+            "--api-token-file".as_ref(),
+            token.as_os_str(),
+            "--password-file".as_ref(),
+            password.as_os_str(),
+        ])
+        .unwrap();
+        let config = Config::try_from(cli.command).unwrap();
+        assert_eq!(
+            config.api_token.as_deref(),
+            Some("Maie2RlOFDDDRao0Y5ll54EAtv2imUlZ")
+        );
+        assert_eq!(
+            config.password.as_deref(),
+            Some(format!("{:x}", Sha256::digest("pass")).as_str())
+        );
+
+        let cli = Cli::try_parse_from([
+            "gofile-dav".as_ref(),
+            "serve".as_ref(),
+            "-t".as_ref(),
+            "token".as_ref(),
+            "--api-token-file".as_ref(),
+            token.as_os_str(),
+        ]);
+        assert!(cli.is_err());
+
+        std::fs::remove_file(&token).unwrap();
+        std::fs::remove_file(&password).unwrap();
+        let cli = Cli::try_parse_from([
+            "gofile-dav".as_ref(),
+            "serve".as_ref(),
+            "--api-token-file".as_ref(),
+            token.as_os_str(),
+        ])
+        .unwrap();
+        assert!(Config::try_from(cli.command).is_err());
+    }
+
+    #[test]
+    fn test_invalid_proxy() {
+        let config = |proxy: &str| Config {
+            proxy: Some(proxy.to_string()),
+            ..Config::default()
+        };
+
+        assert!(build_client(&config("socks5://127.0.0.1:1080")).is_ok());
+        assert!(build_client(&config("http://[::1")).is_err());
+    }
+
+    #[test]
+    fn test_parse_file_mode() {
+        assert_eq!(parse_file_mode("660"), Ok(0o660));
+        assert_eq!(parse_file_mode("0o600"), Ok(0o600));
+        assert!(parse_file_mode("rw-rw----").is_err());
+        assert!(parse_file_mode("1777").is_err());
+    }
+
+    #[test]
+    fn test_retry_bounds() {
+        let cli =
+            Cli::try_parse_from(["gofile-dav", "serve", "Veil7n", "--max-retries", "3"]).unwrap();
+        let config = Config::try_from(cli.command).unwrap();
+        assert_eq!(config.max_retries, 3);
+        assert_eq!(config.retry_min_ms, 500);
+
+        let cli = Cli::try_parse_from([
+            "gofile-dav",
+            "serve",
+            "Veil7n",
+            "--retry-min-ms",
+            "5000",
+            "--retry-max-ms",
+            "1000",
+        ])
+        .unwrap();
+        assert!(Config::try_from(cli.command).is_err());
+    }
+
+    #[test]
+    fn test_connection_limits() {
+        let cli = Cli::try_parse_from([
+            "gofile-dav",
+            "serve",
+            "Veil7n",
+            "--workers",
+            "2",
+            "--max-connections",
+            "100",
+            "--max-concurrent-listings",
+            "4",
+        ])
+        .unwrap();
+        let config = Config::try_from(cli.command).unwrap();
+        assert_eq!(config.workers, Some(2));
+        assert_eq!(config.max_connections, Some(100));
+        assert_eq!(config.max_concurrent_listings, Some(4));
+
+        let cli = Cli::try_parse_from(["gofile-dav", "serve", "Veil7n", "--workers", "0"]).unwrap();
+        assert!(Config::try_from(cli.command).is_err());
+    }
+
+    #[test]
+    fn test_write_method_rejected_in_read_only_mode() {
+        let config = Config {
+            enabled_methods: vec![DavMethod::Put],
+            ..Config::default()
+        };
+
+        assert!(dav_methods(&config).is_err());
+    }
+
+    #[test]
+    fn test_required_method_cannot_be_disabled() {
+        let config = Config {
+            disabled_methods: vec![DavMethod::PropFind],
+            ..Config::default()
+        };
+
+        assert!(dav_methods(&config).is_err());
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(1719990416), "2024-07-03 07:06:56 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_767_225_599), "2025-12-31 23:59:59 UTC");
+    }
+
+    #[test]
+    fn test_stat_lines() {
+        let file = Contents::File(FileEntry {
+            id: "4991e6d7-5217-46ae-af3d-c9174adae924".parse().unwrap(),
+            size: 42,
+            md5: "D41D8CD98F00B204E9800998ECF8427E".to_string(),
+            is_frozen: true,
+            ..file("intro.mkv")
+        });
+
+        assert_eq!(
+            stat_lines(&file),
+            [
+                ("Name", "intro.mkv".to_string()),
+                ("Id", "4991e6d7-5217-46ae-af3d-c9174adae924".to_string()),
+                ("Type", "file".to_string()),
+                ("Size", "42 bytes".to_string()),
+                ("Created", "2024-07-03 07:06:56 UTC".to_string()),
+                ("Modified", "2024-07-03 07:06:56 UTC".to_string()),
+                ("MD5", "d41d8cd98f00b204e9800998ecf8427e".to_string()),
+                ("Accessible", "yes".to_string()),
+                ("Frozen", "yes".to_string()),
+            ]
+        );
+
+        let folder = Contents::Folder(FolderEntry {
+            can_access: false,
+            ..FolderEntry::default()
+        });
+        let lines = stat_lines(&folder);
+        assert!(lines.contains(&("Type", "folder".to_string())));
+        assert!(lines.contains(&("Children", "0".to_string())));
+        assert!(lines.contains(&("Accessible", "no".to_string())));
+    }
+
+    #[test]
+    fn test_download_link() {
+        let intro = || file("intro.mkv");
+
+        let link = download_link("/intro.mkv", Contents::File(intro()), false, None).unwrap();
+        assert_eq!(
+            link.as_str(),
+            "https://store1.gofile.io/download/web/0/intro.mkv"
+        );
+
+        let folder = Contents::Folder(FolderEntry::default());
+        assert!(download_link("/videos", folder, false, None).is_err());
+
+        let protected = Contents::File(FileEntry {
+            password: true,
+            ..intro()
+        });
+        assert!(download_link("/intro.mkv", protected, false, None).is_err());
+        let frozen = Contents::File(FileEntry {
+            is_frozen: true,
+            ..intro()
+        });
+        assert!(download_link("/intro.mkv", frozen, false, None).is_err());
+
+        // the bypass service left the file out
+        assert!(download_link("/intro.mkv", Contents::File(intro()), true, None).is_err());
+        let error = download_link(
+            "/intro.mkv",
+            Contents::File(intro()),
+            true,
+            Some("the folder is private"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "bypass unavailable for /intro.mkv, the folder is private"
+        );
+        let bypassed = Contents::File(FileEntry {
+            bypassed: true,
+            link: "https://gf.1drv.eu.org/proxy/intro.mkv".parse().unwrap(),
+            ..intro()
+        });
+        assert_eq!(
+            download_link("/intro.mkv", bypassed, true, None)
+                .unwrap()
+                .as_str(),
+            "https://gf.1drv.eu.org/proxy/intro.mkv"
+        );
     }
 }
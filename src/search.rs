@@ -0,0 +1,249 @@
+use futures_util::StreamExt;
+use log::warn;
+use uuid::Uuid;
+
+use crate::gofile::{
+    Client,
+    error::GofileResult,
+    model::{Contents, FolderEntry},
+};
+
+// Folders listed at once while walking the tree
+const SEARCH_CONCURRENCY: usize = 8;
+
+/// What `gofile-dav search` looks for
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// A glob with `*` and `?`, or a substring when it has neither. Case is ignored.
+    pub pattern: String,
+    /// Folder levels walked below the root, unlimited if not set
+    pub max_depth: Option<usize>,
+    /// Also report the files listings leave out, inaccessible or frozen ones
+    pub include_hidden: bool,
+}
+
+/// An entry whose name matched a [`Query`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub path: String,
+    pub id: Uuid,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+impl Query {
+    /// Whether `name` matches the pattern
+    pub fn matches(&self, name: &str) -> bool {
+        let pattern = self.pattern.to_lowercase();
+        let name = name.to_lowercase();
+
+        if pattern.contains(['*', '?']) {
+            let pattern: Vec<_> = pattern.chars().collect();
+            let name: Vec<_> = name.chars().collect();
+            glob_match(&pattern, &name)
+        } else {
+            name.contains(&pattern)
+        }
+    }
+
+    /// Whether `entry` shows up in the WebDAV listings, where it could be found by hand
+    fn is_visible(&self, entry: &Contents) -> bool {
+        match entry {
+            Contents::File(file) => self.include_hidden || (file.can_access && !file.is_frozen),
+            Contents::Folder(_) => true,
+        }
+    }
+}
+
+/// Walks `root`, served at `root_path`, and returns the entries below it matching `query`,
+/// sorted by path. Folders that fail to list are logged and skipped.
+pub async fn search(
+    client: &Client,
+    root_path: &str,
+    root: FolderEntry,
+    query: &Query,
+) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut level = vec![(root_path.to_string(), root)];
+    // of the folders in `level`, the root being at 0
+    let mut depth = 0;
+
+    while !level.is_empty() {
+        let descend = query.max_depth.is_none_or(|max| depth < max);
+        depth += 1;
+        let mut to_fetch = Vec::new();
+
+        for (path, folder) in &level {
+            for child in folder
+                .children
+                .values()
+                .filter(|child| query.is_visible(child))
+            {
+                let child_path = format!("{path}/{}", child.name());
+
+                if query.matches(child.name()) {
+                    matches.push(match child {
+                        Contents::File(file) => Match {
+                            path: child_path.clone(),
+                            id: file.id,
+                            size: file.size,
+                            is_dir: false,
+                        },
+                        Contents::Folder(folder) => Match {
+                            path: child_path.clone(),
+                            id: folder.id,
+                            size: folder.size(),
+                            is_dir: true,
+                        },
+                    });
+                }
+
+                if let Contents::Folder(subfolder) = child
+                    && descend
+                {
+                    to_fetch.push((child_path, subfolder.id));
+                }
+            }
+        }
+
+        level = futures_util::stream::iter(to_fetch)
+            .map(|(path, id)| async move { (path, client.get_contents(id).await) })
+            .buffer_unordered(SEARCH_CONCURRENCY)
+            .filter_map(|(path, result)| async move {
+                match result {
+                    Ok(Contents::Folder(folder)) => Some((path, folder)),
+                    Ok(Contents::File(_)) => None,
+                    Err(e) => {
+                        warn!("failed to list {path}: {e}");
+                        None
+                    }
+                }
+            })
+            .collect()
+            .await;
+    }
+
+    matches.sort_by(|a, b| a.path.cmp(&b.path));
+    matches
+}
+
+/// Walks `root` down `path`, relative to it and `/`-separated, and returns the entry found
+/// there, `None` when a component is missing or a file stands in the middle of the path.
+pub async fn resolve(
+    client: &Client,
+    root: FolderEntry,
+    path: &str,
+) -> GofileResult<Option<Contents>> {
+    let mut components = path.split('/').filter(|c| !c.is_empty()).peekable();
+    let mut folder = root;
+
+    while let Some(name) = components.next() {
+        let Some(child) = folder.children.values().find(|child| child.name() == name) else {
+            return Ok(None);
+        };
+
+        if components.peek().is_none() {
+            return Ok(Some(child.clone()));
+        }
+
+        folder = match child {
+            Contents::Folder(subfolder) => match client.get_contents(subfolder.id).await? {
+                Contents::Folder(subfolder) => subfolder,
+                Contents::File(_) => return Ok(None),
+            },
+            Contents::File(_) => return Ok(None),
+        };
+    }
+
+    Ok(Some(Contents::Folder(folder)))
+}
+
+/// Matches `name` against a glob where `*` stands for any run of characters and `?` for
+/// a single one.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // where the last `*` was seen, and the name position it currently covers up to
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, covered)) => {
+                    backtrack = Some((star, covered + 1));
+                    p = star + 1;
+                    n = covered + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::gofile::testing::{api_client, serve_api};
+
+    fn query(pattern: &str) -> Query {
+        Query {
+            pattern: pattern.to_string(),
+            max_depth: None,
+            include_hidden: false,
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(query("*.mkv").matches("Movie.MKV"));
+        assert!(!query("*.mkv").matches("movie.mkv.part"));
+        assert!(query("ep??.*").matches("ep01.srt"));
+        assert!(!query("ep??.*").matches("ep1.srt"));
+        assert!(query("a*b*c").matches("aXbYbZc"));
+        assert!(query("*").matches(""));
+        assert!(query("caf?").matches("Café"));
+
+        // without wildcards, a substring
+        assert!(query("report").matches("2024 Report final.pdf"));
+        assert!(!query("report").matches("summary.pdf"));
+    }
+
+    #[actix_web::test]
+    async fn test_search_max_depth() {
+        let (url, api) = serve_api("Veil7n");
+        let first = api.add_folder("Veil7n", "x1");
+        let second = api.add_folder(&first.to_string(), "x2");
+        api.add_folder(&second.to_string(), "x3");
+
+        let client = api_client(&url);
+        let Contents::Folder(root) = client.get_contents("Veil7n").await.unwrap() else {
+            panic!("root is not a folder");
+        };
+        let found = |max_depth| {
+            let (client, root) = (&client, root.clone());
+            async move {
+                let query = Query {
+                    max_depth,
+                    ..query("x")
+                };
+                let matches = search(client, "", root, &query).await;
+                matches.into_iter().map(|m| m.path).collect::<Vec<_>>()
+            }
+        };
+
+        // the children of the root only
+        assert_eq!(found(Some(0)).await, ["/x1"]);
+        assert_eq!(found(Some(1)).await, ["/x1", "/x1/x2"]);
+        assert_eq!(found(None).await, ["/x1", "/x1/x2", "/x1/x2/x3"]);
+    }
+}
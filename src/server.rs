@@ -0,0 +1,990 @@
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    path::Path,
+    sync::Arc,
+    time::Instant,
+};
+
+use actix_web::{
+    Error, HttpRequest, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        Method, StatusCode,
+        header::{AUTHORIZATION, ContentType, LOCATION, RETRY_AFTER, WWW_AUTHENTICATE},
+    },
+    middleware::Next,
+    web,
+};
+use anyhow::Context;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use dav_server::{
+    DavConfig, DavHandler,
+    actix::{DavRequest, DavResponse},
+    davpath::DavPath,
+    fs::FsError,
+};
+use log::{info, warn};
+use reqwest::{
+    StatusCode as DavStatusCode,
+    header::{
+        ACCEPT_RANGES, CACHE_CONTROL, CONTENT_TYPE as DAV_CONTENT_TYPE, HeaderName, HeaderValue,
+        IF_NONE_MATCH,
+    },
+};
+use rustls::{
+    ServerConfig,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+use crate::gofile::{
+    DavFs,
+    health::Health,
+    maintenance::{self, MAINTENANCE_RETRY_AFTER},
+    metrics,
+    model::Contents,
+    report_timeouts,
+};
+
+/// Probe endpoints, answered without credentials so orchestrators can reach them
+const HEALTH_PATH: &str = "/healthz";
+const READY_PATH: &str = "/readyz";
+const METRICS_PATH: &str = "/metrics";
+/// Where `--json-api` lists a folder, followed by its path
+const LIST_PATH: &str = "/api/list";
+// WebDAV header telling whether COPY and MOVE may replace the destination
+const OVERWRITE: HeaderName = HeaderName::from_static("overwrite");
+// WebDAV header telling how far below a folder a request reaches
+const DEPTH: HeaderName = HeaderName::from_static("depth");
+// ownCloud extension carrying the modification time of an upload, in Unix seconds
+const OC_MTIME: HeaderName = HeaderName::from_static("x-oc-mtime");
+
+/// Resolves on SIGINT or, on Unix, SIGTERM
+pub async fn shutdown_signal() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = sigterm.recv() => Ok(()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
+/// Pauses or resumes the writes of `filesystem` on every SIGUSR1, see [`DavFs::toggle_writes`]
+#[cfg(unix)]
+pub fn toggle_writes_on_signal(filesystem: DavFs) -> io::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut sigusr1 = signal(SignalKind::user_defined1())?;
+    tokio::spawn(async move {
+        while sigusr1.recv().await.is_some() {
+            filesystem.toggle_writes();
+        }
+    });
+
+    Ok(())
+}
+
+/// Removes a socket left behind by a previous run, refusing to touch any other file
+#[cfg(unix)]
+pub fn remove_stale_socket(path: &Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => Ok(std::fs::remove_file(path)?),
+        Ok(_) => anyhow::bail!("{} already exists and is not a socket", path.display()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(unix)]
+pub fn set_socket_mode(path: &Path, mode: u32) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .with_context(|| format!("failed to set the permissions of {}", path.display()))
+}
+
+/// The addresses to listen on for a comma-separated list of hosts, e.g. `127.0.0.1,[::1]`.
+/// IPv6 literals may be bracketed, host names resolve to their first address.
+pub fn listen_addrs(hosts: &str, port: u16) -> anyhow::Result<Vec<SocketAddr>> {
+    hosts
+        .split(',')
+        .map(str::trim)
+        .map(|host| {
+            let unbracketed = host
+                .strip_prefix('[')
+                .and_then(|host| host.strip_suffix(']'))
+                .unwrap_or(host);
+
+            (unbracketed, port)
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .with_context(|| format!("invalid host {host:?}"))
+        })
+        .collect()
+}
+
+/// Loads a PEM certificate chain and private key into a TLS server config
+pub fn load_tls_config(cert: &Path, key: &Path) -> anyhow::Result<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("failed to read TLS certificate {}", cert.display()))?;
+
+    if certs.is_empty() {
+        anyhow::bail!("no certificate found in {}", cert.display());
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key)
+        .with_context(|| format!("failed to read TLS private key {}", key.display()))?;
+
+    ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("TLS certificate and private key don't match")
+}
+
+/// Credentials required by [`basic_auth`]
+#[derive(Debug, Clone)]
+pub struct BasicAuth {
+    // Hashed so the comparison doesn't leak the length of the credentials
+    digest: [u8; 32],
+}
+
+impl BasicAuth {
+    pub fn new(user: &str, password: &str) -> Self {
+        Self {
+            digest: Sha256::digest(format!("{user}:{password}")).into(),
+        }
+    }
+
+    /// Checks an `Authorization` header value against the expected credentials
+    fn verify(&self, header: &str) -> bool {
+        let Some((scheme, encoded)) = header.trim().split_once(' ') else {
+            return false;
+        };
+
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return false;
+        }
+
+        let Ok(decoded) = STANDARD.decode(encoded.trim()) else {
+            return false;
+        };
+
+        let digest: [u8; 32] = Sha256::digest(decoded).into();
+        digest.ct_eq(&self.digest).into()
+    }
+}
+
+/// Rejects requests without valid credentials when a [`BasicAuth`] is registered as app data
+pub async fn basic_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let is_probe = req.method() == Method::GET
+        && [HEALTH_PATH, READY_PATH].contains(&req.path())
+        && req.app_data::<web::Data<Health>>().is_some();
+
+    if let Some(auth) = req.app_data::<web::Data<BasicAuth>>().filter(|_| !is_probe) {
+        let authorized = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| auth.verify(value));
+
+        if !authorized {
+            let response = HttpResponse::Unauthorized()
+                .insert_header((WWW_AUTHENTICATE, r#"Basic realm="gofile-dav""#))
+                .finish();
+
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+/// Turns failures caused by gofile.io maintenance into `503 Service Unavailable` with `Retry-After`.
+/// dav-server answers those with `502 Bad Gateway`, other handlers with a 500.
+pub async fn maintenance_unavailable(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let res = next.call(req).await?;
+
+    let failed = matches!(
+        res.status(),
+        StatusCode::INTERNAL_SERVER_ERROR | StatusCode::BAD_GATEWAY
+    );
+    if !failed || !maintenance::is_active() {
+        return Ok(res.map_into_left_body());
+    }
+
+    warn!(
+        "{} {} failed during gofile.io maintenance",
+        res.request().method(),
+        res.request().path()
+    );
+
+    let response = HttpResponse::ServiceUnavailable()
+        .insert_header((RETRY_AFTER, MAINTENANCE_RETRY_AFTER.as_secs()))
+        .body("gofile.io is under maintenance, retry later\n");
+
+    Ok(res.into_response(response).map_into_right_body())
+}
+
+/// Logs every request with its method, path, status and duration as structured fields.
+/// Used instead of actix's `Logger` when logs are written as JSON.
+pub async fn log_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+
+    let res = next.call(req).await?;
+
+    let status = res.status().as_u16();
+    let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+    info!(
+        method = method.as_str(),
+        path = path.as_str(),
+        status,
+        duration_ms;
+        "{method} {path} {status}"
+    );
+
+    Ok(res)
+}
+
+/// `Cache-Control` sent with files, none if not set, see `--cache-control`
+#[derive(Debug, Clone)]
+pub struct CacheControl(pub Option<HeaderValue>);
+
+/// What a plain `GET /` returns instead of the WebDAV error a browser would otherwise get
+#[derive(Debug, Clone)]
+pub enum RootPage {
+    /// A short page explaining how to connect with a WebDAV client
+    Info,
+    Redirect(String),
+}
+
+const ROOT_INFO_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>gofile-dav</title></head>
+<body>
+<h1>gofile-dav</h1>
+<p>This address is a read-only WebDAV endpoint serving a gofile.io folder, it can't be browsed as a web page.</p>
+<p>Open it with a WebDAV client instead, for example:</p>
+<ul>
+<li>Windows Explorer: <em>Map network drive</em> and paste this page's address</li>
+<li>macOS Finder: <em>Go &rarr; Connect to Server</em> and paste this page's address</li>
+<li>rclone: <code>rclone config create gofile webdav url=&lt;this page's address&gt;</code></li>
+</ul>
+</body>
+</html>
+"#;
+
+/// What is served next to the WebDAV handler, see [`configure`]
+#[derive(Clone, Default)]
+pub struct Routes {
+    /// Answers a bare `GET /`
+    pub root_page: Option<RootPage>,
+    /// Only takes effect when the app is wrapped with [`basic_auth`]
+    pub auth: Option<BasicAuth>,
+    /// Reported on by the `GET /healthz` and `GET /readyz` probes
+    pub probes: Option<Health>,
+    /// Serves `GET /metrics`
+    pub metrics: bool,
+    /// Serves `GET /api/list/{path}`
+    pub json_api: bool,
+}
+
+/// Registers the WebDAV handler for every path, plus the `routes` enabled.
+pub fn configure(cfg: &mut web::ServiceConfig, dav_server: DavHandler, routes: Routes) {
+    let Routes {
+        root_page,
+        auth,
+        probes,
+        metrics,
+        json_api,
+    } = routes;
+    cfg.app_data(web::Data::new(dav_server));
+
+    if let Some(auth) = auth {
+        cfg.app_data(web::Data::new(auth));
+    }
+
+    if let Some(root_page) = root_page {
+        cfg.app_data(web::Data::new(root_page)).service(
+            web::resource("/")
+                .route(web::get().to(root_page_handler))
+                .default_service(web::to(dav_handler)),
+        );
+    }
+
+    if let Some(health) = probes {
+        cfg.app_data(web::Data::new(health))
+            .service(
+                web::resource(HEALTH_PATH)
+                    .route(web::get().to(health_handler))
+                    .default_service(web::to(dav_handler)),
+            )
+            .service(
+                web::resource(READY_PATH)
+                    .route(web::get().to(ready_handler))
+                    .default_service(web::to(dav_handler)),
+            );
+    }
+
+    if metrics {
+        cfg.service(
+            web::resource(METRICS_PATH)
+                .route(web::get().to(metrics_handler))
+                .default_service(web::to(dav_handler)),
+        );
+    }
+
+    if json_api {
+        cfg.service(
+            web::resource([LIST_PATH.to_string(), format!("{LIST_PATH}/{{tail:.*}}")])
+                .route(web::get().to(list_handler))
+                .default_service(web::to(dav_handler)),
+        );
+    }
+
+    cfg.service(web::resource("/{tail:.*}").to(dav_handler));
+}
+
+/// A child of a folder listed by the JSON API
+#[derive(Debug, Serialize)]
+struct ListEntry<'a> {
+    name: &'a str,
+    id: Uuid,
+    r#type: &'static str,
+    size: u64,
+    mod_time: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    md5: Option<&'a str>,
+}
+
+impl<'a> From<&'a Contents> for ListEntry<'a> {
+    fn from(contents: &'a Contents) -> Self {
+        match contents {
+            Contents::File(file) => Self {
+                name: &file.name,
+                id: file.id,
+                r#type: "file",
+                size: file.size,
+                mod_time: file.mod_time,
+                md5: Some(&file.md5),
+            },
+            Contents::Folder(folder) => Self {
+                name: &folder.name,
+                id: folder.id,
+                r#type: "folder",
+                size: folder.size(),
+                mod_time: folder.mod_time,
+                md5: None,
+            },
+        }
+    }
+}
+
+/// Lists the folder below [`LIST_PATH`] as JSON, out of the same caches as WebDAV
+async fn list_handler(req: HttpRequest, filesystem: Option<web::Data<DavFs>>) -> HttpResponse {
+    let Some(filesystem) = filesystem else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    let path = req.path().strip_prefix(LIST_PATH).unwrap_or_default();
+    let Ok(path) = DavPath::new(if path.is_empty() { "/" } else { path }) else {
+        return HttpResponse::BadRequest().finish();
+    };
+
+    match filesystem.list(&path).await {
+        Ok(children) => {
+            HttpResponse::Ok().json(children.iter().map(ListEntry::from).collect::<Vec<_>>())
+        }
+        Err(FsError::NotFound) => HttpResponse::NotFound().finish(),
+        Err(FsError::Forbidden) => HttpResponse::Forbidden().finish(),
+        Err(e) => {
+            warn!("failed to list {} for the JSON API: {e:?}", req.path());
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Answers as soon as the server is listening
+async fn health_handler() -> HttpResponse {
+    HttpResponse::Ok().body("ok\n")
+}
+
+/// Answers once startup reached gofile.io, and while the last call to it succeeded
+async fn ready_handler(health: web::Data<Health>) -> HttpResponse {
+    if health.is_ready() {
+        HttpResponse::Ok().body("ok\n")
+    } else {
+        HttpResponse::ServiceUnavailable().body("not ready\n")
+    }
+}
+
+async fn root_page_handler(root_page: web::Data<RootPage>) -> HttpResponse {
+    match root_page.as_ref() {
+        RootPage::Info => HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(ROOT_INFO_PAGE),
+        RootPage::Redirect(url) => HttpResponse::Found()
+            .insert_header((LOCATION, url.as_str()))
+            .finish(),
+    }
+}
+
+async fn metrics_handler() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(metrics::render())
+}
+
+async fn dav_handler(
+    mut req: DavRequest,
+    davhandler: web::Data<DavHandler>,
+    filesystem: Option<web::Data<DavFs>>,
+    cache_control: Option<web::Data<CacheControl>>,
+) -> DavResponse {
+    let started = Instant::now();
+    let method = req.request.method().clone();
+
+    // dav-server answers 412 to these when the target exists, before anything is written
+    if filesystem.as_ref().is_some_and(|fs| fs.is_create_only()) {
+        let headers = req.request.headers_mut();
+        match method.as_str() {
+            "PUT" | "MKCOL" => {
+                headers.insert(IF_NONE_MATCH, HeaderValue::from_static("*"));
+            }
+            "COPY" | "MOVE" => {
+                headers.insert(OVERWRITE, HeaderValue::from_static("F"));
+            }
+            _ => {}
+        }
+    }
+    // dav-server deletes a folder one entry at a time, listing and removing each on its
+    // own, the filesystem removes the whole tree in a few batched calls instead
+    if method == "DELETE"
+        && filesystem
+            .as_ref()
+            .is_some_and(|fs| fs.is_recursive_delete())
+    {
+        let headers = req.request.headers_mut();
+        headers.insert(DEPTH, HeaderValue::from_static("0"));
+    }
+    let mod_time = (method == "PUT")
+        .then(|| {
+            req.request
+                .headers()
+                .get(OC_MTIME)?
+                .to_str()
+                .ok()?
+                .parse::<u64>()
+                .ok()
+        })
+        .flatten();
+    let prefix = req.prefix().map(str::to_string);
+    let path = DavPath::new(req.request.uri().path())
+        .and_then(|mut path| {
+            path.set_prefix(prefix.as_deref().unwrap_or_default())?;
+            Ok(path)
+        })
+        .ok();
+
+    let (mut response, timed_out) = report_timeouts(async {
+        if let Some(prefix) = &prefix {
+            let config = DavConfig::new().strip_prefix(prefix);
+            davhandler.handle_with(config, req.request).await
+        } else {
+            davhandler.handle(req.request).await
+        }
+    })
+    .await;
+    if timed_out && response.status() == DavStatusCode::INTERNAL_SERVER_ERROR {
+        *response.status_mut() = DavStatusCode::GATEWAY_TIMEOUT;
+    }
+
+    // gofile.io stamps uploads with their own time, clients syncing by time would
+    // upload the file again and again
+    if response.status().is_success()
+        && let (Some(filesystem), Some(path), Some(secs)) = (&filesystem, &path, mod_time)
+    {
+        match filesystem.set_mod_time(path, secs).await {
+            Ok(()) => {
+                response
+                    .headers_mut()
+                    .insert(OC_MTIME, HeaderValue::from_static("accepted"));
+            }
+            Err(e) => warn!(
+                "failed to set the modification time of {}: {e}",
+                path.as_url_string()
+            ),
+        }
+    }
+
+    // dav-server guesses the type from the extension, gofile.io knows better
+    if matches!(method.as_str(), "GET" | "HEAD")
+        && response.status().is_success()
+        && let (Some(filesystem), Some(path)) = (filesystem, path)
+        && let Some(mimetype) = filesystem.mimetype(&path).await
+        && let Ok(value) = HeaderValue::from_str(&mimetype)
+    {
+        response.headers_mut().insert(DAV_CONTENT_TYPE, value);
+    }
+
+    // dav-server marks the files it sends with `Accept-Ranges`, folders don't get cached
+    if matches!(method.as_str(), "GET" | "HEAD")
+        && (response.status().is_success() || response.status() == DavStatusCode::NOT_MODIFIED)
+        && response.headers().contains_key(ACCEPT_RANGES)
+        && let Some(CacheControl(Some(value))) = cache_control.as_ref().map(|data| data.get_ref())
+    {
+        response.headers_mut().insert(CACHE_CONTROL, value.clone());
+    }
+
+    // the body may still be streaming, this measures the time to the response head
+    metrics::observe_dav_request(method.as_str(), started.elapsed());
+
+    response.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::Ordering;
+
+    use actix_web::{App, middleware::from_fn, test as actix_test};
+    use dav_server::{
+        DavMethodSet,
+        fakels::FakeLs,
+        fs::{
+            DavDirEntry, DavFile, DavFileSystem, DavMetaData, FsFuture, FsStream, OpenOptions,
+            ReadDirMeta,
+        },
+        memfs::MemFs,
+        memls::MemLs,
+    };
+    use futures_util::FutureExt;
+
+    use crate::gofile::{
+        error::GofileError,
+        testing::{api_fs, serve_api},
+    };
+
+    fn read_only_handler() -> DavHandler {
+        DavConfig::new()
+            .methods(DavMethodSet::WEBDAV_RO)
+            .filesystem(MemFs::new())
+            .locksystem(FakeLs::new())
+            .build_handler()
+    }
+
+    #[test]
+    fn test_listen_addrs() {
+        assert_eq!(
+            listen_addrs("127.0.0.1", 4914).unwrap(),
+            ["127.0.0.1:4914".parse::<SocketAddr>().unwrap()]
+        );
+        assert_eq!(
+            listen_addrs("0.0.0.0, [::], ::1", 80).unwrap(),
+            [
+                "0.0.0.0:80".parse::<SocketAddr>().unwrap(),
+                "[::]:80".parse().unwrap(),
+                "[::1]:80".parse().unwrap(),
+            ]
+        );
+        assert!(listen_addrs("127.0.0.1,", 80).is_err());
+        assert!(listen_addrs("[::1", 80).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_root_info_page() {
+        let app = actix_test::init_service(App::new().configure(|cfg| {
+            configure(
+                cfg,
+                read_only_handler(),
+                Routes {
+                    root_page: Some(RootPage::Info),
+                    ..Default::default()
+                },
+            )
+        }))
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = actix_test::read_body(resp).await;
+        assert!(body.starts_with(b"<!DOCTYPE html>"));
+
+        let req = actix_test::TestRequest::default()
+            .method("PROPFIND".parse().unwrap())
+            .uri("/")
+            .insert_header(("Depth", "1"))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    }
+
+    #[actix_web::test]
+    async fn test_root_redirect() {
+        let app = actix_test::init_service(App::new().configure(|cfg| {
+            configure(
+                cfg,
+                read_only_handler(),
+                Routes {
+                    root_page: Some(RootPage::Redirect("https://example.com/".to_string())),
+                    ..Default::default()
+                },
+            )
+        }))
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(
+            resp.headers().get(LOCATION).unwrap(),
+            "https://example.com/"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_basic_auth() {
+        let app = actix_test::init_service(App::new().wrap(from_fn(basic_auth)).configure(|cfg| {
+            configure(
+                cfg,
+                read_only_handler(),
+                Routes {
+                    auth: Some(BasicAuth::new("user", "secret")),
+                    ..Default::default()
+                },
+            )
+        }))
+        .await;
+
+        let propfind = |authorization: Option<&str>| {
+            let mut req = actix_test::TestRequest::default()
+                .method("PROPFIND".parse().unwrap())
+                .uri("/")
+                .insert_header(("Depth", "0"));
+            if let Some(authorization) = authorization {
+                req = req.insert_header((AUTHORIZATION, authorization));
+            }
+            req.to_request()
+        };
+
+        let resp = actix_test::call_service(&app, propfind(None)).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(resp.headers().contains_key(WWW_AUTHENTICATE));
+
+        let wrong = format!("Basic {}", STANDARD.encode("user:wrong"));
+        let resp = actix_test::call_service(&app, propfind(Some(&wrong))).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let valid = format!("Basic {}", STANDARD.encode("user:secret"));
+        let resp = actix_test::call_service(&app, propfind(Some(&valid))).await;
+        assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    }
+
+    #[actix_web::test]
+    async fn test_probes() {
+        let health = Health::default();
+        let app = actix_test::init_service(App::new().wrap(from_fn(basic_auth)).configure(|cfg| {
+            configure(
+                cfg,
+                read_only_handler(),
+                Routes {
+                    auth: Some(BasicAuth::new("user", "secret")),
+                    probes: Some(health.clone()),
+                    ..Default::default()
+                },
+            )
+        }))
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/healthz").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = actix_test::TestRequest::get().uri("/readyz").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        health.set_started();
+
+        let req = actix_test::TestRequest::get().uri("/readyz").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // only the probes skip authentication
+        let req = actix_test::TestRequest::default()
+            .method("PROPFIND".parse().unwrap())
+            .uri("/healthz")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        // without the flag the paths are left to the WebDAV handler, behind authentication
+        let app = actix_test::init_service(App::new().wrap(from_fn(basic_auth)).configure(|cfg| {
+            configure(
+                cfg,
+                read_only_handler(),
+                Routes {
+                    auth: Some(BasicAuth::new("user", "secret")),
+                    ..Default::default()
+                },
+            )
+        }))
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/healthz").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_metrics() {
+        let app = actix_test::init_service(App::new().configure(|cfg| {
+            configure(
+                cfg,
+                read_only_handler(),
+                Routes {
+                    metrics: true,
+                    ..Default::default()
+                },
+            )
+        }))
+        .await;
+
+        let req = actix_test::TestRequest::default()
+            .method("PROPFIND".parse().unwrap())
+            .uri("/")
+            .insert_header(("Depth", "0"))
+            .to_request();
+        actix_test::call_service(&app, req).await;
+
+        let req = actix_test::TestRequest::get().uri("/metrics").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = actix_test::read_body(resp).await;
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains(r#"gofile_dav_requests_total{method="PROPFIND"}"#));
+
+        // without the flag the path is left to the WebDAV handler
+        let app = actix_test::init_service(
+            App::new().configure(|cfg| configure(cfg, read_only_handler(), Routes::default())),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/metrics").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_maintenance_unavailable() {
+        let app =
+            actix_test::init_service(App::new().wrap(from_fn(maintenance_unavailable)).route(
+                "/",
+                web::get().to(|| async { HttpResponse::InternalServerError().finish() }),
+            ))
+            .await;
+
+        maintenance::report();
+
+        let req = actix_test::TestRequest::get().uri("/").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get(RETRY_AFTER).unwrap(), "60");
+    }
+
+    /// Fails every lookup the way a `DavFs` does while gofile.io is under maintenance
+    #[derive(Clone)]
+    struct MaintenanceFs;
+
+    impl DavFileSystem for MaintenanceFs {
+        fn open<'a>(
+            &'a self,
+            _path: &'a DavPath,
+            _options: OpenOptions,
+        ) -> FsFuture<'a, Box<dyn DavFile>> {
+            async { Err(GofileError::Maintenance.into()) }.boxed()
+        }
+
+        fn read_dir<'a>(
+            &'a self,
+            _path: &'a DavPath,
+            _meta: ReadDirMeta,
+        ) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
+            async { Err(GofileError::Maintenance.into()) }.boxed()
+        }
+
+        fn metadata<'a>(&'a self, _path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+            async { Err(GofileError::Maintenance.into()) }.boxed()
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_maintenance_reaches_dav_clients() {
+        let dav_server = DavConfig::new()
+            .filesystem(Box::new(MaintenanceFs))
+            .locksystem(FakeLs::new())
+            .build_handler();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(from_fn(maintenance_unavailable))
+                .configure(|cfg| configure(cfg, dav_server, Routes::default())),
+        )
+        .await;
+
+        maintenance::report();
+
+        let req = actix_test::TestRequest::get().uri("/file.txt").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get(RETRY_AFTER).unwrap(), "60");
+    }
+
+    fn writable_handler(fs: &DavFs) -> DavHandler {
+        DavConfig::new()
+            .filesystem(Box::new(fs.clone()))
+            .locksystem(MemLs::new())
+            .build_handler()
+    }
+
+    #[actix_web::test]
+    async fn test_put_keeps_client_mod_time() {
+        let (url, api) = serve_api("Veil7n");
+        let fs = api_fs(&url, "Veil7n");
+        let dav_server = writable_handler(&fs);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(fs))
+                .configure(|cfg| configure(cfg, dav_server, Routes::default())),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::put()
+            .uri("/a.txt")
+            .insert_header(("X-OC-Mtime", "1700000000"))
+            .set_payload("hello")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert!(resp.status().is_success(), "{}", resp.status());
+        assert_eq!(resp.headers().get("x-oc-mtime").unwrap(), "accepted");
+
+        let file = api.find_file("a.txt").unwrap();
+        assert_eq!(file.size, 5);
+        assert_eq!(file.mod_time, 1700000000);
+
+        // the listing shows the time the client sent, not the upload time
+        let req = actix_test::TestRequest::default()
+            .method("PROPFIND".parse().unwrap())
+            .uri("/a.txt")
+            .insert_header(("Depth", "0"))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        let body = actix_test::read_body(resp).await;
+        assert!(String::from_utf8_lossy(&body).contains("Tue, 14 Nov 2023 22:13:20 GMT"));
+    }
+
+    #[actix_web::test]
+    async fn test_delete_folder() {
+        let (url, api) = serve_api("Veil7n");
+        let add_tree = || {
+            let folder = api.add_folder("Veil7n", "a");
+            let file = api.add_file(&folder.to_string(), "x");
+            let subfolder = api.add_folder(&folder.to_string(), "b");
+            let nested = api.add_file(&subfolder.to_string(), "y");
+            [folder, file, subfolder, nested]
+        };
+        let delete = |depth: Option<&str>| {
+            let mut req = actix_test::TestRequest::delete().uri("/a/");
+            if let Some(depth) = depth {
+                req = req.insert_header(("Depth", depth));
+            }
+            req.to_request()
+        };
+
+        let fs = api_fs(&url, "Veil7n");
+        let dav_server = writable_handler(&fs);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(fs))
+                .configure(|cfg| configure(cfg, dav_server, Routes::default())),
+        )
+        .await;
+
+        // removing the folder alone is refused while it has contents
+        let tree = add_tree();
+        let resp = actix_test::call_service(&app, delete(Some("0"))).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(tree.iter().all(|id| api.exists(*id)));
+
+        // dav-server walks the folder, one call for each entry
+        let resp = actix_test::call_service(&app, delete(None)).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert!(tree.iter().all(|id| !api.exists(*id)));
+        assert_eq!(api.delete_calls.swap(0, Ordering::SeqCst), 4);
+
+        let fs = api_fs(&url, "Veil7n").allow_recursive_delete(true);
+        let dav_server = writable_handler(&fs);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(fs))
+                .configure(|cfg| configure(cfg, dav_server, Routes::default())),
+        )
+        .await;
+
+        // the contents in a batch, then the folder
+        let tree = add_tree();
+        let resp = actix_test::call_service(&app, delete(None)).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert!(tree.iter().all(|id| !api.exists(*id)));
+        assert_eq!(api.delete_calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_load_tls_config_errors() {
+        let dir = std::env::temp_dir();
+        let empty = dir.join(format!("gofile-dav-{}-empty.pem", std::process::id()));
+        std::fs::write(&empty, "not a certificate").unwrap();
+
+        let missing = load_tls_config(&dir.join("missing.pem"), &empty).unwrap_err();
+        assert!(
+            missing
+                .to_string()
+                .contains("failed to read TLS certificate")
+        );
+
+        let no_cert = load_tls_config(&empty, &empty).unwrap_err();
+        assert!(no_cert.to_string().contains("no certificate found"));
+
+        std::fs::remove_file(&empty).unwrap();
+    }
+}
@@ -1,18 +1,120 @@
-use self_update::cargo_crate_version;
+use std::path::{Path, PathBuf};
 
-pub fn self_upgrade() -> anyhow::Result<()> {
-    let mut status_builder = self_update::backends::github::Update::configure();
+use anyhow::{Context, bail};
+use self_update::{
+    backends::github::{Update, UpdateBuilder},
+    cargo_crate_version,
+    version::bump_is_greater,
+};
 
-    status_builder.repo_owner("synthlace");
+fn configure() -> UpdateBuilder {
+    let mut status_builder = Update::configure();
 
     status_builder
+        .repo_owner("synthlace")
         .repo_name("gofile-dav")
         .bin_name("gofile-dav")
+        .current_version(cargo_crate_version!());
+
+    status_builder
+}
+
+/// Where the binary replaced by the last upgrade is kept, next to the running one
+fn backup_path(exe: &Path) -> PathBuf {
+    exe.with_extension("bak")
+}
+
+/// Installs the release tagged `version`, or the latest one, keeping the replaced
+/// binary for [`rollback`].
+pub fn self_upgrade(version: Option<&str>) -> anyhow::Result<()> {
+    let exe = std::env::current_exe().context("failed to locate the running binary")?;
+    let backup = backup_path(&exe);
+    // only replaces the previous backup once something was actually installed
+    let pending_backup = exe.with_extension("bak.new");
+    std::fs::copy(&exe, &pending_backup)
+        .with_context(|| format!("failed to back up {}", exe.display()))?;
+
+    let mut status_builder = configure();
+
+    if let Some(version) = version {
+        status_builder.target_version_tag(version);
+    }
+
+    let status = status_builder
         .show_download_progress(true)
         .no_confirm(true)
-        .current_version(cargo_crate_version!())
-        .build()?
-        .update()?;
+        .build()
+        .and_then(|updater| updater.update());
+
+    match status {
+        Ok(status) if status.updated() => {
+            std::fs::rename(&pending_backup, &backup)
+                .with_context(|| format!("failed to back up {}", exe.display()))?;
+            println!("The previous binary was kept as {}", backup.display());
+        }
+        status => {
+            let _ = std::fs::remove_file(&pending_backup);
+            status?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Puts back the binary replaced by the last upgrade.
+pub fn rollback() -> anyhow::Result<()> {
+    let exe = std::env::current_exe().context("failed to locate the running binary")?;
+    let backup = backup_path(&exe);
+
+    if !backup.exists() {
+        bail!(
+            "no previous binary to roll back to, {} doesn't exist",
+            backup.display()
+        );
+    }
+
+    self_update::self_replace::self_replace(&backup)
+        .with_context(|| format!("failed to restore {}", backup.display()))?;
+    std::fs::remove_file(&backup)
+        .with_context(|| format!("failed to remove {}", backup.display()))?;
+
+    println!("Rolled back to the binary replaced by the last upgrade");
 
     Ok(())
 }
+
+/// Prints whether a newer release exists without installing it, returning `true` if so.
+pub fn check_upgrade() -> anyhow::Result<bool> {
+    let updater = configure().build()?;
+    let current = updater.current_version();
+    let latest = updater.get_latest_release()?;
+
+    let newer = bump_is_greater(&current, &latest.version)?;
+    if newer {
+        println!(
+            "gofile-dav {} is available (current: {current})",
+            latest.version
+        );
+    } else {
+        println!("gofile-dav {current} is up to date");
+    }
+
+    Ok(newer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_path() {
+        assert_eq!(
+            backup_path(Path::new("/usr/local/bin/gofile-dav")),
+            Path::new("/usr/local/bin/gofile-dav.bak")
+        );
+        assert_eq!(
+            backup_path(Path::new("C:/tools/gofile-dav.exe")),
+            Path::new("C:/tools/gofile-dav.bak")
+        );
+    }
+}